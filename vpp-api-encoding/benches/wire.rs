@@ -0,0 +1,115 @@
+/*
+ * The wire format against bincode (big endian, fixed size integers),
+ * which is what the transport has been using so far.
+ *
+ *   cargo bench -p vpp-api-encoding
+ */
+use bincode::Options;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+use vpp_api_encoding::typ::*;
+use vpp_api_encoding::wire;
+
+/* the shape of sw_interface_details, give or take */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct InterfaceDetails {
+    context: u32,
+    sw_if_index: u32,
+    sup_sw_if_index: u32,
+    l2_address: FixedSizeArray<u8, typenum::U6>,
+    flags: u32,
+    link_speed: u32,
+    link_mtu: u16,
+    mtu: FixedSizeArray<u32, typenum::U4>,
+    sub_id: u32,
+    vtr_tag1: u32,
+    vtr_tag2: u32,
+    interface_name: FixedSizeString<typenum::U64>,
+    interface_dev_type: FixedSizeString<typenum::U64>,
+    tag: FixedSizeString<typenum::U64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CliInbandReply {
+    context: u32,
+    retval: i32,
+    reply: VariableSizeString,
+}
+
+fn encoder() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_big_endian()
+        .with_fixint_encoding()
+}
+
+fn details() -> InterfaceDetails {
+    InterfaceDetails {
+        context: 1,
+        sw_if_index: 2,
+        sup_sw_if_index: 2,
+        l2_address: vec![2, 0xfe, 0x12, 0x34, 0x56, 0x78].try_into().unwrap(),
+        flags: 3,
+        link_speed: 10_000_000,
+        link_mtu: 1500,
+        mtu: vec![9000, 0, 0, 0].try_into().unwrap(),
+        sub_id: 0,
+        vtr_tag1: 0,
+        vtr_tag2: 0,
+        interface_name: "host-vpp1out".try_into().unwrap(),
+        interface_dev_type: "af_packet".try_into().unwrap(),
+        tag: "".try_into().unwrap(),
+    }
+}
+
+fn cli_reply() -> CliInbandReply {
+    let text = "vpp v21.01 built by root on localhost at 2021-01-27T12:00:00\n".repeat(64);
+    CliInbandReply {
+        context: 1,
+        retval: 0,
+        reply: text.as_str().try_into().unwrap(),
+    }
+}
+
+fn bench_messages(c: &mut Criterion) {
+    let msg = details();
+    let data = wire::to_bytes(&msg).unwrap();
+    c.bench_function("details/encode/bincode", |b| {
+        b.iter(|| encoder().serialize(black_box(&msg)).unwrap())
+    });
+    c.bench_function("details/encode/wire", |b| {
+        b.iter(|| wire::to_bytes(black_box(&msg)).unwrap())
+    });
+    c.bench_function("details/decode/bincode", |b| {
+        b.iter(|| {
+            encoder()
+                .deserialize::<InterfaceDetails>(black_box(&data))
+                .unwrap()
+        })
+    });
+    c.bench_function("details/decode/wire", |b| {
+        b.iter(|| wire::from_bytes::<InterfaceDetails>(black_box(&data)).unwrap())
+    });
+
+    let msg = cli_reply();
+    let data = wire::to_bytes(&msg).unwrap();
+    c.bench_function("cli_reply/encode/bincode", |b| {
+        b.iter(|| encoder().serialize(black_box(&msg)).unwrap())
+    });
+    c.bench_function("cli_reply/encode/wire", |b| {
+        b.iter(|| wire::to_bytes(black_box(&msg)).unwrap())
+    });
+    c.bench_function("cli_reply/decode/bincode", |b| {
+        b.iter(|| {
+            encoder()
+                .deserialize::<CliInbandReply>(black_box(&data))
+                .unwrap()
+        })
+    });
+    c.bench_function("cli_reply/decode/wire", |b| {
+        b.iter(|| wire::from_bytes::<CliInbandReply>(black_box(&data)).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_messages);
+criterion_main!(benches);
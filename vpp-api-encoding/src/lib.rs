@@ -1 +1,4 @@
+pub mod net;
 pub mod typ;
+pub mod view;
+pub mod wire;
@@ -0,0 +1,336 @@
+/*
+ * The addresses in their usual text form.
+ *
+ * MacAddress is the mac_address of ethernet_types, which used to be a bare
+ * [u8; 6]; it is the same six bytes on the wire, but it prints and parses
+ * as "aa:bb:cc:dd:ee:ff". Ip4Address and Ip6Address are the same for the
+ * ip4_address and ip6_address of ip_types. The generated Address and
+ * Prefix convert to and from std::net themselves, using the helpers in
+ * here for the prefix strings.
+ */
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
+
+/*
+ * Newtypes over the bytes of an address: the same on the wire as the array,
+ * text in the human readable formats (JSON, YAML).
+ */
+macro_rules! octets_address {
+    ($name:ident, $len:expr) => {
+        #[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(pub [u8; $len]);
+
+        impl $name {
+            pub fn octets(&self) -> [u8; $len] {
+                self.0
+            }
+        }
+
+        impl From<[u8; $len]> for $name {
+            fn from(octets: [u8; $len]) -> Self {
+                $name(octets)
+            }
+        }
+
+        impl From<$name> for [u8; $len] {
+            fn from(address: $name) -> Self {
+                address.0
+            }
+        }
+
+        /* so that the code written for the bare array keeps working */
+        impl Deref for $name {
+            type Target = [u8; $len];
+            fn deref(&self) -> &[u8; $len] {
+                &self.0
+            }
+        }
+
+        impl DerefMut for $name {
+            fn deref_mut(&mut self) -> &mut [u8; $len] {
+                &mut self.0
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}({})", stringify!($name), self)
+            }
+        }
+
+        impl TryFrom<&str> for $name {
+            type Error = String;
+
+            fn try_from(s: &str) -> Result<Self, String> {
+                s.parse()
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                if serializer.is_human_readable() {
+                    serializer.collect_str(self)
+                } else {
+                    self.0.serialize(serializer)
+                }
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                if deserializer.is_human_readable() {
+                    deserialize_text(deserializer)
+                } else {
+                    <[u8; $len]>::deserialize(deserializer).map($name)
+                }
+            }
+        }
+    };
+}
+
+octets_address!(MacAddress, 6);
+octets_address!(Ip4Address, 4);
+octets_address!(Ip6Address, 16);
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let m = &self.0;
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            m[0], m[1], m[2], m[3], m[4], m[5]
+        )
+    }
+}
+
+/// Six hex bytes separated by ':' or '-'
+impl FromStr for MacAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut octets = [0u8; 6];
+        let mut parts = s.split([':', '-']);
+        for octet in octets.iter_mut() {
+            let part = parts.next().unwrap_or("");
+            if part.is_empty() || part.len() > 2 {
+                return Err(format!("invalid MAC address {:?}", s));
+            }
+            *octet =
+                u8::from_str_radix(part, 16).map_err(|_| format!("invalid MAC address {:?}", s))?;
+        }
+        if parts.next().is_some() {
+            return Err(format!("invalid MAC address {:?}", s));
+        }
+        Ok(MacAddress(octets))
+    }
+}
+
+impl fmt::Display for Ip4Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&Ipv4Addr::from(self.0), f)
+    }
+}
+
+impl FromStr for Ip4Address {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        s.parse::<Ipv4Addr>()
+            .map(Ip4Address::from)
+            .map_err(|e| format!("invalid address {:?}: {}", s, e))
+    }
+}
+
+impl From<Ipv4Addr> for Ip4Address {
+    fn from(ip: Ipv4Addr) -> Self {
+        Ip4Address(ip.octets())
+    }
+}
+
+impl From<Ip4Address> for Ipv4Addr {
+    fn from(ip: Ip4Address) -> Self {
+        Ipv4Addr::from(ip.0)
+    }
+}
+
+impl fmt::Display for Ip6Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&Ipv6Addr::from(self.0), f)
+    }
+}
+
+impl FromStr for Ip6Address {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        s.parse::<Ipv6Addr>()
+            .map(Ip6Address::from)
+            .map_err(|e| format!("invalid address {:?}: {}", s, e))
+    }
+}
+
+impl From<Ipv6Addr> for Ip6Address {
+    fn from(ip: Ipv6Addr) -> Self {
+        Ip6Address(ip.octets())
+    }
+}
+
+impl From<Ip6Address> for Ipv6Addr {
+    fn from(ip: Ip6Address) -> Self {
+        Ipv6Addr::from(ip.0)
+    }
+}
+
+/// Deserialize a type from its text form, for the human readable formats
+pub fn deserialize_text<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(D::Error::custom)
+}
+
+/*
+ * Not everything has a text form: an Address of a family the API does
+ * not know is its enum value and its union bytes, and it goes through
+ * JSON or YAML as those parts instead.
+ */
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextOrParts<P> {
+    Text(String),
+    Parts(P),
+}
+
+/// Deserialize a type from its text form, or from the parts it is made of
+pub fn deserialize_text_or_parts<'de, D, T, P, F>(
+    deserializer: D,
+    from_parts: F,
+) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+    P: Deserialize<'de>,
+    F: FnOnce(P) -> Result<T, String>,
+{
+    match TextOrParts::deserialize(deserializer)? {
+        TextOrParts::Text(s) => s.parse().map_err(D::Error::custom),
+        TextOrParts::Parts(parts) => from_parts(parts).map_err(D::Error::custom),
+    }
+}
+
+/// Check that a prefix length fits the address
+pub fn check_prefix_len(address: &IpAddr, len: u8) -> Result<(), String> {
+    let max = if address.is_ipv4() { 32 } else { 128 };
+    if len > max {
+        Err(format!("prefix length {} is too long for {}", len, address))
+    } else {
+        Ok(())
+    }
+}
+
+/// Split "address/len" into its parts; an address alone is a host prefix
+pub fn parse_prefix(s: &str) -> Result<(IpAddr, u8), String> {
+    let (address, len) = match s.find('/') {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+    let address: IpAddr = address
+        .parse()
+        .map_err(|e| format!("invalid prefix {:?}: {}", s, e))?;
+    let len = match len {
+        Some(len) => len
+            .parse()
+            .map_err(|e| format!("invalid prefix {:?}: {}", s, e))?,
+        None if address.is_ipv4() => 32,
+        None => 128,
+    };
+    check_prefix_len(&address, len)?;
+    Ok((address, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mac_address() {
+        let mac: MacAddress = "00:01:0A:bb:cc:ff".parse().unwrap();
+        assert_eq!(mac.octets(), [0, 1, 0xa, 0xbb, 0xcc, 0xff]);
+        assert_eq!(mac.to_string(), "00:01:0a:bb:cc:ff");
+        assert_eq!(MacAddress::try_from("00-01-0a-bb-cc-ff"), Ok(mac));
+        assert_eq!(mac[5], 0xff);
+
+        for bad in &[
+            "",
+            "00:01:0a:bb:cc",
+            "00:01:0a:bb:cc:ff:01",
+            "00:01:0a:bb:cc:fff",
+            "0:1:2:3:4:x",
+        ] {
+            assert!(bad.parse::<MacAddress>().is_err(), "{}", bad);
+        }
+
+        /* the same bytes as the [u8; 6] it replaces */
+        assert_eq!(crate::wire::to_bytes(&mac).unwrap(), mac.0.to_vec());
+        assert_eq!(crate::wire::from_bytes::<MacAddress>(&mac.0), Ok(mac));
+        /* and text in JSON */
+        let json = serde_json::to_string(&mac).unwrap();
+        assert_eq!(json, r#""00:01:0a:bb:cc:ff""#);
+        assert_eq!(serde_json::from_str::<MacAddress>(&json).unwrap(), mac);
+    }
+
+    #[test]
+    fn test_ip_address() {
+        let ip4: Ip4Address = "10.10.1.2".parse().unwrap();
+        assert_eq!(ip4.octets(), [10, 10, 1, 2]);
+        assert_eq!(Ipv4Addr::from(ip4), Ipv4Addr::new(10, 10, 1, 2));
+        assert_eq!(format!("{:?}", ip4), "Ip4Address(10.10.1.2)");
+        let ip6 = Ip6Address::from(Ipv6Addr::LOCALHOST);
+        assert_eq!(ip6.to_string(), "::1");
+        assert!(Ip4Address::try_from("::1").is_err());
+        assert_eq!(crate::wire::to_bytes(&ip4).unwrap(), vec![10, 10, 1, 2]);
+    }
+
+    #[test]
+    fn test_deserialize_text_or_parts() {
+        fn from_json(json: &str) -> Result<Ip4Address, serde_json::Error> {
+            let mut de = serde_json::Deserializer::from_str(json);
+            deserialize_text_or_parts(&mut de, |octets: [u8; 4]| Ok(Ip4Address(octets)))
+        }
+        assert_eq!(
+            from_json(r#""10.0.0.1""#).unwrap(),
+            Ip4Address([10, 0, 0, 1])
+        );
+        assert_eq!(
+            from_json("[10, 0, 0, 2]").unwrap(),
+            Ip4Address([10, 0, 0, 2])
+        );
+        assert!(from_json(r#""10.0.0""#).is_err());
+        assert!(from_json("[10, 0, 0]").is_err());
+    }
+
+    #[test]
+    fn test_parse_prefix() {
+        assert_eq!(
+            parse_prefix("10.10.1.0/24"),
+            Ok(("10.10.1.0".parse().unwrap(), 24))
+        );
+        assert_eq!(
+            parse_prefix("2001:db8::1"),
+            Ok(("2001:db8::1".parse().unwrap(), 128))
+        );
+        assert!(parse_prefix("10.10.1.0/33").is_err());
+        assert!(parse_prefix("2001:db8::/129").is_err());
+        assert!(parse_prefix("10.10.1.0/").is_err());
+        assert!(parse_prefix("10.10.1/24").is_err());
+    }
+}
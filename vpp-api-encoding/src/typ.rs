@@ -17,6 +17,76 @@ use std::fmt;
 use std::str::Utf8Error;
 use typenum::{U10, U256, U32, U64};
 
+/* the strings are text in the human readable formats, or their bytes if they are not UTF-8 */
+fn serialize_text<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    match std::str::from_utf8(data) {
+        Ok(s) => serializer.serialize_str(s),
+        Err(_) => serializer.collect_seq(data),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Text {
+    Str(String),
+    Bytes(Vec<u8>),
+}
+
+impl Text {
+    fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Text::Str(s) => s.into_bytes(),
+            Text::Bytes(b) => b,
+        }
+    }
+}
+
+/* a known number of bytes with nothing in front, read in one go by the wire format */
+struct PackedBytesSeed(usize);
+
+impl<'de> de::DeserializeSeed<'de> for PackedBytesSeed {
+    type Value = Vec<u8>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_tuple_struct(crate::wire::FIXED_BYTES, self.0, self)
+    }
+}
+
+impl<'de> Visitor<'de> for PackedBytesSeed {
+    type Value = Vec<u8>;
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{} bytes", self.0)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        /* the length comes off the wire, so do not trust it for the allocation */
+        let mut res: Vec<u8> = Vec::with_capacity(self.0.min(1024));
+        for i in 0..self.0 {
+            res.push(
+                seq.next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(i, &self))?,
+            );
+        }
+        Ok(res)
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        if v.len() != self.0 {
+            return Err(E::invalid_length(v.len(), &self));
+        }
+        Ok(v.to_vec())
+    }
+}
+
 #[derive(Clone, Default)]
 pub struct FixedSizeString<N: ArrayLength>(GenericArray<u8, N>);
 
@@ -88,6 +158,9 @@ impl<N: ArrayLength> Serialize for FixedSizeString<N> {
         S: Serializer,
     {
         let data = &self.0;
+        if serializer.is_human_readable() {
+            return serialize_text(data.split(|b| *b == 0).next().unwrap(), serializer);
+        }
 
         let mut len = data.len();
         let mut seq = serializer.serialize_tuple(len)?;
@@ -131,9 +204,36 @@ impl<'de, N: ArrayLength> Deserialize<'de> for FixedSizeString<N> {
 
                 return Ok(FixedSizeString(res));
             }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                let mut res: GenericArray<u8, N> = Default::default();
+                if v.len() != res.len() {
+                    return Err(E::invalid_length(v.len(), &self));
+                }
+                res.copy_from_slice(v);
+                Ok(FixedSizeString(res))
+            }
         }
 
-        return Ok(deserializer.deserialize_tuple(
+        if deserializer.is_human_readable() {
+            let data = Text::deserialize(deserializer)?.into_bytes();
+            let mut res: GenericArray<u8, N> = Default::default();
+            if data.len() > res.len() {
+                return Err(D::Error::custom(format!(
+                    "{} bytes do not fit in FixedSizeString[{}]",
+                    data.len(),
+                    res.len()
+                )));
+            }
+            res[..data.len()].copy_from_slice(&data);
+            return Ok(FixedSizeString(res));
+        }
+        /* a plain tuple for the other formats, a slice of the input for the wire one */
+        return Ok(deserializer.deserialize_tuple_struct(
+            crate::wire::FIXED_BYTES,
             N::to_u32() as usize,
             FixedSizeStringVisitor {
                 marker: PhantomData,
@@ -175,10 +275,13 @@ impl Serialize for VariableSizeString {
         S: Serializer,
     {
         let data = &self.0;
+        if serializer.is_human_readable() {
+            return serialize_text(data, serializer);
+        }
 
         let len: usize = data.len();
         let len_u32: u32 = len.try_into().unwrap();
-        let mut seq = serializer.serialize_tuple(len)?;
+        let mut seq = serializer.serialize_tuple(len + 1)?;
         seq.serialize_element(&len_u32)?;
         for b in data {
             seq.serialize_element(&b)?;
@@ -203,23 +306,35 @@ impl<'de> Deserialize<'de> for VariableSizeString {
             where
                 A: SeqAccess<'de>,
             {
-                let mut res: Vec<u8> = vec![];
                 let length: u32 = seq
                     .next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(0, &self))?;
+                let res = seq
+                    .next_element_seed(PackedBytesSeed(length as usize))?
                     .ok_or_else(|| serde::de::Error::invalid_length(1, &self))?;
 
-                for i in 0..length {
-                    res.push(
-                        seq.next_element()?
-                            .ok_or_else(|| serde::de::Error::invalid_length(1, &self))?,
-                    );
-                }
-
                 return Ok(VariableSizeString(res));
             }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(VariableSizeString(v.to_vec()))
+            }
         }
 
-        return Ok(deserializer.deserialize_tuple(1 << 31, VariableSizeStringVisitor)?);
+        if deserializer.is_human_readable() {
+            return Ok(VariableSizeString(
+                Text::deserialize(deserializer)?.into_bytes(),
+            ));
+        }
+        /* the length and then the bytes, which the wire format takes as one byte string */
+        return Ok(deserializer.deserialize_tuple_struct(
+            crate::wire::VARIABLE_BYTES,
+            2,
+            VariableSizeStringVisitor,
+        )?);
     }
 }
 
@@ -246,9 +361,8 @@ impl Serialize for F64 {
     where
         S: Serializer,
     {
-        let data = &self.0;
-        let out = f64::from_bits((data).to_bits().to_be());
-        serializer.serialize_f64(out)
+        /* the serializer puts it in network order, like VPP's clib_host_to_net_f64() */
+        serializer.serialize_f64(self.0)
     }
 }
 
@@ -257,23 +371,7 @@ impl<'de> Deserialize<'de> for F64 {
     where
         D: Deserializer<'de>,
     {
-        struct F64Visitor;
-        impl<'de> Visitor<'de> for F64Visitor {
-            type Value = F64;
-            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("F64")
-            }
-
-            fn visit_f64<E>(self, value: f64) -> Result<Self::Value, E>
-            where
-                E: de::Error,
-            {
-                let f64_value = f64::from_bits(value.to_bits().to_be());
-                Ok(F64(f64_value))
-            }
-        }
-
-        return Ok(deserializer.deserialize_f64(F64Visitor)?);
+        f64::deserialize(deserializer).map(F64)
     }
 }
 
@@ -464,9 +562,26 @@ impl<T: Serialize + Default + Debug, N: ArrayLength> Serialize for FixedSizeArra
     }
 }
 */
-#[derive(Copy, Clone, Default, Deserialize)]
-#[serde(bound = "T: Deserialize<'de> + Default")]
-pub struct SizedEnum<T, X>(T, PhantomData<X>); // This is the sized enum declaration, It's a unit struct
+#[derive(Copy, Clone, Default, PartialEq)]
+pub struct SizedEnum<T, X>(T, PhantomData<X>); // The enum T, on the wire as the container type X
+
+impl<T, X> SizedEnum<T, X> {
+    pub fn new(data: T) -> Self {
+        SizedEnum(data, PhantomData)
+    }
+    pub fn inner(&self) -> &T {
+        &self.0
+    }
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T, X> From<T> for SizedEnum<T, X> {
+    fn from(data: T) -> Self {
+        SizedEnum::new(data)
+    }
+}
 
 impl<T: Debug, X> fmt::Debug for SizedEnum<T, X> {
     // implement debug trait for sized enum
@@ -476,13 +591,17 @@ impl<T: Debug, X> fmt::Debug for SizedEnum<T, X> {
     }
 }
 
-impl<T: Serialize + Copy + AsU32 + Debug, X: Serialize + Debug + TryFrom<u32>> Serialize
+impl<T: Copy + AsU32 + Debug + Serialize, X: Serialize + Debug + TryFrom<u32>> Serialize
     for SizedEnum<T, X>
 {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
+        /* the container type only matters on the wire */
+        if serializer.is_human_readable() {
+            return self.0.serialize(serializer);
+        }
         let data = self.0.clone();
         let data_u32: u32 = AsU32::as_u32(data);
 
@@ -498,11 +617,34 @@ impl<T: Serialize + Copy + AsU32 + Debug, X: Serialize + Debug + TryFrom<u32>> S
     }
 }
 
-pub trait AsU32 {
-    fn as_u32(data: Self) -> u32;
+impl<'de, T: AsU32 + Deserialize<'de>, X: Deserialize<'de> + Into<u32>> Deserialize<'de>
+    for SizedEnum<T, X>
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            return T::deserialize(deserializer).map(SizedEnum::new);
+        }
+        /* the same one-element tuple as in serialize() */
+        let (data_x,): (X,) = Deserialize::deserialize(deserializer)?;
+        let data_u32: u32 = data_x.into();
+        match T::from_u32(data_u32) {
+            Some(data) => Ok(SizedEnum::new(data)),
+            None => Err(D::Error::custom(format!(
+                "invalid value {} for {}",
+                data_u32,
+                std::any::type_name::<T>()
+            ))),
+        }
+    }
 }
 
-/* FIXME: add deserializer for SizedEnum */
+pub trait AsU32: Sized {
+    fn as_u32(data: Self) -> u32;
+    fn from_u32(data: u32) -> Option<Self>;
+}
 
 #[derive(Clone, Default, Debug)]
 pub struct VariableSizeArray<T>(pub Vec<T>);
@@ -528,8 +670,15 @@ impl<T: Debug + Serialize> Serialize for VariableSizeArray<T> {
 use core::marker::PhantomData;
 use serde::de::{self, Deserializer, SeqAccess, Visitor};
 use std::fmt::Debug;
+use std::iter::FromIterator;
 // use std::fmt;
 
+/*
+ * Without a count field (see VariableSizeArraySeed for the arrays which
+ * have one) the array is the last one in the message and takes the rest
+ * of it, the way vpp_papi decodes such a trailing array. Only the wire
+ * format knows where the message ends.
+ */
 impl<'de, T: Deserialize<'de> + Debug> Deserialize<'de> for VariableSizeArray<T> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -538,218 +687,320 @@ impl<'de, T: Deserialize<'de> + Debug> Deserialize<'de> for VariableSizeArray<T>
         struct VariableSizeArrayVisitor<T> {
             marker: PhantomData<T>,
         }
-        let mut size = 0;
         impl<'de, T> Visitor<'de> for VariableSizeArrayVisitor<T>
         where
             T: Deserialize<'de> + Debug,
         {
             type Value = VariableSizeArray<T>;
             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("VariableSizeArray")
+                formatter.write_str("the rest of the message as a VariableSizeArray")
             }
 
-            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E>
             where
-                A: SeqAccess<'de>,
+                E: de::Error,
             {
-                let mut res: Vec<T> = vec![];
-                /*
-                                let length: u32 = seq
-                                    .next_element()?
-                                    .ok_or_else(|| serde::de::Error::invalid_length(1, &self))?;
-                */
-
-                loop {
-                    let nxt = seq.next_element();
-                    if nxt.is_err() {
-                        break;
-                    }
-                    let nxt = nxt?;
-                    if nxt.is_some() {
-                        let nxt: T = nxt.unwrap();
-                        res.push(nxt);
-                    }
-                }
-                /*
-                for i in 0..length {
-                    res.push(
-                        seq.next_element()?
-                            .ok_or_else(|| serde::de::Error::invalid_length(1, &self))?,
-                    );
-                }
-                */
-
-                return Ok(VariableSizeArray::<T>(res));
+                crate::wire::from_bytes_trailing(v)
+                    .map(VariableSizeArray)
+                    .map_err(E::custom)
             }
         }
 
-        return Ok(deserializer.deserialize_tuple(
-            1 << 31,
+        if deserializer.is_human_readable() {
+            return Vec::<T>::deserialize(deserializer).map(VariableSizeArray);
+        }
+        deserializer.deserialize_tuple_struct(
+            crate::wire::TRAILING,
+            0,
             VariableSizeArrayVisitor {
                 marker: PhantomData,
             },
-        )?);
+        )
+    }
+}
+
+impl<T> VariableSizeArray<T> {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Deserializes a VariableSizeArray of exactly "count" elements, the count
+/// coming from the field it is tied to (see the VppSerde derive)
+pub struct VariableSizeArraySeed<T> {
+    count: usize,
+    marker: PhantomData<T>,
+}
+
+impl<T> VariableSizeArraySeed<T> {
+    pub fn new(count: usize) -> Self {
+        VariableSizeArraySeed {
+            count,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> de::DeserializeSeed<'de> for VariableSizeArraySeed<T> {
+    type Value = VariableSizeArray<T>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_tuple(self.count, self)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for VariableSizeArraySeed<T> {
+    type Value = VariableSizeArray<T>;
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "VariableSizeArray of {} elements", self.count)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        /* the count comes off the wire, so do not trust it for the allocation */
+        let mut res: Vec<T> = Vec::with_capacity(self.count.min(1024));
+        for i in 0..self.count {
+            res.push(
+                seq.next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(i, &self))?,
+            );
+        }
+        Ok(VariableSizeArray(res))
     }
 }
 
-pub trait AsEnumFlag {
+pub trait AsEnumFlag: Sized {
     fn as_u32(data: &Self) -> u32;
-    fn from_u32(data: u32) -> Self;
+    /// None for the values which are not in the bindings
+    fn from_u32(data: u32) -> Option<Self>;
     fn size_of_enum_flag() -> u32;
 }
 
-#[derive(Clone, Debug)]
-pub struct EnumFlag<T: Clone + Debug + AsEnumFlag>(Vec<T>);
+/*
+ * A set of the flags of T, as the bits which go on the wire. Bits which
+ * T does not know (a newer VPP may add flags) are kept as they are, so
+ * that they make it back to VPP unchanged.
+ */
+pub struct EnumFlag<T> {
+    bits: u32,
+    marker: PhantomData<T>,
+}
 
-impl<T> EnumFlag<T>
-where
-    T: Clone + Debug + AsEnumFlag + std::cmp::PartialEq,
-{
-    pub fn contains(&self, other: T) -> bool {
-        self.0.contains(&other)
+impl<T: AsEnumFlag> EnumFlag<T> {
+    pub fn empty() -> Self {
+        EnumFlag::from_bits(0)
+    }
+    /// All the bits are kept, including the ones T does not know
+    pub fn from_bits(bits: u32) -> Self {
+        EnumFlag {
+            bits,
+            marker: PhantomData,
+        }
+    }
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+    /// Whether all the bits of the flag are set
+    pub fn contains(&self, flag: T) -> bool {
+        let bits = T::as_u32(&flag);
+        self.bits & bits == bits
+    }
+    pub fn insert(&mut self, flag: T) {
+        self.bits |= T::as_u32(&flag);
+    }
+    pub fn remove(&mut self, flag: T) {
+        self.bits &= !T::as_u32(&flag);
+    }
+    /// The flags which are set, one per bit; masks spanning several bits are not listed
+    pub fn iter(&self) -> impl Iterator<Item = T> {
+        let bits = self.bits;
+        (0..32)
+            .map(|i| 1u32 << i)
+            .filter(move |bit| bits & bit != 0)
+            .filter_map(T::from_u32)
+    }
+    /// The bits which are set but are not a flag of T
+    pub fn unknown_bits(&self) -> u32 {
+        (0..32)
+            .map(|i| 1u32 << i)
+            .filter(|bit| self.bits & bit != 0 && T::from_u32(*bit).is_none())
+            .fold(0, |acc, bit| acc | bit)
+    }
+    pub fn sum(&self) -> u32 {
+        self.bits
     }
 }
 
-impl<T: Clone + Debug + AsEnumFlag> TryFrom<Vec<T>> for EnumFlag<T> {
-    type Error = String;
+impl<T> Clone for EnumFlag<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for EnumFlag<T> {}
 
-    fn try_from(value: Vec<T>) -> Result<Self, Self::Error> {
-        let mut out = vec![];
-        for i in 0..value.len() {
-            out.push(value[i].clone());
-        }
-        Ok(EnumFlag(out))
+impl<T> PartialEq for EnumFlag<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits == other.bits
     }
 }
-impl<T: Clone + Debug + AsEnumFlag> Default for EnumFlag<T> {
+impl<T> Eq for EnumFlag<T> {}
+
+impl<T> std::hash::Hash for EnumFlag<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.bits.hash(state)
+    }
+}
+
+impl<T: AsEnumFlag> Default for EnumFlag<T> {
     fn default() -> Self {
-        vec![].try_into().unwrap()
+        EnumFlag::empty()
     }
 }
-impl<T: Clone + Debug + AsEnumFlag> EnumFlag<T> {
-    pub fn sum(&self) -> u32 {
-        let mut sum: u32 = 0;
-        for x in &self.0 {
-            sum = sum + T::as_u32(x); // AsU8 and AsU32 trait needed for using T
+
+impl<T: AsEnumFlag + Debug> Debug for EnumFlag<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct UnknownBits(u32);
+        impl Debug for UnknownBits {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{:#x}", self.0)
+            }
         }
-        sum
+        let mut set = f.debug_set();
+        set.entries(self.iter());
+        match self.unknown_bits() {
+            0 => {}
+            bits => {
+                set.entry(&UnknownBits(bits));
+            }
+        }
+        set.finish()
+    }
+}
+
+impl<T: AsEnumFlag> From<T> for EnumFlag<T> {
+    fn from(flag: T) -> Self {
+        EnumFlag::from_bits(T::as_u32(&flag))
+    }
+}
+
+impl<T: AsEnumFlag> FromIterator<T> for EnumFlag<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut flags = EnumFlag::empty();
+        flags.extend(iter);
+        flags
     }
 }
-impl<T: Clone + Debug + AsEnumFlag> Serialize for EnumFlag<T> {
+
+impl<T: AsEnumFlag> Extend<T> for EnumFlag<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for flag in iter {
+            self.insert(flag);
+        }
+    }
+}
+
+impl<T: AsEnumFlag> TryFrom<Vec<T>> for EnumFlag<T> {
+    type Error = String;
+
+    fn try_from(value: Vec<T>) -> Result<Self, Self::Error> {
+        Ok(value.into_iter().collect())
+    }
+}
+
+/* the set operations, with another set or with a single flag */
+macro_rules! enum_flag_op {
+    ($op:ident, $fn:ident, $op_assign:ident, $fn_assign:ident, |$a:ident, $b:ident| $e:expr) => {
+        impl<T: AsEnumFlag> std::ops::$op for EnumFlag<T> {
+            type Output = Self;
+            fn $fn(self, other: Self) -> Self {
+                let ($a, $b) = (self.bits, other.bits);
+                EnumFlag::from_bits($e)
+            }
+        }
+        impl<T: AsEnumFlag> std::ops::$op<T> for EnumFlag<T> {
+            type Output = Self;
+            fn $fn(self, other: T) -> Self {
+                std::ops::$op::$fn(self, EnumFlag::from(other))
+            }
+        }
+        impl<T: AsEnumFlag> std::ops::$op_assign for EnumFlag<T> {
+            fn $fn_assign(&mut self, other: Self) {
+                *self = std::ops::$op::$fn(*self, other);
+            }
+        }
+        impl<T: AsEnumFlag> std::ops::$op_assign<T> for EnumFlag<T> {
+            fn $fn_assign(&mut self, other: T) {
+                *self = std::ops::$op::$fn(*self, other);
+            }
+        }
+    };
+}
+
+enum_flag_op!(BitOr, bitor, BitOrAssign, bitor_assign, |a, b| a | b);
+enum_flag_op!(BitAnd, bitand, BitAndAssign, bitand_assign, |a, b| a & b);
+enum_flag_op!(BitXor, bitxor, BitXorAssign, bitxor_assign, |a, b| a ^ b);
+enum_flag_op!(Sub, sub, SubAssign, sub_assign, |a, b| a & !b);
+
+/* in JSON and YAML, a list of the flag names, with any unknown bits as a number */
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum FlagOrBits<T> {
+    Bits(u32),
+    Flag(T),
+}
+
+impl<T: AsEnumFlag + Serialize> Serialize for EnumFlag<T> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
+        if serializer.is_human_readable() {
+            let unknown = match self.unknown_bits() {
+                0 => None,
+                bits => Some(FlagOrBits::Bits(bits)),
+            };
+            return serializer.collect_seq(self.iter().map(FlagOrBits::Flag).chain(unknown));
+        }
         let size: u32 = T::size_of_enum_flag();
         match size {
-            32 => serializer.serialize_u32(self.sum()),
-            16 => serializer.serialize_u16(self.sum() as u16),
-            8 => serializer.serialize_u8(self.sum() as u8),
+            32 => serializer.serialize_u32(self.bits),
+            16 => serializer.serialize_u16(self.bits as u16),
+            8 => serializer.serialize_u8(self.bits as u8),
             _ => panic!("EnumFlags do not support {} bit type flag", size),
         }
     }
 }
-impl<'de, T: Debug + Clone + AsEnumFlag + Deserialize<'de>> Deserialize<'de> for EnumFlag<T> {
+impl<'de, T: AsEnumFlag + Deserialize<'de>> Deserialize<'de> for EnumFlag<T> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
-        struct EnumFlagVisitor<T> {
-            marker: PhantomData<T>,
-        }
-        // let mut size = 0;
-        impl<'de, T> Visitor<'de> for EnumFlagVisitor<T>
-        where
-            T: Debug + Clone + AsEnumFlag,
-        {
-            type Value = EnumFlag<T>;
-            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("EnumFlag")
-            }
-
-            fn visit_u32<E>(self, v: u32) -> Result<Self::Value, E>
-            where
-                E: Error,
-            {
-                let mut res: Vec<T> = vec![];
-                trace!("{}", v);
-                /* let number = u32::pow(2, v);
-                let enum_d: T = AsU32::from_u32(number);
-                res.push(enum_d);*/
-                let felix = format!("{:032b}", v);
-                let char_vec: Vec<char> = felix.chars().collect();
-                for c in 0..char_vec.len() {
-                    if char_vec[c] == '1' {
-                        let size: u32 = char_vec.len() as u32 - c as u32 - 1;
-                        let number = u32::pow(2, size);
-                        let enum_d = T::from_u32(number);
-                        res.push(enum_d);
-                    }
-                }
-                return Ok(EnumFlag::<T>(res));
-            }
-            fn visit_u16<E>(self, v: u16) -> Result<Self::Value, E>
-            where
-                E: Error,
-            {
-                let mut res: Vec<T> = vec![];
-                trace!("{}", v);
-                /* let number = u32::pow(2, v);
-                let enum_d: T = AsU32::from_u32(number);
-                res.push(enum_d);*/
-                let felix = format!("{:016b}", v);
-                let char_vec: Vec<char> = felix.chars().collect();
-                for c in 0..char_vec.len() {
-                    if char_vec[c] == '1' {
-                        let size: u32 = char_vec.len() as u32 - c as u32 - 1;
-                        let number = u32::pow(2, size);
-                        let enum_d = T::from_u32(number);
-                        res.push(enum_d);
-                    }
-                }
-                return Ok(EnumFlag::<T>(res));
-            }
-            fn visit_u8<E>(self, v: u8) -> Result<Self::Value, E>
-            where
-                E: Error,
-            {
-                let mut res: Vec<T> = vec![];
-                trace!("{}", v);
-                /* let number = u32::pow(2, v);
-                let enum_d: T = AsU32::from_u32(number);
-                res.push(enum_d);*/
-                let felix = format!("{:08b}", v);
-                let char_vec: Vec<char> = felix.chars().collect();
-                for c in 0..char_vec.len() {
-                    if char_vec[c] == '1' {
-                        let size: u32 = char_vec.len() as u32 - c as u32 - 1;
-                        let number = u32::pow(2, size);
-                        let enum_d = T::from_u32(number);
-                        res.push(enum_d);
-                    }
-                }
-                return Ok(EnumFlag::<T>(res));
-            }
+        if deserializer.is_human_readable() {
+            let list = Vec::<FlagOrBits<T>>::deserialize(deserializer)?;
+            return Ok(list.into_iter().fold(EnumFlag::empty(), |acc, x| match x {
+                FlagOrBits::Bits(bits) => acc | EnumFlag::from_bits(bits),
+                FlagOrBits::Flag(flag) => acc | flag,
+            }));
         }
         let size: u32 = T::size_of_enum_flag();
-        match size {
-            32 => {
-                return Ok(deserializer.deserialize_u32(EnumFlagVisitor {
-                    marker: PhantomData,
-                })?);
-            }
-            16 => {
-                return Ok(deserializer.deserialize_u16(EnumFlagVisitor {
-                    marker: PhantomData,
-                })?);
-            }
-            8 => {
-                return Ok(deserializer.deserialize_u8(EnumFlagVisitor {
-                    marker: PhantomData,
-                })?);
-            }
+        let bits = match size {
+            32 => u32::deserialize(deserializer)?,
+            16 => u16::deserialize(deserializer)? as u32,
+            8 => u8::deserialize(deserializer)? as u32,
             _ => panic!("Deserializing not supported for {} bit set flags", size),
-        }
+        };
+        Ok(EnumFlag::from_bits(bits))
     }
 }
 /*
@@ -778,3 +1029,154 @@ pub struct TunnelFlags {}
 
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
 pub struct IpFlowHashConfig {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bincode::Options;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    enum Af {
+        Ip4 = 0,
+        Ip6 = 1,
+    }
+    impl AsU32 for Af {
+        fn as_u32(data: Self) -> u32 {
+            data as u32
+        }
+        fn from_u32(data: u32) -> Option<Self> {
+            match data {
+                0 => Some(Af::Ip4),
+                1 => Some(Af::Ip6),
+                _ => None,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Prefix {
+        af: SizedEnum<Af, u8>,
+        len: u16,
+        kind: SizedEnum<Af, u16>,
+    }
+
+    fn encoder() -> impl Options {
+        bincode::DefaultOptions::new()
+            .with_big_endian()
+            .with_fixint_encoding()
+    }
+
+    #[test]
+    fn test_sized_enum_round_trip() {
+        let p = Prefix {
+            af: Af::Ip6.into(),
+            len: 64,
+            kind: SizedEnum::new(Af::Ip6),
+        };
+        let data = encoder().serialize(&p).unwrap();
+        assert_eq!(data, vec![1, 0, 64, 0, 1]);
+        let decoded: Prefix = encoder().deserialize(&data).unwrap();
+        assert_eq!(decoded, p);
+        assert_eq!(*decoded.af.inner(), Af::Ip6);
+        assert_eq!(decoded.kind.into_inner(), Af::Ip6);
+
+        assert!(encoder().deserialize::<Prefix>(&[7, 0, 64, 0, 1]).is_err());
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    enum Status {
+        AdminUp = 1,
+        LinkUp = 2,
+    }
+    impl AsEnumFlag for Status {
+        fn as_u32(data: &Self) -> u32 {
+            *data as u32
+        }
+        fn from_u32(data: u32) -> Option<Self> {
+            match data {
+                1 => Some(Status::AdminUp),
+                2 => Some(Status::LinkUp),
+                _ => None,
+            }
+        }
+        fn size_of_enum_flag() -> u32 {
+            16
+        }
+    }
+
+    #[test]
+    fn test_enum_flag() {
+        let mut flags: EnumFlag<Status> = vec![Status::LinkUp].try_into().unwrap();
+        assert!(!flags.contains(Status::AdminUp));
+        flags |= Status::AdminUp;
+        assert_eq!(flags.bits(), 3);
+        assert_eq!(
+            flags.iter().collect::<Vec<_>>(),
+            [Status::AdminUp, Status::LinkUp]
+        );
+        assert_eq!(flags - Status::LinkUp, Status::AdminUp.into());
+        assert_eq!(
+            flags & EnumFlag::from(Status::LinkUp),
+            Status::LinkUp.into()
+        );
+        flags.remove(Status::AdminUp);
+        assert_eq!(flags, [Status::LinkUp].iter().copied().collect());
+        assert!((flags ^ Status::LinkUp).is_empty());
+
+        /* a flag from a newer VPP makes it back to the wire */
+        let data = encoder().serialize(&0x42u16).unwrap();
+        let flags: EnumFlag<Status> = encoder().deserialize(&data).unwrap();
+        assert!(flags.contains(Status::LinkUp));
+        assert_eq!(flags.unknown_bits(), 0x40);
+        assert_eq!(format!("{:?}", flags), "{LinkUp, 0x40}");
+        assert_eq!(encoder().serialize(&flags).unwrap(), data);
+
+        let json = serde_json::to_string(&flags).unwrap();
+        assert_eq!(json, r#"["LinkUp",64]"#);
+        assert_eq!(
+            serde_json::from_str::<EnumFlag<Status>>(&json).unwrap(),
+            flags
+        );
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct Details {
+        name: FixedSizeString<typenum::U8>,
+        descr: VariableSizeString,
+        af: SizedEnum<Af, u8>,
+        metric: F64,
+    }
+
+    #[test]
+    fn test_human_readable() {
+        let d = Details {
+            name: "eth0".try_into().unwrap(),
+            descr: "uplink".try_into().unwrap(),
+            af: Af::Ip6.into(),
+            metric: F64(1.5),
+        };
+        let json = serde_json::to_string(&d).unwrap();
+        assert_eq!(
+            json,
+            r#"{"name":"eth0","descr":"uplink","af":"Ip6","metric":1.5}"#
+        );
+        let decoded: Details = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            encoder().serialize(&decoded).unwrap(),
+            encoder().serialize(&d).unwrap()
+        );
+
+        /* not UTF-8, so the bytes */
+        let d = Details {
+            descr: VariableSizeString(vec![0xff, 0x41]),
+            ..d
+        };
+        let json = serde_json::to_string(&d).unwrap();
+        assert!(json.contains(r#""descr":[255,65]"#));
+        let decoded: Details = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.descr.0, vec![0xff, 0x41]);
+
+        let long = r#"{"name":"too long a name","descr":"","af":"Ip4","metric":0}"#;
+        assert!(serde_json::from_str::<Details>(long).is_err());
+    }
+}
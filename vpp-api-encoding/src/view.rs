@@ -0,0 +1,544 @@
+/*
+ * Borrowed views of the messages in a receive buffer.
+ *
+ * Decoding a big dump (a full FIB, say) into the owned structs copies
+ * every string and array of every entry. A view instead keeps a slice of
+ * the received message and the offsets of its fields, and decodes a field
+ * only when it is asked for; the strings and the u8 arrays are never
+ * copied. The generator emits a view for each type and message (the
+ * "views" feature of the generated crate), built on the WireView impls
+ * in here, with decode() to get the usual struct from it.
+ *
+ * The layout is the one of the wire module, and anything that fails
+ * wire::from_bytes() also fails the view.
+ */
+use crate::typ::*;
+use crate::wire::{self, Error, Result};
+use generic_array::ArrayLength;
+use serde::Deserialize;
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::Utf8Error;
+
+/// How a type is looked at in place within a buffer
+pub trait WireView<'a>: Sized {
+    /// What an accessor returns: a copy of the small values, a view of the others
+    type View;
+
+    /// The size on the wire, if it is the same for all values
+    const FIXED_SIZE: Option<usize> = None;
+
+    /// Check the value at the start of the buffer and return its size on the wire
+    fn wire_size(buf: &'a [u8]) -> Result<usize>;
+
+    /// The view of a value which wire_size() has checked, buf being just that value
+    fn view(buf: &'a [u8]) -> Self::View;
+}
+
+/// The end of the field of type T which starts at the offset "start"
+pub fn field_end<'a, T: WireView<'a>>(buf: &'a [u8], start: usize) -> Result<usize> {
+    Ok(start + T::wire_size(&buf[start..])?)
+}
+
+/// The end of an array of "count" elements of type T starting at the offset "start"
+pub fn counted_end<'a, T: WireView<'a>>(
+    buf: &'a [u8],
+    start: usize,
+    count: usize,
+) -> Result<usize> {
+    Ok(start + ArrayView::<T>::new(&buf[start..], Some(count))?.wire_size())
+}
+
+/// The sum of the fixed sizes, if all of them are fixed
+pub const fn fixed_size_sum(sizes: &[Option<usize>]) -> Option<usize> {
+    let mut sum = 0;
+    let mut i = 0;
+    while i < sizes.len() {
+        match sizes[i] {
+            Some(size) => sum += size,
+            None => return None,
+        }
+        i += 1;
+    }
+    Some(sum)
+}
+
+fn fixed(buf: &[u8], size: usize) -> Result<usize> {
+    if buf.len() < size {
+        Err(Error::Eof)
+    } else {
+        Ok(size)
+    }
+}
+
+macro_rules! int_view {
+    ($($t:ty),*) => {
+        $(
+            impl<'a> WireView<'a> for $t {
+                type View = $t;
+                const FIXED_SIZE: Option<usize> = Some(std::mem::size_of::<$t>());
+
+                fn wire_size(buf: &'a [u8]) -> Result<usize> {
+                    fixed(buf, std::mem::size_of::<$t>())
+                }
+                fn view(buf: &'a [u8]) -> $t {
+                    let mut bytes = [0; std::mem::size_of::<$t>()];
+                    bytes.copy_from_slice(&buf[..std::mem::size_of::<$t>()]);
+                    <$t>::from_be_bytes(bytes)
+                }
+            }
+        )*
+    };
+}
+
+int_view!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl<'a> WireView<'a> for bool {
+    type View = bool;
+    const FIXED_SIZE: Option<usize> = Some(1);
+
+    fn wire_size(buf: &'a [u8]) -> Result<usize> {
+        match buf.first() {
+            Some(0) | Some(1) => Ok(1),
+            Some(b) => Err(Error::InvalidBool(*b)),
+            None => Err(Error::Eof),
+        }
+    }
+    fn view(buf: &'a [u8]) -> bool {
+        buf[0] != 0
+    }
+}
+
+impl<'a> WireView<'a> for f64 {
+    type View = f64;
+    const FIXED_SIZE: Option<usize> = Some(8);
+
+    fn wire_size(buf: &'a [u8]) -> Result<usize> {
+        fixed(buf, 8)
+    }
+    fn view(buf: &'a [u8]) -> f64 {
+        f64::from_bits(u64::view(buf))
+    }
+}
+
+/// A string in place; the fixed size ones without the NULs padding them
+#[derive(Clone, Copy, PartialEq)]
+pub struct StrView<'a>(&'a [u8]);
+
+impl<'a> StrView<'a> {
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+    pub fn to_str(&self) -> std::result::Result<&'a str, Utf8Error> {
+        std::str::from_utf8(self.0)
+    }
+}
+
+impl fmt::Debug for StrView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", String::from_utf8_lossy(self.0))
+    }
+}
+
+impl fmt::Display for StrView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(self.0))
+    }
+}
+
+impl PartialEq<&str> for StrView<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl<'a, N: ArrayLength> WireView<'a> for FixedSizeString<N> {
+    type View = StrView<'a>;
+    const FIXED_SIZE: Option<usize> = Some(N::USIZE);
+
+    fn wire_size(buf: &'a [u8]) -> Result<usize> {
+        fixed(buf, N::USIZE)
+    }
+    fn view(buf: &'a [u8]) -> StrView<'a> {
+        let s = &buf[..N::USIZE];
+        let len = s.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+        StrView(&s[..len])
+    }
+}
+
+impl<'a> WireView<'a> for VariableSizeString {
+    type View = StrView<'a>;
+
+    fn wire_size(buf: &'a [u8]) -> Result<usize> {
+        let len = u32::view(&buf[..fixed(buf, 4)?]) as usize;
+        fixed(buf, 4 + len)
+    }
+    fn view(buf: &'a [u8]) -> StrView<'a> {
+        StrView(&buf[4..])
+    }
+}
+
+/// An array in place, its elements viewed one at a time
+pub struct ArrayView<'a, T> {
+    buf: &'a [u8],
+    len: usize,
+    marker: PhantomData<T>,
+}
+
+impl<T> Clone for ArrayView<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ArrayView<'_, T> {}
+
+impl<'a, T: WireView<'a>> ArrayView<'a, T> {
+    /// The "count" elements at the start of buf, or all of buf for None
+    pub fn new(buf: &'a [u8], count: Option<usize>) -> Result<Self> {
+        if let (Some(size), Some(count)) = (T::FIXED_SIZE, count) {
+            let total = size.checked_mul(count).ok_or(Error::Eof)?;
+            fixed(buf, total)?;
+            return Ok(ArrayView {
+                buf: &buf[..total],
+                len: count,
+                marker: PhantomData,
+            });
+        }
+        let mut ofs = 0;
+        let mut len = 0;
+        while count.map_or(ofs < buf.len(), |count| len < count) {
+            let size = T::wire_size(&buf[ofs..])?;
+            if size == 0 && count.is_none() {
+                break;
+            }
+            ofs += size;
+            len += 1;
+        }
+        Ok(ArrayView {
+            buf: &buf[..ofs],
+            len,
+            marker: PhantomData,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn wire_size(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<T::View> {
+        match T::FIXED_SIZE {
+            Some(size) if index < self.len => {
+                Some(T::view(&self.buf[index * size..(index + 1) * size]))
+            }
+            _ => self.iter().nth(index),
+        }
+    }
+
+    pub fn iter(&self) -> ArrayIter<'a, T> {
+        ArrayIter {
+            buf: self.buf,
+            remaining: self.len,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a> ArrayView<'a, u8> {
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.buf
+    }
+}
+
+impl<'a, T: WireView<'a>> fmt::Debug for ArrayView<'a, T>
+where
+    T::View: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, T: WireView<'a>> IntoIterator for ArrayView<'a, T> {
+    type Item = T::View;
+    type IntoIter = ArrayIter<'a, T>;
+
+    fn into_iter(self) -> ArrayIter<'a, T> {
+        self.iter()
+    }
+}
+
+pub struct ArrayIter<'a, T> {
+    buf: &'a [u8],
+    remaining: usize,
+    marker: PhantomData<T>,
+}
+
+impl<'a, T: WireView<'a>> Iterator for ArrayIter<'a, T> {
+    type Item = T::View;
+
+    fn next(&mut self) -> Option<T::View> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let size = T::wire_size(self.buf).expect("checked by ArrayView::new()");
+        let (elem, rest) = self.buf.split_at(size);
+        self.buf = rest;
+        self.remaining -= 1;
+        Some(T::view(elem))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T: WireView<'a>> ExactSizeIterator for ArrayIter<'a, T> {}
+
+impl<'a, T: WireView<'a> + Default + fmt::Debug, N: ArrayLength> WireView<'a>
+    for FixedSizeArray<T, N>
+{
+    type View = ArrayView<'a, T>;
+    const FIXED_SIZE: Option<usize> = match T::FIXED_SIZE {
+        Some(size) => Some(size * N::USIZE),
+        None => None,
+    };
+
+    fn wire_size(buf: &'a [u8]) -> Result<usize> {
+        Ok(ArrayView::<T>::new(buf, Some(N::USIZE))?.wire_size())
+    }
+    fn view(buf: &'a [u8]) -> ArrayView<'a, T> {
+        ArrayView::new(buf, Some(N::USIZE)).expect("checked by wire_size()")
+    }
+}
+
+impl<'a, T: WireView<'a>, const N: usize> WireView<'a> for [T; N] {
+    type View = ArrayView<'a, T>;
+    const FIXED_SIZE: Option<usize> = match T::FIXED_SIZE {
+        Some(size) => Some(size * N),
+        None => None,
+    };
+
+    fn wire_size(buf: &'a [u8]) -> Result<usize> {
+        Ok(ArrayView::<T>::new(buf, Some(N))?.wire_size())
+    }
+    fn view(buf: &'a [u8]) -> ArrayView<'a, T> {
+        ArrayView::new(buf, Some(N)).expect("checked by wire_size()")
+    }
+}
+
+/* a trailing array without a count field takes the rest of the message */
+impl<'a, T: WireView<'a>> WireView<'a> for VariableSizeArray<T> {
+    type View = ArrayView<'a, T>;
+
+    fn wire_size(buf: &'a [u8]) -> Result<usize> {
+        Ok(ArrayView::<T>::new(buf, None)?.wire_size())
+    }
+    fn view(buf: &'a [u8]) -> ArrayView<'a, T> {
+        ArrayView::new(buf, None).expect("checked by wire_size()")
+    }
+}
+
+impl<'a, T, X> WireView<'a> for SizedEnum<T, X>
+where
+    T: AsU32 + Copy + Deserialize<'a>,
+    X: WireView<'a> + Deserialize<'a> + Into<u32>,
+{
+    type View = T;
+    const FIXED_SIZE: Option<usize> = X::FIXED_SIZE;
+
+    fn wire_size(buf: &'a [u8]) -> Result<usize> {
+        wire::from_bytes_prefix::<Self>(buf).map(|(_, size)| size)
+    }
+    fn view(buf: &'a [u8]) -> T {
+        wire::from_bytes::<Self>(buf)
+            .expect("checked by wire_size()")
+            .into_inner()
+    }
+}
+
+impl<'a, T> WireView<'a> for EnumFlag<T>
+where
+    T: AsEnumFlag + Deserialize<'a>,
+{
+    type View = EnumFlag<T>;
+
+    fn wire_size(buf: &'a [u8]) -> Result<usize> {
+        fixed(buf, T::size_of_enum_flag() as usize / 8)
+    }
+    fn view(buf: &'a [u8]) -> EnumFlag<T> {
+        wire::from_bytes(buf).expect("checked by wire_size()")
+    }
+}
+
+/// WireView for a type which is small enough to be just decoded, like the enums and unions
+#[macro_export]
+macro_rules! owned_wire_view {
+    ($t:ty) => {
+        impl<'a> $crate::view::WireView<'a> for $t {
+            type View = $t;
+
+            fn wire_size(buf: &'a [u8]) -> $crate::wire::Result<usize> {
+                $crate::wire::from_bytes_prefix::<$t>(buf).map(|(_, size)| size)
+            }
+            fn view(buf: &'a [u8]) -> $t {
+                $crate::wire::from_bytes(buf).expect("checked by wire_size()")
+            }
+        }
+    };
+}
+
+owned_wire_view!(F64);
+
+/* the addresses of the net module, fixed size and copied out like the integers */
+macro_rules! octets_view {
+    ($($t:ident),*) => {
+        $(
+            impl<'a> WireView<'a> for crate::net::$t {
+                type View = crate::net::$t;
+                const FIXED_SIZE: Option<usize> = Some(std::mem::size_of::<crate::net::$t>());
+
+                fn wire_size(buf: &'a [u8]) -> Result<usize> {
+                    fixed(buf, std::mem::size_of::<crate::net::$t>())
+                }
+                fn view(buf: &'a [u8]) -> crate::net::$t {
+                    let mut octets = crate::net::$t::default();
+                    let len = octets.len();
+                    octets.copy_from_slice(&buf[..len]);
+                    octets
+                }
+            }
+        )*
+    };
+}
+
+octets_view!(MacAddress, Ip4Address, Ip6Address);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+    use std::convert::TryInto;
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct RouteDetails {
+        context: u32,
+        tag: FixedSizeString<typenum::U8>,
+        descr: VariableSizeString,
+        is_add: bool,
+        n_hops: u8,
+        hops: VariableSizeArray<u16>,
+    }
+
+    /* what the generator emits for the struct above */
+    #[derive(Debug, Clone, Copy)]
+    struct RouteDetailsView<'a> {
+        buf: &'a [u8],
+        ofs: [usize; 7],
+    }
+
+    impl<'a> RouteDetailsView<'a> {
+        fn new(buf: &'a [u8]) -> Result<Self> {
+            let mut ofs = [0; 7];
+            ofs[1] = field_end::<u32>(buf, ofs[0])?;
+            ofs[2] = field_end::<FixedSizeString<typenum::U8>>(buf, ofs[1])?;
+            ofs[3] = field_end::<VariableSizeString>(buf, ofs[2])?;
+            ofs[4] = field_end::<bool>(buf, ofs[3])?;
+            ofs[5] = field_end::<u8>(buf, ofs[4])?;
+            let count = <u8 as WireView>::view(&buf[ofs[4]..ofs[5]]) as usize;
+            ofs[6] = counted_end::<u16>(buf, ofs[5], count)?;
+            Ok(RouteDetailsView {
+                buf: &buf[..ofs[6]],
+                ofs,
+            })
+        }
+        fn context(&self) -> u32 {
+            <u32 as WireView>::view(&self.buf[self.ofs[0]..self.ofs[1]])
+        }
+        fn tag(&self) -> StrView<'a> {
+            <FixedSizeString<typenum::U8> as WireView>::view(&self.buf[self.ofs[1]..self.ofs[2]])
+        }
+        fn descr(&self) -> StrView<'a> {
+            <VariableSizeString as WireView>::view(&self.buf[self.ofs[2]..self.ofs[3]])
+        }
+        fn is_add(&self) -> bool {
+            <bool as WireView>::view(&self.buf[self.ofs[3]..self.ofs[4]])
+        }
+        fn hops(&self) -> ArrayView<'a, u16> {
+            <VariableSizeArray<u16> as WireView>::view(&self.buf[self.ofs[5]..self.ofs[6]])
+        }
+        fn decode(&self) -> Result<RouteDetails> {
+            wire::from_bytes(self.buf)
+        }
+    }
+
+    fn sample() -> RouteDetails {
+        RouteDetails {
+            context: 42,
+            tag: "fib".try_into().unwrap(),
+            descr: "default route".try_into().unwrap(),
+            is_add: true,
+            n_hops: 3,
+            hops: VariableSizeArray(vec![10, 20, 30]),
+        }
+    }
+
+    #[test]
+    fn test_view_fields() {
+        let data = wire::to_bytes(&sample()).unwrap();
+        let view = RouteDetailsView::new(&data).unwrap();
+        assert_eq!(view.context(), 42);
+        assert_eq!(view.tag(), "fib");
+        assert_eq!(view.descr().to_str().unwrap(), "default route");
+        assert!(view.is_add());
+        let hops = view.hops();
+        assert_eq!(hops.len(), 3);
+        assert_eq!(hops.get(1), Some(20));
+        assert_eq!(hops.get(3), None);
+        assert_eq!(hops.iter().collect::<Vec<_>>(), vec![10, 20, 30]);
+
+        let owned = view.decode().unwrap();
+        assert_eq!(owned.hops.0, vec![10, 20, 30]);
+        assert_eq!(
+            format!("{:?}", owned.descr),
+            "VariableSizeString: \"default route\""
+        );
+    }
+
+    #[test]
+    fn test_view_errors() {
+        let data = wire::to_bytes(&sample()).unwrap();
+        for len in 0..data.len() {
+            assert!(RouteDetailsView::new(&data[..len]).is_err(), "{}", len);
+        }
+        /* anything past the count is not a part of the view */
+        let mut longer = data.clone();
+        longer.extend_from_slice(&[0, 40]);
+        let view = RouteDetailsView::new(&longer).unwrap();
+        assert_eq!(view.buf.len(), data.len());
+        assert_eq!(view.hops().len(), 3);
+
+        let mut bad = data;
+        bad[4 + 8 + 4 + 13] = 2;
+        assert!(matches!(
+            RouteDetailsView::new(&bad),
+            Err(Error::InvalidBool(2))
+        ));
+    }
+
+    #[test]
+    fn test_fixed_sizes() {
+        assert_eq!(<FixedSizeArray<u32, typenum::U4>>::FIXED_SIZE, Some(16));
+        assert_eq!(<VariableSizeArray<u32>>::FIXED_SIZE, None);
+        assert_eq!(fixed_size_sum(&[Some(4), Some(1)]), Some(5));
+        assert_eq!(fixed_size_sum(&[Some(4), None]), None);
+    }
+}
@@ -0,0 +1,789 @@
+/*
+ * Serde data format for the VPP API messages as they are on the wire.
+ *
+ * Everything is packed, without padding, and the numbers are big endian.
+ * Tuples, arrays and structs are their fields one after another; strings,
+ * byte strings and sequences have a u32 length in front of them (the
+ * vl_api_string_t and "count, then the array" layout), and their bytes are
+ * copied in one go rather than one element at a time.
+ *
+ * Tuples and structs are closed: they take exactly their fields, and a
+ * value cut short is an error rather than a shorter value. The one thing
+ * which runs to the end of the input is a trailing array without a count
+ * (a VariableSizeArray which has no count field), as in vpp_papi.
+ *
+ * The types in typ.rs come out the same as with bincode set to big endian
+ * and fixed size integers, which the generated code still offers; the
+ * plain sequences, strings and byte strings differ, with a u32 length here
+ * and a u64 one with bincode. The typ.rs strings are read here as one
+ * slice of the input, where bincode goes through them byte by byte.
+ */
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser::{self, Impossible, Serialize};
+use serde::Deserialize;
+use std::convert::TryFrom;
+use std::fmt;
+
+/* tuple struct names for the byte strings in typ.rs, to decode them in bulk */
+pub(crate) const FIXED_BYTES: &str = "$vpp_api_encoding::wire::FixedBytes";
+pub(crate) const VARIABLE_BYTES: &str = "$vpp_api_encoding::wire::VariableBytes";
+/* and for the trailing array without a count, which is the rest of the input */
+pub(crate) const TRAILING: &str = "$vpp_api_encoding::wire::Trailing";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The input ended in the middle of a value
+    Eof,
+    /// The value was decoded with this many bytes left over
+    TrailingBytes(usize),
+    /// A bool other than 0 or 1
+    InvalidBool(u8),
+    InvalidUtf8,
+    /// A length that does not fit in the u32 in front of it
+    TooLong(usize),
+    /// Something serde can express but the wire format can not
+    Unsupported(&'static str),
+    Custom(Box<str>),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Eof => write!(f, "unexpected end of input"),
+            Error::TrailingBytes(n) => write!(f, "{} bytes left after the value", n),
+            Error::InvalidBool(b) => write!(f, "invalid bool {}", b),
+            Error::InvalidUtf8 => write!(f, "string is not valid UTF-8"),
+            Error::TooLong(n) => write!(f, "length {} does not fit in u32", n),
+            Error::Unsupported(what) => write!(f, "{} is not supported on the wire", what),
+            Error::Custom(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    #[inline]
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Custom(msg.to_string().into())
+    }
+}
+
+impl de::Error for Error {
+    #[inline]
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Custom(msg.to_string().into())
+    }
+}
+
+/// Encode a value
+pub fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let mut ser = Serializer::new();
+    value.serialize(&mut ser)?;
+    Ok(ser.into_inner())
+}
+
+/// Decode a value which takes up all of the input
+pub fn from_bytes<'de, T: Deserialize<'de>>(input: &'de [u8]) -> Result<T> {
+    let mut de = Deserializer::new(input);
+    let value = T::deserialize(&mut de)?;
+    de.end()?;
+    Ok(value)
+}
+
+/// Decode a value from the start of the input, returning it with the number of bytes used
+pub fn from_bytes_prefix<'de, T: Deserialize<'de>>(input: &'de [u8]) -> Result<(T, usize)> {
+    let mut de = Deserializer::new(input);
+    let value = T::deserialize(&mut de)?;
+    Ok((value, input.len() - de.input.len()))
+}
+
+/* the elements of a trailing array, up to the end of the input */
+pub(crate) fn from_bytes_trailing<'de, T: Deserialize<'de>>(input: &'de [u8]) -> Result<Vec<T>> {
+    let mut de = Deserializer::new(input);
+    let mut out = vec![];
+    while !de.input.is_empty() {
+        let left = de.input.len();
+        out.push(T::deserialize(&mut de)?);
+        if de.input.len() == left {
+            return Err(Error::Unsupported("trailing array of empty elements"));
+        }
+    }
+    Ok(out)
+}
+
+#[derive(Debug)]
+pub struct Serializer {
+    out: Vec<u8>,
+}
+
+impl Serializer {
+    pub fn new() -> Self {
+        /* room for most messages, growing one byte at a time is what costs */
+        Serializer {
+            out: Vec::with_capacity(256),
+        }
+    }
+
+    /// Append to an existing buffer, e.g. the one holding the message id
+    pub fn with_buffer(out: Vec<u8>) -> Self {
+        Serializer { out }
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.out
+    }
+
+    #[inline]
+    fn put_len(&mut self, len: usize) -> Result<()> {
+        let len32 = u32::try_from(len).map_err(|_| Error::TooLong(len))?;
+        self.out.extend_from_slice(&len32.to_be_bytes());
+        Ok(())
+    }
+}
+
+impl Default for Serializer {
+    fn default() -> Self {
+        Serializer::new()
+    }
+}
+
+impl ser::Serializer for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    #[inline]
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.out.push(v as u8);
+        Ok(())
+    }
+    #[inline]
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.out.push(v as u8);
+        Ok(())
+    }
+    #[inline]
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+    #[inline]
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+    #[inline]
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+    #[inline]
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.out.push(v);
+        Ok(())
+    }
+    #[inline]
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+    #[inline]
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+    #[inline]
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+    #[inline]
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.out.extend_from_slice(&v.to_bits().to_be_bytes());
+        Ok(())
+    }
+    #[inline]
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.out.extend_from_slice(&v.to_bits().to_be_bytes());
+        Ok(())
+    }
+    #[inline]
+    fn serialize_char(self, _v: char) -> Result<()> {
+        Err(Error::Unsupported("char"))
+    }
+    #[inline]
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.serialize_bytes(v.as_bytes())
+    }
+    #[inline]
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.put_len(v.len())?;
+        self.out.extend_from_slice(v);
+        Ok(())
+    }
+    #[inline]
+    fn serialize_none(self) -> Result<()> {
+        Err(Error::Unsupported("Option"))
+    }
+    #[inline]
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<()> {
+        Err(Error::Unsupported("Option"))
+    }
+    #[inline]
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+    #[inline]
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+    #[inline]
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        self.serialize_u32(variant_index)
+    }
+    #[inline]
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+    #[inline]
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.serialize_u32(variant_index)?;
+        value.serialize(self)
+    }
+    #[inline]
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self> {
+        let len = len.ok_or(Error::Unsupported("sequence of unknown length"))?;
+        self.put_len(len)?;
+        Ok(self)
+    }
+    #[inline]
+    fn serialize_tuple(self, len: usize) -> Result<Self> {
+        self.out.reserve(len);
+        Ok(self)
+    }
+    #[inline]
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Self> {
+        self.serialize_tuple(len)
+    }
+    #[inline]
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.serialize_u32(variant_index)?;
+        Ok(self)
+    }
+    #[inline]
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(Error::Unsupported("map"))
+    }
+    #[inline]
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+    #[inline]
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.serialize_u32(variant_index)?;
+        Ok(self)
+    }
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+impl ser::SerializeSeq for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    #[inline]
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+    #[inline]
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    #[inline]
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+    #[inline]
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    #[inline]
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+    #[inline]
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    #[inline]
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+    #[inline]
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    #[inline]
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+    #[inline]
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    #[inline]
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+    #[inline]
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+pub struct Deserializer<'de> {
+    input: &'de [u8],
+}
+
+impl<'de> Deserializer<'de> {
+    pub fn new(input: &'de [u8]) -> Self {
+        Deserializer { input }
+    }
+
+    /// Check that all of the input has been used up
+    pub fn end(&self) -> Result<()> {
+        if self.input.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::TrailingBytes(self.input.len()))
+        }
+    }
+
+    #[inline]
+    fn take(&mut self, len: usize) -> Result<&'de [u8]> {
+        if self.input.len() < len {
+            return Err(Error::Eof);
+        }
+        let (head, tail) = self.input.split_at(len);
+        self.input = tail;
+        Ok(head)
+    }
+
+    #[inline]
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut out = [0; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    #[inline]
+    fn take_len(&mut self) -> Result<usize> {
+        Ok(u32::from_be_bytes(self.take_array()?) as usize)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    #[inline]
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(Error::Unsupported("deserialize_any"))
+    }
+    #[inline]
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.take(1)?[0] {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            b => Err(Error::InvalidBool(b)),
+        }
+    }
+    #[inline]
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i8(self.take(1)?[0] as i8)
+    }
+    #[inline]
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i16(i16::from_be_bytes(self.take_array()?))
+    }
+    #[inline]
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i32(i32::from_be_bytes(self.take_array()?))
+    }
+    #[inline]
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i64(i64::from_be_bytes(self.take_array()?))
+    }
+    #[inline]
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u8(self.take(1)?[0])
+    }
+    #[inline]
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u16(u16::from_be_bytes(self.take_array()?))
+    }
+    #[inline]
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u32(u32::from_be_bytes(self.take_array()?))
+    }
+    #[inline]
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u64(u64::from_be_bytes(self.take_array()?))
+    }
+    #[inline]
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f32(f32::from_bits(u32::from_be_bytes(self.take_array()?)))
+    }
+    #[inline]
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f64(f64::from_bits(u64::from_be_bytes(self.take_array()?)))
+    }
+    #[inline]
+    fn deserialize_char<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(Error::Unsupported("char"))
+    }
+    #[inline]
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.take_len()?;
+        let s = std::str::from_utf8(self.take(len)?).map_err(|_| Error::InvalidUtf8)?;
+        visitor.visit_borrowed_str(s)
+    }
+    #[inline]
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+    #[inline]
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.take_len()?;
+        visitor.visit_borrowed_bytes(self.take(len)?)
+    }
+    #[inline]
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+    #[inline]
+    fn deserialize_option<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(Error::Unsupported("Option"))
+    }
+    #[inline]
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+    #[inline]
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+    #[inline]
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+    #[inline]
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.take_len()?;
+        visitor.visit_seq(Access { de: self, len })
+    }
+    #[inline]
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Access { de: self, len })
+    }
+    #[inline]
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        match name {
+            FIXED_BYTES => visitor.visit_borrowed_bytes(self.take(len)?),
+            VARIABLE_BYTES => self.deserialize_bytes(visitor),
+            TRAILING => visitor.visit_borrowed_bytes(self.take(self.input.len())?),
+            _ => self.deserialize_tuple(len, visitor),
+        }
+    }
+    #[inline]
+    fn deserialize_map<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(Error::Unsupported("map"))
+    }
+    #[inline]
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+    #[inline]
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_enum(self)
+    }
+    #[inline]
+    fn deserialize_identifier<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(Error::Unsupported("identifier"))
+    }
+    #[inline]
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(Error::Unsupported("deserialize_ignored_any"))
+    }
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+struct Access<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    len: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for Access<'_, 'de> {
+    type Error = Error;
+
+    #[inline]
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+        self.len -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        /* the length may have come off the wire, keep the preallocation sane */
+        Some(self.len.min(self.de.input.len()))
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    #[inline]
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let index: de::value::U32Deserializer<Error> =
+            u32::from_be_bytes(self.take_array()?).into_deserializer();
+        let value = seed.deserialize(index)?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    #[inline]
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+    #[inline]
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+    #[inline]
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+    #[inline]
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_tuple(self, fields.len(), visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typ::*;
+    use bincode::Options;
+    use serde::Serialize;
+    use std::convert::TryInto;
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct CliInband {
+        client_index: u32,
+        context: u32,
+        name: FixedSizeString<typenum::U8>,
+        cmd: VariableSizeString,
+        addr: FixedSizeArray<u8, typenum::U4>,
+        metric: F64,
+        is_add: bool,
+        hops: VariableSizeArray<u16>,
+    }
+
+    fn sample() -> CliInband {
+        CliInband {
+            client_index: 1,
+            context: 0x1234,
+            name: "vpp".try_into().unwrap(),
+            cmd: "show version".try_into().unwrap(),
+            addr: vec![10, 0, 0, 1].try_into().unwrap(),
+            metric: F64(1.5),
+            is_add: true,
+            hops: VariableSizeArray(vec![1, 2, 3]),
+        }
+    }
+
+    #[test]
+    fn test_same_as_bincode() {
+        let enc = bincode::DefaultOptions::new()
+            .with_big_endian()
+            .with_fixint_encoding();
+        let msg = sample();
+        let data = to_bytes(&msg).unwrap();
+        assert_eq!(data, enc.serialize(&msg).unwrap());
+        assert_eq!(&data[0..8], &[0, 0, 0, 1, 0, 0, 0x12, 0x34]);
+        assert_eq!(&data[16..20], &[0, 0, 0, 12]);
+        /* 1.5 in network order, as VPP checks with get_f64_endian_value */
+        assert_eq!(&data[36..44], &[0x3f, 0xf8, 0, 0, 0, 0, 0, 0]);
+
+        let decoded: CliInband = from_bytes(&data).unwrap();
+        assert!(decoded.name.equals_str("vpp"));
+        assert_eq!(String::try_from(decoded.name).unwrap(), "vpp");
+        assert_eq!(format!("{:?}", decoded.cmd), format!("{:?}", msg.cmd));
+        assert_eq!(decoded.addr.0.as_slice(), &[10, 0, 0, 1]);
+        assert_eq!(decoded.metric.0, 1.5);
+        assert!(decoded.is_add);
+        assert_eq!(decoded.hops.0, vec![1, 2, 3]);
+
+        /* bincode reads the strings back the same, but not the trailing array */
+        let (name, cmd): (FixedSizeString<typenum::U8>, VariableSizeString) =
+            enc.deserialize(&data[8..32]).unwrap();
+        assert!(name.equals_str("vpp"));
+        assert_eq!(format!("{:?}", cmd), format!("{:?}", msg.cmd));
+        assert!(enc.deserialize::<CliInband>(&data).is_err());
+    }
+
+    #[test]
+    fn test_trailing_array() {
+        let data = to_bytes(&(1u8, VariableSizeArray(vec![7u16, 8]))).unwrap();
+        assert_eq!(data, vec![1, 0, 7, 0, 8]);
+        let (b, v): (u8, VariableSizeArray<u16>) = from_bytes(&data).unwrap();
+        assert_eq!((b, v.0), (1, vec![7, 8]));
+        let (_, v): (u8, VariableSizeArray<u16>) = from_bytes(&data[..1]).unwrap();
+        assert!(v.is_empty());
+        /* a byte left over is half an element, not the end of the array */
+        assert!(from_bytes::<(u8, VariableSizeArray<u16>)>(&data[..4]).is_err());
+    }
+
+    #[test]
+    fn test_length_prefixed() {
+        let data = to_bytes(&("ab", vec![7u16, 8], 9u8)).unwrap();
+        assert_eq!(
+            data,
+            vec![0, 0, 0, 2, b'a', b'b', 0, 0, 0, 2, 0, 7, 0, 8, 9]
+        );
+        let (s, v, b): (String, Vec<u16>, u8) = from_bytes(&data).unwrap();
+        assert_eq!((&s[..], &v[..], b), ("ab", &[7, 8][..], 9));
+        let borrowed: (&str, Vec<u16>, u8) = from_bytes(&data).unwrap();
+        assert_eq!(borrowed.0, "ab");
+
+        /* a count larger than the input fails instead of allocating it */
+        assert_eq!(
+            from_bytes::<Vec<u32>>(&[0xff, 0xff, 0xff, 0xff, 0, 0, 0, 1]),
+            Err(Error::Eof)
+        );
+    }
+
+    #[test]
+    fn test_exact_consumption() {
+        let data = to_bytes(&(1u16, 2u32)).unwrap();
+        assert_eq!(from_bytes::<(u16, u32)>(&data), Ok((1, 2)));
+        assert_eq!(from_bytes::<u16>(&data), Err(Error::TrailingBytes(4)));
+        assert_eq!(from_bytes_prefix::<u16>(&data), Ok((1, 2)));
+        assert_eq!(from_bytes::<(u16, u32)>(&data[..5]), Err(Error::Eof));
+        /* a tuple does not end early with the input */
+        assert_eq!(from_bytes::<(u16, u32)>(&data[..2]), Err(Error::Eof));
+        assert_eq!(from_bytes::<bool>(&[2]), Err(Error::InvalidBool(2)));
+        assert_eq!(
+            from_bytes::<FixedSizeString<typenum::U8>>(&[b'x'; 7]).map(|_| ()),
+            Err(Error::Eof)
+        );
+        assert_eq!(to_bytes(&Some(1u8)), Err(Error::Unsupported("Option")));
+    }
+}
@@ -3,7 +3,6 @@ use std::convert::TryInto;
 use std::process::Command;
 use vpp_api_gen::interface::*;
 use vpp_api_gen::interface_types::*;
-use vpp_api_gen::ip_types::*;
 use vpp_api_gen::vhost_user::*;
 use vpp_api_gen::virtio_types::*;
 use vpp_api_gen::vlib::CliInband;
@@ -90,13 +89,7 @@ fn main() {
             is_add: true,
             del_all: false,
             sw_if_index: 1,
-            prefix: AddressWithPrefix {
-                address: Address {
-                    af: AddressFamily::ADDRESS_IP4,
-                    un: AddressUnion::new_Ip4Address([10, 10, 1, 2]),
-                },
-                len: 24,
-            },
+            prefix: "10.10.1.2/24".parse().unwrap(),
         },
         &mut *t,
     ).unwrap();
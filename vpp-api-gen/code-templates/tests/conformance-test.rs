@@ -0,0 +1,99 @@
+/*
+ * The reference wire vectors of conformance-vectors.json against the
+ * encoding and the decoding of the generated types. No VPP is needed.
+ *
+ * The vectors were derived by hand following vpp_papi's serializer and
+ * are not yet confirmed against vpp_papi itself: run
+ * conformance-vectors.py --check where vpp_papi is installed.
+ */
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use vpp_api_encoding::wire::{from_bytes_prefix, to_bytes};
+use vpp_api_gen::{acl, interface, ip_neighbor, punt, vpe};
+use vpp_api_message::VppApiMessage;
+
+#[derive(Debug, Deserialize)]
+struct Vector {
+    file: String,
+    message: String,
+    crc: String,
+    /* the message as the JSON of the generated type */
+    rust: serde_json::Value,
+    /* the bytes after the message id */
+    wire: String,
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+fn check<T: VppApiMessage + Serialize + DeserializeOwned>(v: &Vector) {
+    /* the vectors are for the API files the types were generated from */
+    assert_eq!(
+        T::get_message_name_and_crc(),
+        format!("{}_{}", v.message, v.crc.trim_start_matches("0x")),
+        "{} of {}",
+        v.message,
+        v.file
+    );
+
+    let msg: T = serde_json::from_value(v.rust.clone()).unwrap();
+    assert_eq!(
+        to_hex(&to_bytes(&msg).unwrap()),
+        v.wire,
+        "encoding {}",
+        v.message
+    );
+
+    let wire = from_hex(&v.wire);
+    let (decoded, size): (T, usize) = from_bytes_prefix(&wire).unwrap();
+    assert_eq!(size, wire.len(), "decoding {} left bytes", v.message);
+    assert_eq!(
+        serde_json::to_value(&decoded).unwrap(),
+        v.rust,
+        "decoding {}",
+        v.message
+    );
+}
+
+macro_rules! vector_types {
+    ($($name:literal => $t:ty,)*) => {
+        fn check_vector(v: &Vector) {
+            match v.message.as_str() {
+                $($name => check::<$t>(v),)*
+                other => panic!("no type for {} of {}", other, v.file),
+            }
+        }
+    };
+}
+
+vector_types! {
+    "control_ping" => vpe::ControlPing,
+    "control_ping_reply" => vpe::ControlPingReply,
+    "show_version_reply" => vpe::ShowVersionReply,
+    "cli_inband" => vpe::CliInband,
+    "get_f64_endian_value" => vpe::GetF64EndianValue,
+    "get_f64_endian_value_reply" => vpe::GetF64EndianValueReply,
+    "sw_interface_set_flags" => interface::SwInterfaceSetFlags,
+    "sw_interface_set_mac_address" => interface::SwInterfaceSetMacAddress,
+    "sw_interface_add_del_address" => interface::SwInterfaceAddDelAddress,
+    "set_punt" => punt::SetPunt,
+    "ip_neighbor_add_del" => ip_neighbor::IpNeighborAddDel,
+    "acl_interface_set_acl_list" => acl::AclInterfaceSetAclList,
+}
+
+#[test]
+fn test_conformance_vectors() {
+    let vectors: Vec<Vector> =
+        serde_json::from_str(include_str!("conformance-vectors.json")).unwrap();
+    assert!(!vectors.is_empty());
+    for v in &vectors {
+        check_vector(v);
+    }
+}
@@ -0,0 +1,264 @@
+#!/usr/bin/env python3
+"""
+Writes conformance-vectors.json for conformance-test.rs: the wire bytes of
+the messages below as vpp_papi encodes them, with the same message in the
+JSON of the generated types. With --check, compares the bytes in the
+checked in file with those of vpp_papi instead.
+
+The checked in file was not written by this script: its bytes were derived
+by hand following vpp_papi's serializer, and are not yet confirmed against
+vpp_papi itself. Run it with --check to do that.
+
+    conformance-vectors.py [--check] vpp-api-gen/testdata/vpp/api
+
+The message id is left out, the generated types start after it.
+"""
+import argparse
+import json
+import os
+import sys
+
+from vpp_papi.vpp_papi import VPPApiJSONFiles
+
+# (API file, message, the fields for vpp_papi, the JSON of the generated type)
+VECTORS = [
+    (
+        "core/vpe.api.json",
+        "control_ping",
+        {"client_index": 1, "context": 0x12345678},
+        {"client_index": 1, "context": 0x12345678},
+    ),
+    (
+        "core/vpe.api.json",
+        "control_ping_reply",
+        {"context": 0x12345678, "retval": -1, "client_index": 1, "vpe_pid": 4242},
+        {"context": 0x12345678, "retval": -1, "client_index": 1, "vpe_pid": 4242},
+    ),
+    (
+        "core/vpe.api.json",
+        "show_version_reply",
+        {
+            "context": 7,
+            "retval": 0,
+            "program": "vpe",
+            "version": "23.10-release",
+            "build_date": "2023-10-25T12:00:00",
+            "build_directory": "/w/vpp",
+        },
+        {
+            "context": 7,
+            "retval": 0,
+            "program": "vpe",
+            "version": "23.10-release",
+            "build_date": "2023-10-25T12:00:00",
+            "build_directory": "/w/vpp",
+        },
+    ),
+    (
+        "core/vpe.api.json",
+        "cli_inband",
+        {"client_index": 1, "context": 2, "cmd": "show version"},
+        {"client_index": 1, "context": 2, "cmd": "show version"},
+    ),
+    (
+        "core/vpe.api.json",
+        "get_f64_endian_value",
+        {"client_index": 1, "context": 3, "f64_one": 1.0},
+        {"client_index": 1, "context": 3, "f64_one": 1.0},
+    ),
+    (
+        "core/vpe.api.json",
+        "get_f64_endian_value_reply",
+        {"context": 3, "retval": 0, "f64_one_result": 1.0},
+        {"context": 3, "retval": 0, "f64_one_result": 1.0},
+    ),
+    (
+        "core/interface.api.json",
+        "sw_interface_set_flags",
+        {"client_index": 1, "context": 4, "sw_if_index": 5, "flags": 3},
+        {
+            "client_index": 1,
+            "context": 4,
+            "sw_if_index": 5,
+            "flags": ["IF_STATUS_API_FLAG_ADMIN_UP", "IF_STATUS_API_FLAG_LINK_UP"],
+        },
+    ),
+    (
+        "core/interface.api.json",
+        "sw_interface_set_mac_address",
+        {
+            "client_index": 1,
+            "context": 5,
+            "sw_if_index": 1,
+            "mac_address": "02:fe:aa:bb:cc:dd",
+        },
+        {
+            "client_index": 1,
+            "context": 5,
+            "sw_if_index": 1,
+            "mac_address": "02:fe:aa:bb:cc:dd",
+        },
+    ),
+    (
+        "core/interface.api.json",
+        "sw_interface_add_del_address",
+        {
+            "client_index": 1,
+            "context": 6,
+            "sw_if_index": 1,
+            "is_add": True,
+            "del_all": False,
+            "prefix": "10.10.1.2/24",
+        },
+        {
+            "client_index": 1,
+            "context": 6,
+            "sw_if_index": 1,
+            "is_add": True,
+            "del_all": False,
+            "prefix": "10.10.1.2/24",
+        },
+    ),
+    (
+        "core/interface.api.json",
+        "sw_interface_add_del_address",
+        {
+            "client_index": 1,
+            "context": 7,
+            "sw_if_index": 1,
+            "is_add": False,
+            "del_all": False,
+            "prefix": "2001:db8::1/64",
+        },
+        {
+            "client_index": 1,
+            "context": 7,
+            "sw_if_index": 1,
+            "is_add": False,
+            "del_all": False,
+            "prefix": "2001:db8::1/64",
+        },
+    ),
+    (
+        "core/punt.api.json",
+        "set_punt",
+        {
+            "client_index": 1,
+            "context": 8,
+            "is_add": True,
+            "punt": {
+                "type": 1,
+                "punt": {"l4": {"af": 0, "protocol": 17, "port": 4789}},
+            },
+        },
+        {
+            "client_index": 1,
+            "context": 8,
+            "is_add": True,
+            "punt": {
+                "L4": {
+                    "af": "ADDRESS_IP4",
+                    "protocol": "IP_API_PROTO_UDP",
+                    "port": 4789,
+                }
+            },
+        },
+    ),
+    (
+        "core/ip_neighbor.api.json",
+        "ip_neighbor_add_del",
+        {
+            "client_index": 1,
+            "context": 10,
+            "is_add": True,
+            "neighbor": {
+                "sw_if_index": 1,
+                "flags": 1,
+                "mac_address": "02:fe:aa:bb:cc:dd",
+                "ip_address": "2001:db8::2",
+            },
+        },
+        {
+            "client_index": 1,
+            "context": 10,
+            "is_add": True,
+            "neighbor": {
+                "sw_if_index": 1,
+                "flags": ["IP_API_NEIGHBOR_FLAG_STATIC"],
+                "mac_address": "02:fe:aa:bb:cc:dd",
+                "ip_address": "2001:db8::2",
+            },
+        },
+    ),
+    (
+        "plugins/acl.api.json",
+        "acl_interface_set_acl_list",
+        {
+            "client_index": 1,
+            "context": 9,
+            "sw_if_index": 2,
+            "count": 3,
+            "n_input": 1,
+            "acls": [10, 20, 30],
+        },
+        {
+            "client_index": 1,
+            "context": 9,
+            "sw_if_index": 2,
+            "count": 3,
+            "n_input": 1,
+            "acls": [10, 20, 30],
+        },
+    ),
+]
+
+
+def encode(apidir, file, message, fields):
+    path = os.path.join(apidir, file)
+    with open(path) as f:
+        messages, _ = VPPApiJSONFiles.process_json_file(f)
+    with open(path) as f:
+        crc = next(m[-1]["crc"] for m in json.load(f)["messages"] if m[0] == message)
+    data = messages[message].pack(dict(fields, _vl_msg_id=0))
+    return crc, data[2:].hex()
+
+
+def main():
+    parser = argparse.ArgumentParser()
+    parser.add_argument("--check", action="store_true")
+    parser.add_argument("apidir")
+    args = parser.parse_args()
+    out = os.path.join(os.path.dirname(os.path.abspath(__file__)), "conformance-vectors.json")
+
+    vectors = []
+    for file, message, papi, rust in VECTORS:
+        crc, wire = encode(args.apidir, file, message, papi)
+        vectors.append(
+            {
+                "file": file,
+                "message": message,
+                "crc": crc,
+                "papi": papi,
+                "rust": rust,
+                "wire": wire,
+            }
+        )
+    if args.check:
+        with open(out) as f:
+            checked_in = json.load(f)
+        failed = False
+        for old, new in zip(checked_in, vectors):
+            if (old["message"], old["crc"], old["wire"]) != (new["message"], new["crc"], new["wire"]):
+                print("{}: {} != {}".format(new["message"], old["wire"], new["wire"]))
+                failed = True
+        if len(checked_in) != len(vectors):
+            print("{} vectors checked in, {} here".format(len(checked_in), len(vectors)))
+            failed = True
+        sys.exit(1 if failed else 0)
+    with open(out, "w") as f:
+        json.dump(vectors, f, indent=2)
+        f.write("\n")
+
+
+if __name__ == "__main__":
+    main()
@@ -45,10 +45,7 @@ fn test_sw_interface_add_del_address() {
             del_all: false,
             sw_if_index: 0,
             prefix: AddressWithPrefix {
-                address: Address {
-                    af: AddressFamily::ADDRESS_IP4,
-                    un: AddressUnion::new_Ip4Address([10, 10, 1, 2]),
-                },
+                address: Address::Ip4([10, 10, 1, 2].into()),
                 len: 24,
             },
         },
@@ -419,7 +416,7 @@ fn test_sw_interface_add_del_mac_address() {
             context: 0,
             sw_if_index: 1,
             is_add: 0,
-            addr: [0, 0x01, 0x02, 0x03, 0x04, 0x05],
+            addr: [0, 0x01, 0x02, 0x03, 0x04, 0x05].into(),
         },
         &mut *t,
         &SwInterfaceAddDelMacAddressReply::get_message_name_and_crc(),
@@ -445,7 +442,7 @@ fn test_sw_interface_set_mac_address() {
             client_index: t.get_client_index(),
             context: 0,
             sw_if_index: 1,
-            mac_address: [0, 0x01, 0x02, 0x03, 0x04, 0x05],
+            mac_address: "00:01:02:03:04:05".parse().unwrap(),
         },
         &mut *t,
         &SwInterfaceSetMacAddressReply::get_message_name_and_crc(),
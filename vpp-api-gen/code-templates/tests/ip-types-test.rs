@@ -0,0 +1,62 @@
+/*
+ * The ip_types addresses and prefixes in JSON and YAML: text, except for
+ * an address of a family the API does not know, which has no text form
+ * and goes as the enum value and the union bytes. No VPP is needed.
+ */
+use vpp_api_encoding::wire;
+use vpp_api_gen::ip_types::*;
+
+/* family 7 is neither IPv4 nor IPv6, the union bytes are 1..=16 */
+#[cfg(not(feature = "strict-enums"))]
+fn unknown_address() -> Address {
+    let mut data = vec![7u8];
+    data.extend(1..=16u8);
+    wire::from_bytes(&data).unwrap()
+}
+
+#[test]
+fn test_address_text() {
+    let address: Address = "10.0.0.1".parse().unwrap();
+    assert_eq!(serde_json::to_string(&address).unwrap(), r#""10.0.0.1""#);
+    let prefix: Prefix = "2001:db8::/32".parse().unwrap();
+    assert_eq!(
+        serde_json::to_string(&prefix).unwrap(),
+        r#""2001:db8::/32""#
+    );
+    let yaml = serde_yaml::to_string(&prefix).unwrap();
+    let back: Prefix = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(back.to_string(), "2001:db8::/32");
+}
+
+/* with strict-enums there is no unknown family to begin with */
+#[cfg(not(feature = "strict-enums"))]
+#[test]
+fn test_unknown_address_roundtrip() {
+    use std::convert::TryFrom;
+
+    let address = unknown_address();
+    assert!(matches!(address, Address::Unknown(..)));
+    assert!(std::net::IpAddr::try_from(&address).is_err());
+    assert_eq!(
+        address.to_string(),
+        "<address family 7: 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f 10>"
+    );
+    let wire_bytes = wire::to_bytes(&address).unwrap();
+
+    let json = serde_json::to_string(&address).unwrap();
+    assert_eq!(json, "[7,[1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16]]");
+    let back: Address = serde_json::from_str(&json).unwrap();
+    assert_eq!(wire::to_bytes(&back).unwrap(), wire_bytes);
+
+    let yaml = serde_yaml::to_string(&address).unwrap();
+    let back: Address = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(wire::to_bytes(&back).unwrap(), wire_bytes);
+
+    let prefix = Prefix { address, len: 24 };
+    let json = serde_json::to_string(&prefix).unwrap();
+    let back: Prefix = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        wire::to_bytes(&back).unwrap(),
+        wire::to_bytes(&prefix).unwrap()
+    );
+}
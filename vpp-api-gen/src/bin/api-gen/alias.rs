@@ -33,6 +33,17 @@ impl VppJsApiAlias {
     pub fn generate_code(&self, name: &str) -> String {
         let mut code = String::new();
         code.push_str(&format!("pub type {}=", camelize_ident(&get_ident(&name))));
+        /* the same bytes, but with Display/FromStr */
+        let net_type = match (name, self.length) {
+            ("mac_address", Some(6)) => Some("MacAddress"),
+            ("ip4_address", Some(4)) => Some("Ip4Address"),
+            ("ip6_address", Some(16)) => Some("Ip6Address"),
+            _ => None,
+        };
+        if let Some(net_type) = net_type {
+            code.push_str(&format!("vpp_api_encoding::net::{};\n", net_type));
+            return code;
+        }
         match self.length {
             Some(len) => {
                 let newtype = get_type(&self.ctype);
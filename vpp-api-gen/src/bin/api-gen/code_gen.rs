@@ -82,6 +82,12 @@ pub fn create_cargo_toml(package_path: &str, packageName: &str, vppapi_opts: &st
     code.push_str("authors = [\"Andrew Yourtchenko <ayourtch@gmail.com>\"]\n");
     code.push_str("edition = \"2018\"\n\n");
 
+    code.push_str("[features]\n");
+    code.push_str("# borrowed views of the messages, see vpp_api_encoding::view\n");
+    code.push_str("views = []\n");
+    code.push_str("# fail to decode the enum values which are not in the bindings, see VppEnum\n");
+    code.push_str("strict-enums = []\n\n");
+
     code.push_str("[dev-dependencies]\n");
     code.push_str("trybuild = {version = \"1.0\", features = [\"diff\"]}\n\n");
     code.push_str(&vpp_api_crate("vpp-api-transport", &vppapi_opts));
@@ -0,0 +1,163 @@
+use crate::parser_helper::camelize_ident;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VppJsApiCounterElement {
+    pub name: String,
+    pub severity: String,
+    #[serde(rename = "type")]
+    pub typ: String,
+    pub units: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VppJsApiCounter {
+    pub name: String,
+    pub elements: Vec<VppJsApiCounterElement>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VppJsApiPath {
+    pub path: String,
+    pub counter: String,
+}
+
+fn counter_ident(name: &str) -> String {
+    let ident: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let ident = camelize_ident(&ident);
+    if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("C{}", ident)
+    } else {
+        ident
+    }
+}
+
+fn severity(severity: &str) -> &'static str {
+    match severity {
+        "error" => "CounterSeverity::Error",
+        "warn" | "warning" => "CounterSeverity::Warn",
+        "info" => "CounterSeverity::Info",
+        x => {
+            log::warn!("Unknown counter severity {}, using info", x);
+            "CounterSeverity::Info"
+        }
+    }
+}
+
+impl VppJsApiCounter {
+    pub fn enum_name(&self) -> String {
+        format!("{}Counter", counter_ident(&self.name))
+    }
+
+    // The paths this counter set lives under. Some files (ikev2) refer to
+    // their only counter set by another name, so those go to it as well.
+    pub fn get_paths<'a>(
+        &self,
+        counters: &[VppJsApiCounter],
+        paths: &'a [Vec<VppJsApiPath>],
+    ) -> Vec<&'a str> {
+        paths
+            .iter()
+            .flatten()
+            .filter(|p| {
+                p.counter == self.name
+                    || (counters.len() == 1 && !counters.iter().any(|c| c.name == p.counter))
+            })
+            .map(|p| p.path.as_str())
+            .collect()
+    }
+
+    fn generate_match(
+        &self,
+        enum_name: &str,
+        typ: (&str, &str),
+        f: impl Fn(&VppJsApiCounterElement) -> String,
+    ) -> String {
+        let mut code = String::new();
+        code.push_str("\t\tmatch self {\n");
+        for e in &self.elements {
+            code.push_str(&format!(
+                "\t\t\t{}::{} => {},\n",
+                enum_name,
+                counter_ident(&e.name),
+                f(e)
+            ));
+        }
+        code.push_str("\t\t}\n");
+        format!("\tfn {}(&self) -> {} {{\n{}\t}}\n", typ.0, typ.1, code)
+    }
+
+    pub fn generate_code(&self, paths: &[&str]) -> String {
+        let enum_name = self.enum_name();
+        let mut code = String::new();
+        code.push_str("#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]\n");
+        code.push_str("#[repr(u32)]\n");
+        code.push_str(&format!("pub enum {} {{\n", enum_name));
+        for (i, e) in self.elements.iter().enumerate() {
+            code.push_str(&format!("\t{} = {},\n", counter_ident(&e.name), i));
+        }
+        code.push_str("}\n");
+
+        code.push_str(&format!("impl VppApiCounter for {} {{\n", enum_name));
+        code.push_str(&format!(
+            "\tfn counter_set_name() -> &'static str {{\n\t\t{:?}\n\t}}\n",
+            self.name
+        ));
+        let all: Vec<String> = self
+            .elements
+            .iter()
+            .map(|e| format!("{}::{}", enum_name, counter_ident(&e.name)))
+            .collect();
+        code.push_str(&format!(
+            "\tfn all() -> &'static [Self] {{\n\t\t&[{}]\n\t}}\n",
+            all.join(", ")
+        ));
+        let paths: Vec<String> = paths.iter().map(|p| format!("{:?}", p)).collect();
+        code.push_str(&format!(
+            "\tfn paths() -> &'static [&'static str] {{\n\t\t&[{}]\n\t}}\n",
+            paths.join(", ")
+        ));
+        code.push_str(
+            &self.generate_match(&enum_name, ("name", "&'static str"), |e| {
+                format!("{:?}", e.name)
+            }),
+        );
+        code.push_str(
+            &self.generate_match(&enum_name, ("severity", "CounterSeverity"), |e| {
+                severity(&e.severity).to_string()
+            }),
+        );
+        code.push_str(
+            &self.generate_match(&enum_name, ("counter_type", "&'static str"), |e| {
+                format!("{:?}", e.typ)
+            }),
+        );
+        code.push_str(
+            &self.generate_match(&enum_name, ("units", "&'static str"), |e| {
+                format!("{:?}", e.units)
+            }),
+        );
+        code.push_str(
+            &self.generate_match(&enum_name, ("description", "&'static str"), |e| {
+                format!("{:?}", e.description)
+            }),
+        );
+        code.push_str("}\n");
+        code
+    }
+
+    pub fn iter_and_generate_code(
+        counters: &[VppJsApiCounter],
+        paths: &[Vec<VppJsApiPath>],
+    ) -> String {
+        counters
+            .iter()
+            .filter(|c| !c.elements.is_empty())
+            .map(|c| c.generate_code(&c.get_paths(counters, paths)))
+            .collect()
+    }
+}
@@ -4,6 +4,7 @@ extern crate strum;
 use crate::basetypes::{maxSizeUnion, sizeof_alias, sizeof_struct};
 use crate::parser_helper::{camelize_ident, get_ident, get_type};
 use serde::de::{Deserializer, SeqAccess, Visitor};
+use std::collections::HashMap;
 use std::fmt;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -108,47 +109,27 @@ impl<'de> Deserialize<'de> for VppJsApiEnum {
         deserializer.deserialize_seq(VppJsApiEnumVisitor)
     }
 }
-pub fn isPowerOfTwo(n: &mut i64) -> bool {
-    if *n == 0 {
-        return false;
-    }
-    while *n != 1 {
-        if *n % 2 != 0 {
-            return false;
-        }
-        *n = *n / 2;
-    }
-    return true;
+/// How an enum goes on the wire, when it is not just a u32
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnumContainer {
+    /// a SizedEnum of the enumtype
+    Sized(String),
+    /// an EnumFlag, a set of the flags
+    Flags,
 }
+
 impl VppJsApiEnum {
-    pub fn if_flag(&self) -> bool {
-        if self.name.contains("flag") {
-            return true;
-        }
-        let mut prev: i64 = self.values[0].value;
-        for x in 1..self.values.len() {
-            if self.values[x].value == 0 {
-                continue;
-            }
-            if !isPowerOfTwo(&mut self.values[x].value.clone()) {
-                return false;
-            }
-            let next = prev + 1;
-            if self.values[x].value == next {
-                prev = next;
-                continue;
-            } else {
-                if self.values.len() <= x + 1 {
-                    // avoid panic in the next if
-                    return false;
-                }
-                if isPowerOfTwo(&mut self.values[x + 1].value.clone()) {
-                    return true;
-                }
-                return false;
-            }
-        }
-        false
+    /*
+     * The flag sets are in the enumflags section; the API files from before
+     * VPP had enumflag declare them as enums, which are then told apart by
+     * their name.
+     */
+    pub fn is_legacy_flags(&self) -> bool {
+        self.name.ends_with("_flags")
+    }
+    /* the integer type of the enum, on the wire and for its #[repr] */
+    pub fn repr(&self) -> &str {
+        self.info.enumtype.as_deref().unwrap_or("u32")
     }
     pub fn generate_as_enumflag_trait(&self) -> String {
         let mut code = String::new();
@@ -159,17 +140,17 @@ impl VppJsApiEnum {
         code.push_str("\t fn as_u32(data: &Self) -> u32{\n");
         code.push_str("\t\t *data as u32\n");
         code.push_str("\t }\n");
-        code.push_str("\t fn from_u32(data: u32) -> Self{\n");
+        code.push_str("\t fn from_u32(data: u32) -> Option<Self>{\n");
         code.push_str("\t\t match data{\n");
         for x in 0..self.values.len() {
             code.push_str(&format!(
-                "\t\t\t {} => {}::{},\n",
+                "\t\t\t {} => Some({}::{}),\n",
                 self.values[x].value,
                 camelize_ident(&self.name),
                 get_ident(&self.values[x].name)
             ));
         }
-        code.push_str("\t\t\t_ => panic!(\"Invalid Enum Descriminant\")\n");
+        code.push_str("\t\t\t_ => None,\n");
         code.push_str("\t\t }\n");
         code.push_str("\t }\n");
         code.push_str("\t fn size_of_enum_flag() -> u32{\n");
@@ -181,9 +162,9 @@ impl VppJsApiEnum {
         code.push_str("}\n");
         code
     }
-    pub fn generate_code(&self) -> String {
+    pub fn generate_code(&self, flags: bool) -> String {
         let mut code = String::new();
-        if self.if_flag() {
+        if flags {
             // This tells if the enum is a flag or not
             code.push_str(&format!(
                 "#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]\n"
@@ -195,12 +176,10 @@ impl VppJsApiEnum {
         } else {
             // This tells if the enum is a flag or not
             code.push_str(&format!(
-                "#[derive(Debug, Clone, Serialize_repr, Deserialize_repr)]\n"
+                "#[derive(Debug, Clone, Copy, PartialEq, VppEnum)]\n"
             ));
-            match &self.info.enumtype {
-                Some(len) => code.push_str(&format!("#[repr({})]\n", &len)),
-                _ => code.push_str(&format!("#[repr(u32)]\n")),
-            }
+            code.push_str(&format!("#[repr({})]\n", self.repr()));
+            code.push_str("#[cfg_attr(feature = \"strict-enums\", vpp_strict)]\n");
         }
         code.push_str(&format!("pub enum {} {{\n", camelize_ident(&self.name)));
         for x in 0..self.values.len() {
@@ -210,14 +189,96 @@ impl VppJsApiEnum {
                 self.values[x].value
             ));
         }
-        // code.push_str("\t #[serde(other)]\n\t Invalid\n");
+        if !flags {
+            /*
+             * the values from a newer VPP, see the VppEnum derive; its own
+             * discriminant only has to differ from the ones of the others
+             */
+            let unused = (0..)
+                .find(|d| self.values.iter().all(|v| v.value != *d))
+                .unwrap();
+            code.push_str(&format!("\t Unknown({})={},\n", self.repr(), unused));
+        }
         code.push_str("}\n");
         code.push_str(&self.impl_default());
-        if self.if_flag() {
+        if flags {
             code.push_str(&self.generate_as_enumflag_trait());
+        } else {
+            code.push_str(&self.generate_as_u32_trait());
+        }
+        code.push_str("#[cfg(feature = \"views\")]\n");
+        code.push_str(&format!(
+            "vpp_api_encoding::owned_wire_view!({});\n",
+            camelize_ident(&self.name)
+        ));
+        code
+    }
+    /* for SizedEnum, which puts the value on the wire as the enumtype */
+    pub fn generate_as_u32_trait(&self) -> String {
+        let mut code = String::new();
+        code.push_str(&format!(
+            "impl AsU32 for {} {{\n",
+            camelize_ident(&self.name)
+        ));
+        code.push_str("\t fn as_u32(data: Self) -> u32 {\n");
+        code.push_str("\t\t match data {\n");
+        for v in &self.values {
+            code.push_str(&format!(
+                "\t\t\t {}::{} => {},\n",
+                camelize_ident(&self.name),
+                get_ident(&v.name),
+                v.value
+            ));
+        }
+        code.push_str(&format!(
+            "\t\t\t {}::Unknown(raw) => raw as u32,\n",
+            camelize_ident(&self.name)
+        ));
+        code.push_str("\t\t }\n");
+        code.push_str("\t }\n");
+        code.push_str("\t fn from_u32(data: u32) -> Option<Self> {\n");
+        code.push_str("\t\t match data {\n");
+        for v in &self.values {
+            code.push_str(&format!(
+                "\t\t\t {} => Some({}::{}),\n",
+                v.value,
+                camelize_ident(&self.name),
+                get_ident(&v.name)
+            ));
         }
+        code.push_str("\t\t\t #[cfg(not(feature = \"strict-enums\"))]\n");
+        code.push_str(&format!(
+            "\t\t\t _ => std::convert::TryFrom::try_from(data).ok().map({}::Unknown),\n",
+            camelize_ident(&self.name)
+        ));
+        code.push_str("\t\t\t #[cfg(feature = \"strict-enums\")]\n");
+        code.push_str("\t\t\t _ => None,\n");
+        code.push_str("\t\t }\n");
+        code.push_str("\t }\n");
+        code.push_str("}\n");
         code
     }
+    /// The enums which are not a plain u32 on the wire, by Rust type name
+    pub fn enum_containers(
+        enums: &[VppJsApiEnum],
+        enumflags: &[VppJsApiEnum],
+    ) -> HashMap<String, EnumContainer> {
+        let flags = enumflags
+            .iter()
+            .chain(enums.iter().filter(|e| e.is_legacy_flags()))
+            .map(|e| (camelize_ident(&e.name), EnumContainer::Flags));
+        enums
+            .iter()
+            .filter(|e| !e.is_legacy_flags())
+            .filter_map(|e| match e.info.enumtype.as_deref() {
+                Some(t) if t != "u32" => {
+                    Some((camelize_ident(&e.name), EnumContainer::Sized(t.to_string())))
+                }
+                _ => None,
+            })
+            .chain(flags)
+            .collect()
+    }
     pub fn impl_default(&self) -> String {
         let mut code = String::new();
         code.push_str(&format!(
@@ -234,6 +295,7 @@ impl VppJsApiEnum {
     }
     pub fn iter_and_generate_code(
         enums: &Vec<VppJsApiEnum>,
+        flags: bool,
         api_definition: &mut Vec<(String, String)>,
         name: &str,
         import_table: &mut Vec<(String, Vec<String>)>,
@@ -263,7 +325,7 @@ impl VppJsApiEnum {
                 return true;
             })
             .fold(String::new(), |mut acc, x| {
-                acc.push_str(&x.generate_code());
+                acc.push_str(&x.generate_code(flags || x.is_legacy_flags()));
                 acc
             })
     }
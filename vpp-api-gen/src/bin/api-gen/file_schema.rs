@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 extern crate strum;
 use crate::alias::VppJsApiAlias;
+use crate::counters::{VppJsApiCounter, VppJsApiPath};
 use crate::enums::VppJsApiEnum;
 use crate::message::VppJsApiMessage;
 use crate::services::{VppJsApiOptions, VppJsApiService};
@@ -9,28 +10,6 @@ use lazy_static::lazy_static;
 use linked_hash_map::LinkedHashMap;
 use regex::Regex;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct VppJsApiCounterElement {
-    pub name: String,
-    pub severity: String,
-    #[serde(rename = "type")]
-    pub typ: String,
-    pub units: String,
-    pub description: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct VppJsApiCounter {
-    pub name: String,
-    pub elements: Vec<VppJsApiCounterElement>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct VppJsApiPath {
-    pub path: String,
-    pub counter: String,
-}
-
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct VppJsApiFile {
     pub types: Vec<VppJsApiType>,
@@ -102,20 +81,26 @@ impl VppJsApiFile {
         let mut preamble: String = String::new();
         preamble.push_str(&VppJsApiFile::generate_header());
         preamble.push_str("#![allow(dead_code,unused_mut,unused_variables,unused_must_use,non_camel_case_types,unused_imports,non_snake_case)]\n");
-        preamble.push_str("use vpp_api_macros::{VppMessage,VppUnionIdent};\n");
+        preamble.push_str("use vpp_api_macros::{VppEnum,VppMessage,VppSerde,VppUnionIdent};\n");
         preamble.push_str("use std::convert::TryInto;\n");
         preamble.push_str("use serde::{de::DeserializeOwned, Deserialize, Serialize};\n");
         preamble.push_str("use vpp_api_encoding::typ::*;\n");
         preamble.push_str("pub use vpp_api_encoding;\n");
-        preamble.push_str("use vpp_api_message::VppApiMessage;\n");
+        preamble.push_str("#[cfg(feature = \"views\")]\n");
+        preamble.push_str("use vpp_api_encoding::view::*;\n");
+        preamble
+            .push_str("use vpp_api_message::{CounterSeverity, VppApiCounter, VppApiMessage};\n");
         preamble.push_str("use serde_repr::{Serialize_repr, Deserialize_repr};\n");
         preamble.push_str("use typenum;\n");
         let mut import_table: Vec<(String, Vec<String>)> = vec![];
+        let enum_containers = VppJsApiEnum::enum_containers(&self.enums, &self.enumflags);
         let typstructs = VppJsApiType::iter_and_generate_code(
             &self.types,
             api_definition,
             name,
+            &self,
             &mut import_table,
+            &enum_containers,
         );
         let typunions = VppJsApiType::iter_and_generate_code_union(
             &self.unions,
@@ -126,12 +111,14 @@ impl VppJsApiFile {
         );
         let typenum = VppJsApiEnum::iter_and_generate_code(
             &self.enums,
+            false,
             api_definition,
             name,
             &mut import_table,
         );
         let typenumflags = VppJsApiEnum::iter_and_generate_code(
             &self.enumflags,
+            true,
             api_definition,
             name,
             &mut import_table,
@@ -142,7 +129,8 @@ impl VppJsApiFile {
             name,
             &mut import_table,
         );
-        let typmessage = VppJsApiMessage::iter_and_generate_code(&self.messages);
+        let typmessage = VppJsApiMessage::iter_and_generate_code(&self.messages, &enum_containers);
+        let typcounters = VppJsApiCounter::iter_and_generate_code(&self.counters, &self.paths);
 
         for x in 0..import_table.len() {
             let name = &import_table[x].0;
@@ -160,6 +148,7 @@ impl VppJsApiFile {
         preamble.push_str(&typenumflags);
         preamble.push_str(&typalias);
         preamble.push_str(&typmessage);
+        preamble.push_str(&typcounters);
         preamble
     }
 }
@@ -17,6 +17,7 @@ use linked_hash_map::LinkedHashMap;
 mod alias;
 mod basetypes;
 mod code_gen;
+mod counters;
 mod enums;
 mod file_schema;
 mod message;
@@ -287,6 +288,18 @@ fn main() {
                         &opts.package_name,
                         "tests/interface_test.rs",
                     );
+                    for (template, target) in &[
+                        ("conformance-test.rs", "conformance_test.rs"),
+                        ("conformance-vectors.json", "conformance-vectors.json"),
+                        ("ip-types-test.rs", "ip_types_test.rs"),
+                    ] {
+                        copy_file_with_fixup(
+                            &opts.package_path,
+                            &format!("{}/code-templates/tests/{}", crate_dir, template),
+                            &opts.package_name,
+                            &format!("tests/{}", target),
+                        );
+                    }
                     copy_file_with_fixup(
                         &opts.package_path,
                         &format!("{}/code-templates/examples/progressive-vpp.rs", crate_dir),
@@ -438,4 +451,299 @@ mod tests {
         // std::thread::sleep(std::time::Duration::from_secs(1));
         t.disconnect();
     }*/
+    #[test]
+    fn test_counters() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("testdata/vpp/api/plugins/map.api.json");
+        let data = std::fs::read_to_string(&path).unwrap();
+        let desc = VppJsApiFile::from_str(&data).unwrap();
+        let code =
+            crate::counters::VppJsApiCounter::iter_and_generate_code(&desc.counters, &desc.paths);
+        assert!(code.contains("pub enum MapCounter {"));
+        assert!(code.contains("\tBadProtocol = 1,\n"));
+        assert!(code.contains("\"/err/ip4-map\", \"/err/ip6-map\""));
+        assert!(code.contains("MapCounter::BadProtocol => CounterSeverity::Error,"));
+
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("testdata/vpp/api/plugins/ikev2.api.json");
+        let data = std::fs::read_to_string(&path).unwrap();
+        let desc = VppJsApiFile::from_str(&data).unwrap();
+        let counters = &desc.counters;
+        assert_eq!(
+            counters[0].get_paths(counters, &desc.paths),
+            vec!["/err/ikev2-ip4", "/err/ikev2-ip6", "/err/ikev2-ip4-natt"]
+        );
+    }
+
+    #[test]
+    fn test_sized_enums() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("testdata/vpp/api/core/ip_types.api.json");
+        let data = std::fs::read_to_string(&path).unwrap();
+        let desc = VppJsApiFile::from_str(&data).unwrap();
+        let containers = crate::enums::VppJsApiEnum::enum_containers(&desc.enums, &desc.enumflags);
+        assert_eq!(
+            containers.get("AddressFamily"),
+            Some(&crate::enums::EnumContainer::Sized("u8".to_string()))
+        );
+
+        let mprefix = desc
+            .types
+            .iter()
+            .find(|t| t.type_name == "mprefix")
+            .unwrap();
+        assert!(mprefix
+            .generate_code(&desc, &containers)
+            .contains("pub af: SizedEnum<AddressFamily, u8>,\n"));
+        let af = desc
+            .enums
+            .iter()
+            .find(|e| e.name == "address_family")
+            .unwrap();
+        let code = af.generate_code(false);
+        assert!(
+            code.starts_with("#[derive(Debug, Clone, Copy, PartialEq, VppEnum)]\n#[repr(u8)]\n")
+        );
+        assert!(code.contains("impl AsU32 for AddressFamily {"));
+        assert!(code.contains("\t\t\t 1 => Some(AddressFamily::ADDRESS_IP6),\n"));
+
+        /* the values from a newer VPP, unless strict */
+        assert!(code.contains(
+            "#[cfg_attr(feature = \"strict-enums\", vpp_strict)]\npub enum AddressFamily {\n"
+        ));
+        assert!(code.contains("\t ADDRESS_IP6=1,\n\t Unknown(u8)=2,\n}\n"));
+        assert!(code.contains("\t\t\t AddressFamily::Unknown(raw) => raw as u32,\n"));
+        assert!(code.contains(
+            "\t\t\t _ => std::convert::TryFrom::try_from(data).ok().map(AddressFamily::Unknown),\n"
+        ));
+    }
+
+    #[test]
+    fn test_enum_flags() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("testdata/vpp/api/core/ipip.api.json");
+        let data = std::fs::read_to_string(&path).unwrap();
+        let desc = VppJsApiFile::from_str(&data).unwrap();
+        let containers = crate::enums::VppJsApiEnum::enum_containers(&desc.enums, &desc.enumflags);
+        /* from the enumflags section */
+        assert_eq!(
+            containers.get("TunnelFlags"),
+            Some(&crate::enums::EnumContainer::Flags)
+        );
+        let tunnel = desc.types.iter().find(|t| t.type_name == "tunnel").unwrap();
+        assert!(tunnel
+            .generate_code(&desc, &containers)
+            .contains("pub flags: EnumFlag<TunnelFlags>,\n"));
+        let flags = desc
+            .enumflags
+            .iter()
+            .find(|e| e.name == "tunnel_flags")
+            .unwrap();
+        let code = flags.generate_code(true);
+        assert!(code.contains("impl AsEnumFlag for TunnelFlags {"));
+        assert!(code.contains("\t fn from_u32(data: u32) -> Option<Self>{\n"));
+        assert!(code.contains("\t\t\t_ => None,\n"));
+        assert!(!code.contains("panic!"));
+
+        /* declared as an enum by the older API files */
+        assert_eq!(
+            containers.get("TunnelEncapDecapFlags"),
+            Some(&crate::enums::EnumContainer::Flags)
+        );
+        assert_eq!(
+            containers.get("IfStatusFlags"),
+            Some(&crate::enums::EnumContainer::Flags)
+        );
+        assert_eq!(containers.get("LinkDuplex"), None);
+    }
+
+    #[test]
+    fn test_counted_arrays() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("testdata/vpp/api/core/ip.api.json");
+        let data = std::fs::read_to_string(&path).unwrap();
+        let desc = VppJsApiFile::from_str(&data).unwrap();
+        let containers = HashMap::new();
+
+        let route = desc
+            .types
+            .iter()
+            .find(|t| t.type_name == "ip_route")
+            .unwrap();
+        let code = route.generate_code(&desc, &containers);
+        assert!(code.starts_with(
+            "// Implementation for ip_route\n#[derive(Debug, Clone, VppSerde, Default)]\n"
+        ));
+        assert!(
+            code.contains("\t#[vpp_count(n_paths)]\n\tpub paths: VariableSizeArray<FibPath>,\n")
+        );
+
+        let table = desc
+            .types
+            .iter()
+            .find(|t| t.type_name == "ip_table")
+            .unwrap();
+        let code = table.generate_code(&desc, &containers);
+        assert!(code.contains("Serialize, Deserialize"));
+        assert!(!code.contains("vpp_count"));
+    }
+
+    #[test]
+    fn test_net_conversions() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("testdata/vpp/api/core/ip_types.api.json");
+        let data = std::fs::read_to_string(&path).unwrap();
+        let desc = VppJsApiFile::from_str(&data).unwrap();
+        let containers = crate::enums::VppJsApiEnum::enum_containers(&desc.enums, &desc.enumflags);
+
+        let address = desc
+            .types
+            .iter()
+            .find(|t| t.type_name == "address")
+            .unwrap();
+        let code = address.generate_code(&desc, &containers);
+        assert!(code.contains("impl From<std::net::IpAddr> for Address {\n"));
+        assert!(code.contains("\t\t\tstd::net::IpAddr::V4(ip) => Address::Ip4(ip.into()),\n"));
+        assert!(code.contains("impl std::str::FromStr for Address {\n"));
+        /* no made up IPv4 address for an unknown family */
+        assert!(code.contains("impl std::convert::TryFrom<&Address> for std::net::IpAddr {\n"));
+        assert!(code.contains(
+            "\t\t\tAddress::Unknown(af, _) => Err(format!(\"unknown address family {}\", AsU32::as_u32(*af))),\n"
+        ));
+        assert!(!code.contains("impl From<&Address> for std::net::IpAddr"));
+        /* text in JSON, the enum value and the union on the wire */
+        assert!(code.contains("// Implementation for address\n#[derive(Debug, Clone)]\n"));
+        assert!(code.contains("\t\t\t\t_ => serializer.collect_str(self),\n"));
+        /* an unknown family has no text, it is the parts in JSON too */
+        assert!(code.contains("\t\t\t\tAddress::Unknown(tag, un) => (tag, un).serialize(serializer),\n"));
+        assert!(code.contains("vpp_api_encoding::net::deserialize_text_or_parts(deserializer, |(tag, un): (AddressFamily, AddressUnion)| Address::from_parts(tag, un))"));
+        assert!(!code.contains("Err(e) => write!(f, \"{}\", e)"));
+        assert!(code.contains("\t\t\tself.to_parts()\n"));
+        assert!(code.contains("\t\t\tlet (tag, un) = Deserialize::deserialize(deserializer)?;\n"));
+
+        let prefix = desc.types.iter().find(|t| t.type_name == "prefix").unwrap();
+        let code = prefix.generate_code(&desc, &containers);
+        assert!(code.contains("impl std::convert::TryFrom<(std::net::IpAddr, u8)> for Prefix {\n"));
+        assert!(code.contains("impl std::convert::TryFrom<&Prefix> for (std::net::IpAddr, u8) {\n"));
+        assert!(
+            code.contains("\t\tlet (address, len) = vpp_api_encoding::net::parse_prefix(s)?;\n")
+        );
+        assert!(code.contains(
+            "\t\tif serializer.is_human_readable() && !matches!(self.address, Address::Unknown(..)) {\n"
+        ));
+
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("testdata/vpp/api/core/ethernet_types.api.json");
+        let data = std::fs::read_to_string(&path).unwrap();
+        let desc = VppJsApiFile::from_str(&data).unwrap();
+        assert_eq!(
+            desc.aliases["mac_address"].generate_code("mac_address"),
+            "pub type MacAddress=vpp_api_encoding::net::MacAddress;\n"
+        );
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("testdata/vpp/api/core/ip_types.api.json");
+        let data = std::fs::read_to_string(&path).unwrap();
+        let desc = VppJsApiFile::from_str(&data).unwrap();
+        assert_eq!(
+            desc.aliases["ip6_address"].generate_code("ip6_address"),
+            "pub type Ip6Address=vpp_api_encoding::net::Ip6Address;\n"
+        );
+    }
+
+    #[test]
+    fn test_tagged_unions() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("testdata/vpp/api/core/punt.api.json");
+        let data = std::fs::read_to_string(&path).unwrap();
+        let desc = VppJsApiFile::from_str(&data).unwrap();
+        let containers = crate::enums::VppJsApiEnum::enum_containers(&desc.enums, &desc.enumflags);
+
+        let punt = desc.types.iter().find(|t| t.type_name == "punt").unwrap();
+        let tagged = punt.tagged_union(&desc, &containers).unwrap();
+        assert_eq!(tagged.tag, "PuntType");
+        assert_eq!(tagged.union, "PuntUnion");
+        assert_eq!(tagged.members[2].variant, "IpProto");
+        assert_eq!(tagged.members[2].value, "PUNT_API_TYPE_IP_PROTO");
+        /* PUNT_API_TYPE_L4 is the first value */
+        assert_eq!(tagged.default, 1);
+        let code = punt.generate_code(&desc, &containers);
+        assert!(code.contains(
+            "pub enum Punt {\n\tException(PuntException),\n\tL4(PuntL4),\n\tIpProto(PuntIpProto),\n\tUnknown(PuntType, PuntUnion),\n}\n"
+        ));
+        assert!(code.contains("#[serde(remote = \"Self\")]\n"));
+        assert!(code.contains("\t\tPunt::L4(Default::default())\n"));
+        assert!(code.contains(
+            "\t\t\tPunt::L4(v) => Ok((PuntType::PUNT_API_TYPE_L4, PuntUnion::from_member(v)?)),\n"
+        ));
+        assert!(code.contains(
+            "\t\t\tPuntType::PUNT_API_TYPE_EXCEPTION => un.to_member().map(Punt::Exception),\n"
+        ));
+        assert!(code.contains("\t\t\t_ => Ok(Punt::Unknown(tag, un)),\n"));
+        assert!(code.contains("\t\t\tPunt::serialize(self, serializer)\n"));
+        assert!(code.contains("vpp_api_encoding::owned_wire_view!(Punt);\n"));
+
+        /* one family for two unions, the mprefix keeps them */
+        let mprefix = desc
+            .types
+            .iter()
+            .find(|t| t.type_name == "mprefix")
+            .unwrap();
+        assert!(mprefix.tagged_union(&desc, &containers).is_none());
+        assert!(mprefix
+            .generate_code(&desc, &containers)
+            .contains("pub struct Mprefix {\n"));
+
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("testdata/vpp/api/plugins/lisp_types.api.json");
+        let data = std::fs::read_to_string(&path).unwrap();
+        let desc = VppJsApiFile::from_str(&data).unwrap();
+        let containers = crate::enums::VppJsApiEnum::enum_containers(&desc.enums, &desc.enumflags);
+        let eid = desc.types.iter().find(|t| t.type_name == "eid").unwrap();
+        let code = eid.generate_code(&desc, &containers);
+        assert!(code.contains(
+            "pub enum Eid {\n\tPrefix(Prefix),\n\tMac(MacAddress),\n\tNsh(Nsh),\n\tUnknown(EidType, EidAddress),\n}\n"
+        ));
+        assert!(code.contains("\t\t\tEidType::EID_TYPE_API_MAC => un.to_member().map(Eid::Mac),\n"));
+    }
+
+    #[test]
+    fn test_views() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("testdata/vpp/api/core/ip.api.json");
+        let data = std::fs::read_to_string(&path).unwrap();
+        let desc = VppJsApiFile::from_str(&data).unwrap();
+        let containers = HashMap::new();
+
+        let route = desc
+            .types
+            .iter()
+            .find(|t| t.type_name == "ip_route")
+            .unwrap();
+        let code = route.generate_code(&desc, &containers);
+        assert!(
+            code.contains("pub struct IpRouteView<'a> {\n\tbuf: &'a [u8],\n\tofs: [usize; 6],\n")
+        );
+        assert!(code.contains(
+            "\t\tlet count = <u8 as WireView>::view(&buf[ofs[3]..ofs[4]]) as usize;\n\t\tofs[5] = counted_end::<FibPath>(buf, ofs[4], count)?;\n"
+        ));
+        assert!(code.contains(
+            "\tpub fn paths(&self) -> <VariableSizeArray<FibPath> as WireView<'a>>::View {\n"
+        ));
+        assert!(
+            code.contains("impl<'a> WireView<'a> for IpRoute {\n\ttype View = IpRouteView<'a>;\n")
+        );
+
+        /* the views of the messages start after the message id */
+        let details = desc
+            .messages
+            .iter()
+            .find(|m| m.name == "ip_route_details")
+            .unwrap();
+        let code = details.generate_code(&containers);
+        assert!(code.contains("\tofs: [usize; 3],\n"));
+        assert!(code.contains("\t\tofs[1] = field_end::<u32>(buf, ofs[0])?;\n"));
+        assert!(code.contains(
+            "\tpub fn decode(&self) -> vpp_api_encoding::wire::Result<IpRouteDetails> {\n"
+        ));
+    }
 }
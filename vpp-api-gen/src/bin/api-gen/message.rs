@@ -1,7 +1,9 @@
+use crate::enums::EnumContainer;
 use crate::*;
 use serde::de::{Deserializer, SeqAccess, Visitor};
 use serde::ser::SerializeSeq;
 use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashMap;
 use std::fmt;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -89,11 +91,15 @@ impl<'de> Deserialize<'de> for VppJsApiMessage {
     }
 }
 impl VppJsApiMessage {
-    pub fn generate_code(&self) -> String {
+    pub fn generate_code(&self, enum_containers: &HashMap<String, EnumContainer>) -> String {
         let mut code = String::new();
-        code.push_str(&format!(
-            "#[derive(Debug, Clone, Serialize, Deserialize, VppMessage)]\n"
-        ));
+        if has_counted_fields(&self.fields) {
+            code.push_str("#[derive(Debug, Clone, VppSerde, VppMessage)]\n");
+        } else {
+            code.push_str(&format!(
+                "#[derive(Debug, Clone, Serialize, Deserialize, VppMessage)]\n"
+            ));
+        }
         code.push_str(&format!(
             "#[message_name_and_crc({}_{})]\n",
             self.name,
@@ -120,27 +126,27 @@ impl VppJsApiMessage {
                     },
                     _ => code.push_str(&format!("\tpub {}:,\n", get_ident(&self.fields[x].name))),
                 }
-            } else if self.fields[x].ctype.contains("flag") {
-                code.push_str(&format!(
-                    "\t pub {}: EnumFlag<{}>,\n",
-                    get_ident(&self.fields[x].name),
-                    get_type(&self.fields[x].ctype)
-                ));
             } else {
+                if let Some(count) = get_count_field(&self.fields, &self.fields[x]) {
+                    code.push_str(&format!("\t#[vpp_count({})]\n", get_ident(count)));
+                }
                 code.push_str(&format!("\tpub {}: ", get_ident(&self.fields[x].name)));
                 match &self.fields[x].maybe_size {
                     Some(cont) => match cont {
                         VppJsApiFieldSize::Fixed(len) => code.push_str(&format!(
                             "FixedSizeArray<{}, typenum::U{}>,\n",
-                            get_type(&self.fields[x].ctype),
+                            get_rust_type_from_ctype(enum_containers, &self.fields[x].ctype),
                             len
                         )),
                         VppJsApiFieldSize::Variable(t) => code.push_str(&format!(
                             "VariableSizeArray<{}>,\n",
-                            get_type(&self.fields[x].ctype)
+                            get_rust_type_from_ctype(enum_containers, &self.fields[x].ctype)
                         )),
                     },
-                    _ => code.push_str(&format!("{},\n", get_type(&self.fields[x].ctype))),
+                    _ => code.push_str(&format!(
+                        "{},\n",
+                        get_rust_type_from_ctype(enum_containers, &self.fields[x].ctype)
+                    )),
                     /*code.push_str(&format!(
                         "\tpub {}: {},\n",
                         get_ident(&self.fields[x].name),
@@ -150,6 +156,13 @@ impl VppJsApiMessage {
             }
         }
         code.push_str("}\n");
+        let fields: Vec<VppJsApiMessageFieldDef> = self
+            .fields
+            .iter()
+            .filter(|f| f.name != "_vl_msg_id")
+            .cloned()
+            .collect();
+        code.push_str(&generate_view(&self.name, &fields, enum_containers));
         // self.gen_impl_messages(&mut code);
         code
     }
@@ -166,9 +179,12 @@ impl VppJsApiMessage {
         file.push_str(&format!("\t }}\n"));
         file.push_str(&format!("}}\n"));
     }
-    pub fn iter_and_generate_code(messages: &Vec<VppJsApiMessage>) -> String {
+    pub fn iter_and_generate_code(
+        messages: &Vec<VppJsApiMessage>,
+        enum_containers: &HashMap<String, EnumContainer>,
+    ) -> String {
         messages.iter().fold(String::new(), |mut acc, x| {
-            acc.push_str(&x.generate_code());
+            acc.push_str(&x.generate_code(enum_containers));
             acc
         })
     }
@@ -1,5 +1,6 @@
 use std::string::ToString;
 extern crate strum;
+use crate::enums::EnumContainer;
 use crate::file_schema::*;
 use crate::types::*;
 use crate::Opts;
@@ -33,7 +34,7 @@ pub fn parse_api_tree(opts: &Opts, root: &str, map: &mut LinkedHashMap<String, V
      * Admittedly lexicographic sort is a bit of a hack/overkill,
      * but it does that part of the job very nicely.
      */
-    entries.sort_by(|a,b| a.path().cmp(&b.path()));
+    entries.sort_by(|a, b| a.path().cmp(&b.path()));
 
     for entry in &entries {
         let path = entry.path();
@@ -87,30 +88,152 @@ pub fn get_ident(api_ident: &str) -> String {
 }
 
 pub fn get_rust_type_from_ctype(
-    _opts: &Opts,
-    enum_containers: &HashMap<String, String>,
+    enum_containers: &HashMap<String, EnumContainer>,
     ctype: &str,
 ) -> String {
-    use convert_case::{Case, Casing};
+    let rtype = get_type(ctype);
+    /* if the candidate Rust type is an enum, we need to create
+    a parametrized type such that we knew which size to
+    deal with at serialization/deserialization time */
+    match enum_containers.get(&rtype) {
+        Some(EnumContainer::Sized(container)) => format!("SizedEnum<{}, {}>", rtype, container),
+        Some(EnumContainer::Flags) => format!("EnumFlag<{}>", rtype),
+        None => rtype,
+    }
+}
 
-    let rtype = {
-        let rtype: String = if ctype.starts_with("vl_api_") {
-            let ctype_trimmed = ctype.trim_start_matches("vl_api_").trim_end_matches("_t");
-            ctype_trimmed.to_case(Case::UpperCamel)
-        } else {
-            format!("{}", ctype)
-        };
-        /* if the candidate Rust type is an enum, we need to create
-        a parametrized type such that we knew which size to
-        deal with at serialization/deserialization time */
+/*
+ * The field holding the element count of a variable size array,
+ * if it is one of the fields before the array.
+ */
+pub fn get_count_field<'a>(
+    fields: &'a [VppJsApiMessageFieldDef],
+    fld: &VppJsApiMessageFieldDef,
+) -> Option<&'a str> {
+    use crate::VppJsApiFieldSize::*;
+    match &fld.maybe_size {
+        Some(Variable(Some(count))) if fld.ctype != "string" => fields
+            .iter()
+            .take_while(|f| f.name != fld.name)
+            .find(|f| &f.name == count)
+            .map(|f| &f.name[..]),
+        _ => None,
+    }
+}
 
-        if let Some(container) = enum_containers.get(&rtype) {
-            format!("SizedEnum<{}, {}>", rtype, container)
-        } else {
-            rtype
+pub fn has_counted_fields(fields: &[VppJsApiMessageFieldDef]) -> bool {
+    fields.iter().any(|f| get_count_field(fields, f).is_some())
+}
+
+/* the Rust type of a field, as the struct has it */
+pub fn get_field_type(
+    enum_containers: &HashMap<String, EnumContainer>,
+    fld: &VppJsApiMessageFieldDef,
+) -> String {
+    use crate::VppJsApiFieldSize::*;
+    if fld.ctype == "string" {
+        match &fld.maybe_size {
+            Some(Fixed(len)) => format!("FixedSizeString<typenum::U{}>", len),
+            _ => format!("VariableSizeString"),
         }
-    };
-    rtype
+    } else {
+        let rtype = get_rust_type_from_ctype(enum_containers, &fld.ctype);
+        match &fld.maybe_size {
+            Some(Fixed(len)) => format!("FixedSizeArray<{}, typenum::U{}>", rtype, len),
+            Some(Variable(_)) => format!("VariableSizeArray<{}>", rtype),
+            None => rtype,
+        }
+    }
+}
+
+/*
+ * The borrowed view of a type or message (see vpp_api_encoding::view):
+ * new() finds where each field is, and the accessors decode them
+ * only when called.
+ */
+pub fn generate_view(
+    name: &str,
+    fields: &[VppJsApiMessageFieldDef],
+    enum_containers: &HashMap<String, EnumContainer>,
+) -> String {
+    let rname = camelize_ident(name);
+    let nofs = fields.len() + 1;
+    let mut code = String::new();
+    code.push_str("#[cfg(feature = \"views\")]\n");
+    code.push_str("#[derive(Debug, Clone, Copy)]\n");
+    code.push_str(&format!("pub struct {}View<'a> {{\n", rname));
+    code.push_str("\tbuf: &'a [u8],\n");
+    code.push_str(&format!("\tofs: [usize; {}],\n", nofs));
+    code.push_str("}\n");
+    code.push_str("#[cfg(feature = \"views\")]\n");
+    code.push_str(&format!("impl<'a> {}View<'a> {{\n", rname));
+    code.push_str("\tpub fn new(buf: &'a [u8]) -> vpp_api_encoding::wire::Result<Self> {\n");
+    if fields.is_empty() {
+        code.push_str("\t\tlet ofs = [0; 1];\n");
+    } else {
+        code.push_str(&format!("\t\tlet mut ofs = [0; {}];\n", nofs));
+    }
+    for (i, fld) in fields.iter().enumerate() {
+        match get_count_field(fields, fld) {
+            Some(count) => {
+                let c = fields.iter().position(|f| f.name == count).unwrap();
+                code.push_str(&format!(
+                    "\t\tlet count = <{} as WireView>::view(&buf[ofs[{}]..ofs[{}]]) as usize;\n",
+                    get_field_type(enum_containers, &fields[c]),
+                    c,
+                    c + 1
+                ));
+                code.push_str(&format!(
+                    "\t\tofs[{}] = counted_end::<{}>(buf, ofs[{}], count)?;\n",
+                    i + 1,
+                    get_rust_type_from_ctype(enum_containers, &fld.ctype),
+                    i
+                ));
+            }
+            None => code.push_str(&format!(
+                "\t\tofs[{}] = field_end::<{}>(buf, ofs[{}])?;\n",
+                i + 1,
+                get_field_type(enum_containers, fld),
+                i
+            )),
+        }
+    }
+    code.push_str(&format!(
+        "\t\tOk({}View {{ buf: &buf[..ofs[{}]], ofs }})\n",
+        rname,
+        nofs - 1
+    ));
+    code.push_str("\t}\n");
+    for (i, fld) in fields.iter().enumerate() {
+        let ftype = get_field_type(enum_containers, fld);
+        /* cnat has a field called "new", do not clash with the methods */
+        let fname = match fld.name.as_str() {
+            "new" | "wire_size" | "decode" => format!("{}_", fld.name),
+            _ => get_ident(&fld.name),
+        };
+        code.push_str(&format!(
+            "\tpub fn {}(&self) -> <{} as WireView<'a>>::View {{\n",
+            fname, ftype
+        ));
+        code.push_str(&format!(
+            "\t\t<{} as WireView>::view(&self.buf[self.ofs[{}]..self.ofs[{}]])\n",
+            ftype,
+            i,
+            i + 1
+        ));
+        code.push_str("\t}\n");
+    }
+    code.push_str("\tpub fn wire_size(&self) -> usize {\n");
+    code.push_str("\t\tself.buf.len()\n");
+    code.push_str("\t}\n");
+    code.push_str(&format!(
+        "\tpub fn decode(&self) -> vpp_api_encoding::wire::Result<{}> {{\n",
+        rname
+    ));
+    code.push_str("\t\tvpp_api_encoding::wire::from_bytes(self.buf)\n");
+    code.push_str("\t}\n");
+    code.push_str("}\n");
+    code
 }
 
 pub fn get_rust_field_name(opts: &Opts, name: &str) -> String {
@@ -123,12 +246,12 @@ pub fn get_rust_field_name(opts: &Opts, name: &str) -> String {
 
 pub fn get_rust_field_type(
     opts: &Opts,
-    enum_containers: &HashMap<String, String>,
+    enum_containers: &HashMap<String, EnumContainer>,
     fld: &VppJsApiMessageFieldDef,
     is_last: bool,
 ) -> String {
     use crate::VppJsApiFieldSize::*;
-    let rtype = get_rust_type_from_ctype(opts, enum_containers, &fld.ctype);
+    let rtype = get_rust_type_from_ctype(enum_containers, &fld.ctype);
     let full_rtype = if let Some(size) = &fld.maybe_size {
         match size {
             Variable(max_var) => {
@@ -2,10 +2,15 @@ use serde::ser::SerializeSeq;
 use serde::{Deserialize, Serialize, Serializer};
 extern crate strum;
 use crate::basetypes::{field_size, maxSizeUnion, sizeof_alias, sizeof_struct};
+use crate::enums::EnumContainer;
 use crate::file_schema::VppJsApiFile;
-use crate::parser_helper::{camelize_ident, get_ident, get_type};
+use crate::parser_helper::{
+    camelize_ident, generate_view, get_count_field, get_field_type, get_ident,
+    get_rust_type_from_ctype, get_type, has_counted_fields,
+};
 use linked_hash_map::LinkedHashMap;
 use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use std::collections::HashMap;
 use std::fmt;
 
 // This holds the Type and Union Data
@@ -67,19 +72,53 @@ impl<'de> Deserialize<'de> for VppJsApiType {
         deserializer.deserialize_seq(VppJsApiTypeVisitor)
     }
 }
+/* a member of the union of a tagged union, and the enum value telling about it */
+#[derive(Debug, Clone)]
+pub struct TaggedMember {
+    pub variant: String,
+    pub rtype: String,
+    pub value: String,
+}
+
+/* a type made of an enum and the union it tells about, see tagged_union() */
+#[derive(Debug, Clone)]
+pub struct TaggedUnion {
+    pub tag: String,
+    pub union: String,
+    pub members: Vec<TaggedMember>,
+    pub default: usize,
+}
+
 impl VppJsApiType {
-    pub fn generate_code(&self) -> String {
+    pub fn generate_code(
+        &self,
+        file: &VppJsApiFile,
+        enum_containers: &HashMap<String, EnumContainer>,
+    ) -> String {
+        if let Some(tagged) = self.tagged_union(file, enum_containers) {
+            return self.generate_tagged_union(&tagged);
+        }
         let mut code = String::new();
         code.push_str(&format!("// Implementation for {}\n", &self.type_name));
-        code.push_str(&format!(
-            "#[derive(Debug, Clone, Serialize, Deserialize, Default)]\n"
-        ));
+        if has_counted_fields(&self.fields) {
+            code.push_str("#[derive(Debug, Clone, VppSerde, Default)]\n");
+        } else if self.is_text() {
+            /* serde is in generate_text_serde() */
+            code.push_str("#[derive(Debug, Clone, Default)]\n");
+        } else {
+            code.push_str(&format!(
+                "#[derive(Debug, Clone, Serialize, Deserialize, Default)]\n"
+            ));
+        }
         code.push_str(&format!(
             "pub struct {} {{\n",
             camelize_ident(&self.type_name)
         ));
         for x in 0..self.fields.len() {
             // println!("{:#?}", self.fields);
+            if let Some(count) = get_count_field(&self.fields, &self.fields[x]) {
+                code.push_str(&format!("\t#[vpp_count({})]\n", get_ident(count)));
+            }
             code.push_str(&format!("\tpub {}: ", get_ident(&self.fields[x].name)));
             if self.fields[x].ctype == "string" {
                 match &self.fields[x].maybe_size {
@@ -94,25 +133,455 @@ impl VppJsApiType {
                     },
                     _ => code.push_str(&format!("{},\n", get_ident(&self.fields[x].name))),
                 }
-            } else if self.fields[x].ctype.contains("flag") {
-                code.push_str(&format!("EnumFlag<{}>,\n", get_type(&self.fields[x].ctype)));
             } else {
                 match &self.fields[x].maybe_size {
                     Some(cont) => match cont {
                         VppJsApiFieldSize::Fixed(len) => code.push_str(&format!(
                             "FixedSizeArray<{}, typenum::U{}>,\n",
-                            get_type(&self.fields[x].ctype),
+                            get_rust_type_from_ctype(enum_containers, &self.fields[x].ctype),
                             len
                         )),
                         VppJsApiFieldSize::Variable(t) => code.push_str(&format!(
                             "VariableSizeArray<{}>,\n",
-                            get_type(&self.fields[x].ctype)
+                            get_rust_type_from_ctype(enum_containers, &self.fields[x].ctype)
                         )),
                     },
-                    _ => code.push_str(&format!("{},\n", get_type(&self.fields[x].ctype))),
+                    _ => code.push_str(&format!(
+                        "{},\n",
+                        get_rust_type_from_ctype(enum_containers, &self.fields[x].ctype)
+                    )),
+                }
+            }
+        }
+        code.push_str("}\n");
+        code.push_str(&generate_view(
+            &self.type_name,
+            &self.fields,
+            enum_containers,
+        ));
+        code.push_str(&self.generate_wire_view(enum_containers));
+        if self.is_text() {
+            code.push_str(&self.generate_text_serde());
+        }
+        code.push_str(&self.generate_net_conversions());
+        code
+    }
+    /*
+     * A type which is only an enum and the union it tells about, like the
+     * address (af, un), is a rust enum with a variant per union member.
+     * Each member goes with the one enum value ending in its name, ip4
+     * with ADDRESS_IP4 or prefix with EID_TYPE_API_PREFIX; the types with
+     * a member that has none keep the union.
+     */
+    pub fn tagged_union(
+        &self,
+        file: &VppJsApiFile,
+        enum_containers: &HashMap<String, EnumContainer>,
+    ) -> Option<TaggedUnion> {
+        if self.fields.len() != 2 || self.fields.iter().any(|f| f.maybe_size.is_some()) {
+            return None;
+        }
+        let api_name = |ctype: &str| {
+            ctype
+                .trim_start_matches("vl_api_")
+                .trim_end_matches("_t")
+                .to_string()
+        };
+        let tag = file
+            .enums
+            .iter()
+            .find(|e| e.name == api_name(&self.fields[0].ctype) && !e.is_legacy_flags())?;
+        let union = file
+            .unions
+            .iter()
+            .find(|u| u.type_name == api_name(&self.fields[1].ctype))?;
+        let mut members = vec![];
+        for m in &union.fields {
+            let suffix = format!("_{}", m.name.to_uppercase());
+            let mut values = tag.values.iter().filter(|v| v.name.ends_with(&suffix));
+            let value = values.next()?;
+            if values.next().is_some() {
+                return None;
+            }
+            members.push(TaggedMember {
+                variant: camelize_ident(&m.name),
+                rtype: get_field_type(enum_containers, m),
+                value: get_ident(&value.name),
+            });
+        }
+        if members.is_empty() {
+            return None;
+        }
+        /* the default enum value is the first one */
+        let default = tag
+            .values
+            .iter()
+            .find_map(|v| members.iter().position(|m| m.value == get_ident(&v.name)))
+            .unwrap_or(0);
+        Some(TaggedUnion {
+            tag: camelize_ident(&tag.name),
+            union: camelize_ident(&union.type_name),
+            members,
+            default,
+        })
+    }
+    pub fn generate_tagged_union(&self, tagged: &TaggedUnion) -> String {
+        let rname = camelize_ident(&self.type_name);
+        let mut code = String::new();
+        code.push_str(&format!("// Implementation for {}\n", &self.type_name));
+        if self.is_text() {
+            code.push_str("#[derive(Debug, Clone)]\n");
+        } else {
+            /* the derived serde is for the human readable formats only */
+            code.push_str("#[derive(Debug, Clone, Serialize, Deserialize)]\n");
+            code.push_str("#[serde(remote = \"Self\")]\n");
+        }
+        code.push_str(&format!("pub enum {} {{\n", rname));
+        for m in &tagged.members {
+            code.push_str(&format!("\t{}({}),\n", m.variant, m.rtype));
+        }
+        /* the union bytes as they are, for an enum value none of the members goes with */
+        code.push_str(&format!("\tUnknown({}, {}),\n", tagged.tag, tagged.union));
+        code.push_str("}\n");
+        code.push_str(&format!("impl Default for {} {{\n", rname));
+        code.push_str("\tfn default() -> Self {\n");
+        code.push_str(&format!(
+            "\t\t{}::{}(Default::default())\n",
+            rname, tagged.members[tagged.default].variant
+        ));
+        code.push_str("\t}\n");
+        code.push_str("}\n");
+        code.push_str(&format!("impl {} {{\n", rname));
+        code.push_str("\t/// The enum value and the union, as they are on the wire\n");
+        code.push_str(&format!(
+            "\tpub fn to_parts(&self) -> Result<({}, {}), String> {{\n",
+            tagged.tag, tagged.union
+        ));
+        code.push_str("\t\tmatch self {\n");
+        for m in &tagged.members {
+            code.push_str(&format!(
+                "\t\t\t{}::{}(v) => Ok(({}::{}, {}::from_member(v)?)),\n",
+                rname, m.variant, tagged.tag, m.value, tagged.union
+            ));
+        }
+        code.push_str(&format!(
+            "\t\t\t{}::Unknown(tag, un) => Ok((*tag, un.clone())),\n",
+            rname
+        ));
+        code.push_str("\t\t}\n");
+        code.push_str("\t}\n");
+        code.push_str("\t/// The member the enum value tells about, decoded from the union\n");
+        code.push_str(&format!(
+            "\tpub fn from_parts(tag: {}, un: {}) -> Result<Self, String> {{\n",
+            tagged.tag, tagged.union
+        ));
+        code.push_str("\t\tmatch tag {\n");
+        for m in &tagged.members {
+            code.push_str(&format!(
+                "\t\t\t{}::{} => un.to_member().map({}::{}),\n",
+                tagged.tag, m.value, rname, m.variant
+            ));
+        }
+        code.push_str(&format!("\t\t\t_ => Ok({}::Unknown(tag, un)),\n", rname));
+        code.push_str("\t\t}\n");
+        code.push_str("\t}\n");
+        code.push_str("}\n");
+        code.push_str(&self.generate_tagged_serde(tagged));
+        code.push_str("#[cfg(feature = \"views\")]\n");
+        code.push_str(&format!("vpp_api_encoding::owned_wire_view!({});\n", rname));
+        code.push_str(&self.generate_net_conversions());
+        code
+    }
+    /*
+     * The enum value and the union on the wire; the text or the derived
+     * serde of a variant in the human readable formats. An unknown enum
+     * value has no text, it is the enum value and the union there too.
+     */
+    pub fn generate_tagged_serde(&self, tagged: &TaggedUnion) -> String {
+        let rname = camelize_ident(&self.type_name);
+        let (ser, de) = if self.is_text() {
+            (
+                format!(
+                    "match self {{\n\t\t\t\t{}::Unknown(tag, un) => (tag, un).serialize(serializer),\n\t\t\t\t_ => serializer.collect_str(self),\n\t\t\t}}",
+                    rname
+                ),
+                format!(
+                    "vpp_api_encoding::net::deserialize_text_or_parts(deserializer, |(tag, un): ({}, {})| {}::from_parts(tag, un))",
+                    tagged.tag, tagged.union, rname
+                ),
+            )
+        } else {
+            (
+                format!("{}::serialize(self, serializer)", rname),
+                format!("{}::deserialize(deserializer)", rname),
+            )
+        };
+        let mut code = String::new();
+        code.push_str(&format!("impl Serialize for {} {{\n", rname));
+        code.push_str(
+            "\tfn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {\n",
+        );
+        code.push_str("\t\tif serializer.is_human_readable() {\n");
+        code.push_str(&format!("\t\t\t{}\n", ser));
+        code.push_str("\t\t} else {\n");
+        code.push_str("\t\t\tself.to_parts()\n");
+        code.push_str("\t\t\t\t.map_err(serde::ser::Error::custom)?\n");
+        code.push_str("\t\t\t\t.serialize(serializer)\n");
+        code.push_str("\t\t}\n");
+        code.push_str("\t}\n");
+        code.push_str("}\n");
+        code.push_str(&format!("impl<'de> Deserialize<'de> for {} {{\n", rname));
+        code.push_str(
+            "\tfn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {\n",
+        );
+        code.push_str("\t\tif deserializer.is_human_readable() {\n");
+        code.push_str(&format!("\t\t\t{}\n", de));
+        code.push_str("\t\t} else {\n");
+        code.push_str("\t\t\tlet (tag, un) = Deserialize::deserialize(deserializer)?;\n");
+        code.push_str(&format!(
+            "\t\t\t{}::from_parts(tag, un).map_err(serde::de::Error::custom)\n",
+            rname
+        ));
+        code.push_str("\t\t}\n");
+        code.push_str("\t}\n");
+        code.push_str("}\n");
+        code
+    }
+    /* the types which are text in the human readable serde formats */
+    pub fn is_text(&self) -> bool {
+        matches!(self.type_name.as_str(), "address" | "prefix")
+    }
+    /*
+     * The address and prefix of ip_types convert to and from std::net,
+     * and print and parse as "10.0.0.1" and "10.0.0.0/24"; that is also
+     * what they are in JSON or YAML.
+     */
+    pub fn generate_net_conversions(&self) -> String {
+        let mut code = String::new();
+        match self.type_name.as_str() {
+            "address" => {
+                code.push_str("impl From<std::net::IpAddr> for Address {\n");
+                code.push_str("\tfn from(ip: std::net::IpAddr) -> Self {\n");
+                code.push_str("\t\tmatch ip {\n");
+                code.push_str("\t\t\tstd::net::IpAddr::V4(ip) => Address::Ip4(ip.into()),\n");
+                code.push_str("\t\t\tstd::net::IpAddr::V6(ip) => Address::Ip6(ip.into()),\n");
+                code.push_str("\t\t}\n");
+                code.push_str("\t}\n");
+                code.push_str("}\n");
+                for v in &["Ipv4Addr", "Ipv6Addr"] {
+                    code.push_str(&format!("impl From<std::net::{}> for Address {{\n", v));
+                    code.push_str(&format!("\tfn from(ip: std::net::{}) -> Self {{\n", v));
+                    code.push_str("\t\tstd::net::IpAddr::from(ip).into()\n");
+                    code.push_str("\t}\n");
+                    code.push_str("}\n");
                 }
+                /* an unknown family is no address at all, its union bytes are kept as they are */
+                code.push_str("impl std::convert::TryFrom<&Address> for std::net::IpAddr {\n");
+                code.push_str("\ttype Error = String;\n");
+                code.push_str("\tfn try_from(address: &Address) -> Result<Self, String> {\n");
+                code.push_str("\t\tmatch address {\n");
+                code.push_str(
+                    "\t\t\tAddress::Ip4(ip) => Ok(std::net::Ipv4Addr::from(ip.clone()).into()),\n",
+                );
+                code.push_str(
+                    "\t\t\tAddress::Ip6(ip) => Ok(std::net::Ipv6Addr::from(ip.clone()).into()),\n",
+                );
+                code.push_str(
+                    "\t\t\tAddress::Unknown(af, _) => Err(format!(\"unknown address family {}\", AsU32::as_u32(*af))),\n",
+                );
+                code.push_str("\t\t}\n");
+                code.push_str("\t}\n");
+                code.push_str("}\n");
+                code.push_str("impl std::convert::TryFrom<Address> for std::net::IpAddr {\n");
+                code.push_str("\ttype Error = String;\n");
+                code.push_str("\tfn try_from(address: Address) -> Result<Self, String> {\n");
+                code.push_str("\t\tstd::convert::TryFrom::try_from(&address)\n");
+                code.push_str("\t}\n");
+                code.push_str("}\n");
+                /* the unknown one prints as its parts, which do not parse back */
+                code.push_str("impl std::fmt::Display for Address {\n");
+                code.push_str(
+                    "\tfn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {\n",
+                );
+                code.push_str("\t\tmatch self {\n");
+                code.push_str("\t\t\tAddress::Ip4(ip) => write!(f, \"{}\", ip),\n");
+                code.push_str("\t\t\tAddress::Ip6(ip) => write!(f, \"{}\", ip),\n");
+                code.push_str("\t\t\tAddress::Unknown(af, un) => {\n");
+                code.push_str(
+                    "\t\t\t\tlet un = vpp_api_encoding::wire::to_bytes(un).map_err(|_| std::fmt::Error)?;\n",
+                );
+                code.push_str("\t\t\t\twrite!(f, \"<address family {}:\", AsU32::as_u32(*af))?;\n");
+                code.push_str("\t\t\t\tfor b in un {\n");
+                code.push_str("\t\t\t\t\twrite!(f, \" {:02x}\", b)?;\n");
+                code.push_str("\t\t\t\t}\n");
+                code.push_str("\t\t\t\twrite!(f, \">\")\n");
+                code.push_str("\t\t\t}\n");
+                code.push_str("\t\t}\n");
+                code.push_str("\t}\n");
+                code.push_str("}\n");
+                code.push_str("impl std::str::FromStr for Address {\n");
+                code.push_str("\ttype Err = String;\n");
+                code.push_str("\tfn from_str(s: &str) -> Result<Self, String> {\n");
+                code.push_str("\t\ts.parse::<std::net::IpAddr>()\n");
+                code.push_str("\t\t\t.map(Address::from)\n");
+                code.push_str("\t\t\t.map_err(|e| format!(\"invalid address {:?}: {}\", s, e))\n");
+                code.push_str("\t}\n");
+                code.push_str("}\n");
+            }
+            "prefix" => {
+                code.push_str("impl std::convert::TryFrom<(std::net::IpAddr, u8)> for Prefix {\n");
+                code.push_str("\ttype Error = String;\n");
+                code.push_str(
+                    "\tfn try_from((address, len): (std::net::IpAddr, u8)) -> Result<Self, String> {\n",
+                );
+                code.push_str("\t\tvpp_api_encoding::net::check_prefix_len(&address, len)?;\n");
+                code.push_str("\t\tOk(Prefix {\n");
+                code.push_str("\t\t\taddress: address.into(),\n");
+                code.push_str("\t\t\tlen,\n");
+                code.push_str("\t\t})\n");
+                code.push_str("\t}\n");
+                code.push_str("}\n");
+                code.push_str("impl std::convert::TryFrom<&Prefix> for (std::net::IpAddr, u8) {\n");
+                code.push_str("\ttype Error = String;\n");
+                code.push_str("\tfn try_from(prefix: &Prefix) -> Result<Self, String> {\n");
+                code.push_str(
+                    "\t\tOk((std::convert::TryFrom::try_from(&prefix.address)?, prefix.len))\n",
+                );
+                code.push_str("\t}\n");
+                code.push_str("}\n");
+                code.push_str("impl std::fmt::Display for Prefix {\n");
+                code.push_str(
+                    "\tfn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {\n",
+                );
+                code.push_str("\t\twrite!(f, \"{}/{}\", self.address, self.len)\n");
+                code.push_str("\t}\n");
+                code.push_str("}\n");
+                code.push_str("impl std::str::FromStr for Prefix {\n");
+                code.push_str("\ttype Err = String;\n");
+                code.push_str("\tfn from_str(s: &str) -> Result<Self, String> {\n");
+                code.push_str(
+                    "\t\tlet (address, len) = vpp_api_encoding::net::parse_prefix(s)?;\n",
+                );
+                code.push_str("\t\tOk(Prefix {\n");
+                code.push_str("\t\t\taddress: address.into(),\n");
+                code.push_str("\t\t\tlen,\n");
+                code.push_str("\t\t})\n");
+                code.push_str("\t}\n");
+                code.push_str("}\n");
+                code.push_str("impl std::convert::TryFrom<&str> for Prefix {\n");
+                code.push_str("\ttype Error = String;\n");
+                code.push_str("\tfn try_from(s: &str) -> Result<Self, String> {\n");
+                code.push_str("\t\ts.parse()\n");
+                code.push_str("\t}\n");
+                code.push_str("}\n");
             }
+            _ => {}
         }
+        code
+    }
+    /* a type can be a field of the others, so it gets a WireView too */
+    pub fn generate_wire_view(&self, enum_containers: &HashMap<String, EnumContainer>) -> String {
+        let rname = camelize_ident(&self.type_name);
+        let sizes: Vec<String> = self
+            .fields
+            .iter()
+            .map(|f| {
+                format!(
+                    "<{} as WireView<'a>>::FIXED_SIZE",
+                    get_field_type(enum_containers, f)
+                )
+            })
+            .collect();
+        let mut code = String::new();
+        code.push_str("#[cfg(feature = \"views\")]\n");
+        code.push_str(&format!("impl<'a> WireView<'a> for {} {{\n", rname));
+        code.push_str(&format!("\ttype View = {}View<'a>;\n", rname));
+        code.push_str(&format!(
+            "\tconst FIXED_SIZE: Option<usize> = fixed_size_sum(&[{}]);\n",
+            sizes.join(", ")
+        ));
+        code.push_str("\tfn wire_size(buf: &'a [u8]) -> vpp_api_encoding::wire::Result<usize> {\n");
+        code.push_str(&format!(
+            "\t\t{}View::new(buf).map(|v| v.wire_size())\n",
+            rname
+        ));
+        code.push_str("\t}\n");
+        code.push_str(&format!(
+            "\tfn view(buf: &'a [u8]) -> {}View<'a> {{\n",
+            rname
+        ));
+        code.push_str(&format!(
+            "\t\t{}View::new(buf).expect(\"checked by wire_size()\")\n",
+            rname
+        ));
+        code.push_str("\t}\n");
+        code.push_str("}\n");
+        code
+    }
+    /*
+     * The fields one after another on the wire, Display/FromStr for the
+     * others; but an address of an unknown family has no text form, so
+     * a prefix of one is its fields in the human readable formats too.
+     */
+    pub fn generate_text_serde(&self) -> String {
+        let rname = camelize_ident(&self.type_name);
+        let fields: Vec<String> = self.fields.iter().map(|f| get_ident(&f.name)).collect();
+        let no_text: Vec<String> = self
+            .fields
+            .iter()
+            .filter(|f| f.ctype == "vl_api_address_t")
+            .map(|f| {
+                format!(
+                    " && !matches!(self.{}, Address::Unknown(..))",
+                    get_ident(&f.name)
+                )
+            })
+            .collect();
+        let mut code = String::new();
+        code.push_str(&format!("impl Serialize for {} {{\n", rname));
+        code.push_str(
+            "\tfn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {\n",
+        );
+        code.push_str(&format!(
+            "\t\tif serializer.is_human_readable(){} {{\n",
+            no_text.concat()
+        ));
+        code.push_str("\t\t\tserializer.collect_str(self)\n");
+        code.push_str("\t\t} else {\n");
+        code.push_str(&format!(
+            "\t\t\t({},).serialize(serializer)\n",
+            fields
+                .iter()
+                .map(|f| format!("&self.{}", f))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        code.push_str("\t\t}\n");
+        code.push_str("\t}\n");
+        code.push_str("}\n");
+        code.push_str(&format!("impl<'de> Deserialize<'de> for {} {{\n", rname));
+        code.push_str(
+            "\tfn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {\n",
+        );
+        code.push_str("\t\tif deserializer.is_human_readable() {\n");
+        code.push_str(&format!(
+            "\t\t\tvpp_api_encoding::net::deserialize_text_or_parts(deserializer, |({},)| Ok({} {{ {} }}))\n",
+            fields.join(", "),
+            rname,
+            fields.join(", ")
+        ));
+        code.push_str("\t\t} else {\n");
+        code.push_str(&format!(
+            "\t\t\tlet ({},) = Deserialize::deserialize(deserializer)?;\n",
+            fields.join(", ")
+        ));
+        code.push_str(&format!(
+            "\t\t\tOk({} {{ {} }})\n",
+            rname,
+            fields.join(", ")
+        ));
+        code.push_str("\t\t}\n");
+        code.push_str("\t}\n");
         code.push_str("}\n");
         code
     }
@@ -132,13 +601,20 @@ impl VppJsApiType {
             camelize_ident(&self.type_name),
             unionsize
         ));
+        code.push_str("#[cfg(feature = \"views\")]\n");
+        code.push_str(&format!(
+            "vpp_api_encoding::owned_wire_view!({});\n",
+            camelize_ident(&self.type_name)
+        ));
         code
     }
     pub fn iter_and_generate_code(
         structs: &Vec<VppJsApiType>,
         api_definition: &mut Vec<(String, String)>,
         name: &str,
+        file: &VppJsApiFile,
         import_table: &mut Vec<(String, Vec<String>)>,
+        enum_containers: &HashMap<String, EnumContainer>,
     ) -> String {
         structs
             .iter()
@@ -167,7 +643,7 @@ impl VppJsApiType {
                 return true;
             })
             .fold(String::new(), |mut acc, x| {
-                acc.push_str(&x.generate_code());
+                acc.push_str(&x.generate_code(file, enum_containers));
                 acc
             })
     }
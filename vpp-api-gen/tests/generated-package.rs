@@ -0,0 +1,81 @@
+/*
+ * Generates a package from a part of the test API files and runs its
+ * tests that need no VPP: the conformance vectors and the ip_types
+ * serde. The package gets a target directory of its own, the one of
+ * the workspace being locked by the cargo running this test.
+ */
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/* the API files of the messages of the vectors, with their imports */
+const API_FILES: &[&str] = &[
+    "core/ethernet_types.api.json",
+    "core/interface.api.json",
+    "core/interface_types.api.json",
+    "core/ip_neighbor.api.json",
+    "core/ip_types.api.json",
+    "core/punt.api.json",
+    "core/vpe.api.json",
+    "core/vpe_types.api.json",
+    "plugins/acl.api.json",
+    "plugins/acl_types.api.json",
+];
+
+#[test]
+fn test_generated_package() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let work = Path::new(env!("CARGO_TARGET_TMPDIR")).join("generated-package");
+    let api_dir = work.join("api");
+    let package_dir = work.join("package");
+
+    /* the target directory stays, to build only the package again */
+    for dir in [&api_dir, &package_dir] {
+        if dir.exists() {
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+    for file in API_FILES {
+        let to = api_dir.join(file);
+        fs::create_dir_all(to.parent().unwrap()).unwrap();
+        fs::copy(manifest_dir.join("testdata/vpp/api").join(file), to).unwrap();
+    }
+    fs::create_dir_all(&package_dir).unwrap();
+
+    let workspace = manifest_dir.parent().unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_api-gen"))
+        .arg("--in-file")
+        .arg(&api_dir)
+        .args(["--parse-type", "Tree", "--create-package"])
+        .args(["--package-name", "vppgen", "--package-path"])
+        .arg(&package_dir)
+        .arg("--vppapi-opts")
+        .arg(format!(r#"{{ path="{}/{{crate}}" }}"#, workspace.display()))
+        .status()
+        .unwrap();
+    assert!(status.success(), "api-gen failed");
+
+    /* build it on its own, with the versions of the workspace */
+    let package = package_dir.join("vppgen");
+    let mut cargo_toml = fs::read_to_string(package.join("Cargo.toml")).unwrap();
+    cargo_toml.push_str("\n[workspace]\n");
+    fs::write(package.join("Cargo.toml"), cargo_toml).unwrap();
+    fs::copy(workspace.join("Cargo.lock"), package.join("Cargo.lock")).unwrap();
+
+    let status = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .current_dir(&package)
+        .env("CARGO_TARGET_DIR", work.join("target"))
+        .args([
+            "test",
+            "--test",
+            "conformance_test",
+            "--test",
+            "ip_types_test",
+        ])
+        .status()
+        .unwrap();
+    assert!(
+        status.success(),
+        "the tests of the generated package failed"
+    );
+}
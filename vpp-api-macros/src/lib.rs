@@ -177,6 +177,346 @@ pub fn derive_unionident(input: proc_macro::TokenStream) -> proc_macro::TokenStr
                 #name(out)
             }
             #(#helperfunctions)*
+            /// The union holding the member, as it is on the wire, padded with zeroes
+            pub fn from_member<T: serde::Serialize>(member: &T) -> std::result::Result<#name, String> {
+                let bytes = vpp_api_encoding::wire::to_bytes(member).map_err(|e| e.to_string())?;
+                if bytes.len() > #maxsize_literal {
+                    return Err(format!("{} bytes do not fit in {}", bytes.len(), stringify!(#name)));
+                }
+                let mut out = #name::new();
+                out.0 .0[..bytes.len()].copy_from_slice(&bytes);
+                Ok(out)
+            }
+            /// The member which the union holds, decoded from its first bytes
+            pub fn to_member<T: serde::de::DeserializeOwned>(&self) -> std::result::Result<T, String> {
+                vpp_api_encoding::wire::from_bytes_prefix(&self.0 .0[..])
+                    .map(|(member, _)| member)
+                    .map_err(|e| e.to_string())
+            }
+        }
+    };
+    expanded.into()
+}
+
+/*
+ * Serialize/Deserialize for the structs where a variable size array has
+ * its element count in another field, marked with #[vpp_count(count_field)]
+ * on the array. The count is taken from the array length when encoding
+ * (a count set by hand must agree with it), and it bounds the array when
+ * decoding, so the fields after the array decode fine.
+ */
+#[proc_macro_derive(VppSerde, attributes(vpp_count))]
+pub fn derive_vpp_serde(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    use quote::format_ident;
+    use syn::ext::IdentExt;
+
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = input.ident;
+    let name_str = name.to_string();
+    let fields = if let syn::Data::Struct(syn::DataStruct {
+        fields: syn::Fields::Named(syn::FieldsNamed { ref named, .. }),
+        ..
+    }) = input.data
+    {
+        named
+    } else {
+        panic!("VppSerde needs a struct with named fields");
+    };
+
+    /* (count field, array field) */
+    let mut counts: Vec<(syn::Ident, syn::Ident)> = vec![];
+    for f in fields.iter() {
+        for attr in f.attrs.iter().filter(|a| a.path.is_ident("vpp_count")) {
+            let count: syn::Ident = attr
+                .parse_args()
+                .expect("expected #[vpp_count(count_field)]");
+            counts.push((count, f.ident.clone().unwrap()));
+        }
+    }
+    let idents: Vec<syn::Ident> = fields.iter().map(|f| f.ident.clone().unwrap()).collect();
+    for (count, array) in &counts {
+        let count_pos = idents.iter().position(|i| i == count);
+        let array_pos = idents.iter().position(|i| i == array).unwrap();
+        match count_pos {
+            Some(pos) if pos < array_pos => {}
+            Some(_) => panic!("count field {} must come before {}", count, array),
+            None => panic!("no count field {} for {}", count, array),
+        }
+    }
+    let count_of = |ident: &syn::Ident| {
+        counts
+            .iter()
+            .find(|(_, a)| a == ident)
+            .map(|(c, _)| format_ident!("__field_{}", c.unraw()))
+    };
+    let array_of = |ident: &syn::Ident| counts.iter().find(|(c, _)| c == ident).map(|(_, a)| a);
+
+    let field_names: Vec<String> = idents.iter().map(|i| i.unraw().to_string()).collect();
+    let vars: Vec<syn::Ident> = idents
+        .iter()
+        .map(|i| format_ident!("__field_{}", i.unraw()))
+        .collect();
+    let nfields = fields.len();
+
+    let ser_fields = fields.iter().zip(field_names.iter()).map(|(f, fname)| {
+        let ident = f.ident.as_ref().unwrap();
+        let ty = &f.ty;
+        match array_of(ident) {
+            Some(array) => quote! {
+                let count: #ty = std::convert::TryFrom::try_from(self.#array.0.len()).map_err(|_| {
+                    S::Error::custom(format!("{} elements do not fit in {}", self.#array.0.len(), #fname))
+                })?;
+                if self.#ident != <#ty as Default>::default() && self.#ident != count {
+                    return Err(S::Error::custom(format!(
+                        "{} is {} but {} has {} elements",
+                        #fname, self.#ident, stringify!(#array), self.#array.0.len()
+                    )));
+                }
+                st.serialize_field(#fname, &count)?;
+            },
+            None => quote! {
+                st.serialize_field(#fname, &self.#ident)?;
+            },
+        }
+    });
+
+    let seq_fields = fields.iter().enumerate().map(|(i, f)| {
+        let ident = f.ident.as_ref().unwrap();
+        let var = &vars[i];
+        let ty = &f.ty;
+        match count_of(ident) {
+            Some(count_var) => quote! {
+                let count: usize = std::convert::TryInto::try_into(#count_var)
+                    .map_err(serde::de::Error::custom)?;
+                let #var: #ty = seq
+                    .next_element_seed(vpp_api_encoding::typ::VariableSizeArraySeed::new(count))?
+                    .ok_or_else(|| serde::de::Error::invalid_length(#i, &self))?;
+            },
+            None => quote! {
+                let #var: #ty = seq
+                    .next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(#i, &self))?;
+            },
+        }
+    });
+
+    let map_checks = fields.iter().enumerate().filter_map(|(i, f)| {
+        let ident = f.ident.as_ref().unwrap();
+        let var = &vars[i];
+        let fname = &field_names[i];
+        count_of(ident).map(|count_var| {
+            quote! {
+                if std::convert::TryInto::<usize>::try_into(#count_var).ok() != Some(#var.0.len()) {
+                    return Err(serde::de::Error::custom(format!(
+                        "{} has {} elements, not {}", #fname, #var.0.len(), #count_var
+                    )));
+                }
+            }
+        })
+    });
+    let map_types = fields.iter().map(|f| &f.ty);
+
+    let expanded = quote! {
+        impl serde::Serialize for #name {
+            fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                use serde::ser::{Error, SerializeStruct};
+                let mut st = serializer.serialize_struct(#name_str, #nfields)?;
+                #(#ser_fields)*
+                st.end()
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for #name {
+            fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                struct VppSerdeVisitor;
+                impl<'de> serde::de::Visitor<'de> for VppSerdeVisitor {
+                    type Value = #name;
+                    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        formatter.write_str(concat!("struct ", #name_str))
+                    }
+
+                    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<#name, A::Error>
+                    where
+                        A: serde::de::SeqAccess<'de>,
+                    {
+                        #(#seq_fields)*
+                        Ok(#name { #(#idents: #vars),* })
+                    }
+
+                    fn visit_map<A>(self, mut map: A) -> std::result::Result<#name, A::Error>
+                    where
+                        A: serde::de::MapAccess<'de>,
+                    {
+                        #(let mut #vars: Option<#map_types> = None;)*
+                        while let Some(key) = map.next_key::<String>()? {
+                            match key.as_str() {
+                                #(#field_names => #vars = Some(map.next_value()?),)*
+                                _ => {
+                                    map.next_value::<serde::de::IgnoredAny>()?;
+                                }
+                            }
+                        }
+                        #(let #vars = #vars.ok_or_else(|| serde::de::Error::missing_field(#field_names))?;)*
+                        #(#map_checks)*
+                        Ok(#name { #(#idents: #vars),* })
+                    }
+                }
+
+                const FIELDS: &[&str] = &[#(#field_names),*];
+                deserializer.deserialize_struct(#name_str, FIELDS, VppSerdeVisitor)
+            }
+        }
+    };
+    expanded.into()
+}
+
+/*
+ * Serialize/Deserialize for the API enums: the #[repr] integer for the
+ * wire and the other binary formats, the variant name for the human
+ * readable ones (JSON, YAML), which also take the integer when decoding.
+ *
+ * A variant with one field, like Unknown(u32), holds the values which are
+ * none of the others, so that a value added by a newer VPP decodes and is
+ * encoded back as it was. #[vpp_strict] makes those values an error instead.
+ */
+#[proc_macro_derive(VppEnum, attributes(vpp_strict))]
+pub fn derive_vpp_enum(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = input.ident;
+    let name_str = name.to_string();
+    let repr: syn::Ident = input
+        .attrs
+        .iter()
+        .find(|a| a.path.is_ident("repr"))
+        .map(|a| a.parse_args().expect("expected #[repr(uN)]"))
+        .unwrap_or_else(|| syn::Ident::new("u32", name.span()));
+    let strict = input.attrs.iter().any(|a| a.path.is_ident("vpp_strict"));
+    let data = match input.data {
+        syn::Data::Enum(de) => de,
+        _ => panic!("VppEnum needs an enum"),
+    };
+
+    let mut variants: Vec<syn::Ident> = vec![];
+    /* the discriminants, counting on from the last explicit one like rustc does */
+    let mut values: Vec<proc_macro2::TokenStream> = vec![];
+    let mut unknown: Option<syn::Ident> = None;
+    for v in data.variants.iter() {
+        match v.fields {
+            syn::Fields::Unit => {
+                let value = match (&v.discriminant, values.last()) {
+                    (Some((_, expr)), _) => quote! { (#expr) as #repr },
+                    (None, Some(prev)) => quote! { (#prev) + 1 },
+                    (None, None) => quote! { 0 as #repr },
+                };
+                variants.push(v.ident.clone());
+                values.push(value);
+            }
+            syn::Fields::Unnamed(ref f) if f.unnamed.len() == 1 && unknown.is_none() => {
+                unknown = Some(v.ident.clone());
+            }
+            _ => panic!("VppEnum needs unit variants, and at most one for the unknown values"),
+        }
+    }
+    let variant_names: Vec<String> = variants.iter().map(|v| v.to_string()).collect();
+
+    let (unknown_value, unknown_name, unknown_from) = match &unknown {
+        Some(u) => (
+            quote! { #name::#u(raw) => *raw, },
+            quote! { #name::#u(raw) => return serde::Serialize::serialize(raw, serializer), },
+            if strict {
+                quote! {}
+            } else {
+                quote! {
+                    if let Ok(raw) = std::convert::TryFrom::try_from(value) {
+                        return Ok(#name::#u(raw));
+                    }
+                }
+            },
+        ),
+        None => (quote! {}, quote! {}, quote! {}),
+    };
+
+    let expanded = quote! {
+        impl serde::Serialize for #name {
+            fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                if serializer.is_human_readable() {
+                    let name = match self {
+                        #(#name::#variants => #variant_names,)*
+                        #unknown_name
+                    };
+                    serializer.serialize_str(name)
+                } else {
+                    let value: #repr = match self {
+                        #(#name::#variants => #values,)*
+                        #unknown_value
+                    };
+                    serde::Serialize::serialize(&value, serializer)
+                }
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for #name {
+            fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                struct VppEnumVisitor;
+                impl VppEnumVisitor {
+                    fn from_value<E: serde::de::Error>(value: u64) -> std::result::Result<#name, E> {
+                        #(if value == (#values) as u64 {
+                            return Ok(#name::#variants);
+                        })*
+                        #unknown_from
+                        Err(E::custom(format!("invalid value {} for {}", value, #name_str)))
+                    }
+                }
+                impl<'de> serde::de::Visitor<'de> for VppEnumVisitor {
+                    type Value = #name;
+                    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        formatter.write_str(concat!("a variant of ", #name_str))
+                    }
+
+                    fn visit_str<E>(self, v: &str) -> std::result::Result<#name, E>
+                    where
+                        E: serde::de::Error,
+                    {
+                        match v {
+                            #(#variant_names => Ok(#name::#variants),)*
+                            _ => Err(E::unknown_variant(v, &[#(#variant_names),*])),
+                        }
+                    }
+
+                    fn visit_u64<E>(self, v: u64) -> std::result::Result<#name, E>
+                    where
+                        E: serde::de::Error,
+                    {
+                        Self::from_value(v)
+                    }
+
+                    fn visit_i64<E>(self, v: i64) -> std::result::Result<#name, E>
+                    where
+                        E: serde::de::Error,
+                    {
+                        Self::from_value(v as u64)
+                    }
+                }
+
+                if deserializer.is_human_readable() {
+                    deserializer.deserialize_any(VppEnumVisitor)
+                } else {
+                    let value: #repr = serde::Deserialize::deserialize(deserializer)?;
+                    VppEnumVisitor::from_value(value as u64)
+                }
+            }
         }
     };
     expanded.into()
@@ -0,0 +1,63 @@
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+use vpp_api_encoding::typ::*;
+use vpp_api_macros::VppSerde;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Path {
+    sw_if_index: u32,
+    weight: u8,
+}
+
+#[derive(Debug, Clone, Default, VppSerde)]
+pub struct Route {
+    table_id: u32,
+    n_paths: u8,
+    is_multipath: bool,
+    #[vpp_count(n_paths)]
+    paths: VariableSizeArray<Path>,
+    r#type: u16,
+}
+
+fn main() {
+    let enc = bincode::DefaultOptions::new()
+        .with_big_endian()
+        .with_fixint_encoding();
+    let route = Route {
+        table_id: 7,
+        n_paths: 0,
+        is_multipath: true,
+        paths: VariableSizeArray(vec![
+            Path {
+                sw_if_index: 1,
+                weight: 1,
+            },
+            Path {
+                sw_if_index: 2,
+                weight: 3,
+            },
+        ]),
+        r#type: 0x102,
+    };
+
+    /* the count is filled in from the array */
+    let data = enc.serialize(&route).unwrap();
+    assert_eq!(
+        data,
+        vec![0, 0, 0, 7, 2, 1, 0, 0, 0, 1, 1, 0, 0, 0, 2, 3, 1, 2]
+    );
+    let decoded: Route = enc.deserialize(&data).unwrap();
+    assert_eq!(decoded.n_paths, 2);
+    assert_eq!(decoded.paths.0, route.paths.0);
+    assert_eq!(decoded.r#type, 0x102);
+
+    /* a count set by hand has to agree */
+    let wrong = Route {
+        n_paths: 3,
+        ..route.clone()
+    };
+    assert!(enc.serialize(&wrong).is_err());
+    let mut short = data.clone();
+    short[4] = 3;
+    assert!(enc.deserialize::<Route>(&short).is_err());
+}
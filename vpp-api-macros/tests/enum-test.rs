@@ -0,0 +1,107 @@
+#![allow(non_camel_case_types)]
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+use vpp_api_encoding::typ::*;
+use vpp_api_macros::VppEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, VppEnum)]
+#[repr(u8)]
+pub enum AddressFamily {
+    ADDRESS_IP4 = 0,
+    ADDRESS_IP6 = 1,
+}
+
+impl AsU32 for AddressFamily {
+    fn as_u32(data: Self) -> u32 {
+        data as u32
+    }
+    fn from_u32(data: u32) -> Option<Self> {
+        match data {
+            0 => Some(AddressFamily::ADDRESS_IP4),
+            1 => Some(AddressFamily::ADDRESS_IP6),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, VppEnum)]
+pub enum IfType {
+    IF_API_TYPE_HARDWARE = 0,
+    IF_API_TYPE_SUB = 1,
+    IF_API_TYPE_P2P = 2,
+}
+
+/* the values a newer VPP may add */
+#[derive(Debug, Clone, Copy, PartialEq, VppEnum)]
+#[repr(u32)]
+pub enum LinkDuplex {
+    LINK_DUPLEX_API_UNKNOWN = 0,
+    LINK_DUPLEX_API_HALF = 1,
+    LINK_DUPLEX_API_FULL = 2,
+    Unknown(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, VppEnum)]
+#[repr(u32)]
+#[vpp_strict]
+pub enum StrictDuplex {
+    LINK_DUPLEX_API_UNKNOWN = 0,
+    LINK_DUPLEX_API_HALF = 1,
+    LINK_DUPLEX_API_FULL = 2,
+    Unknown(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Details {
+    af: AddressFamily,
+    typ: IfType,
+}
+
+fn main() {
+    let enc = bincode::DefaultOptions::new()
+        .with_big_endian()
+        .with_fixint_encoding();
+    let d = Details {
+        af: AddressFamily::ADDRESS_IP6,
+        typ: IfType::IF_API_TYPE_P2P,
+    };
+
+    /* the repr integer in the binary formats */
+    let data = enc.serialize(&d).unwrap();
+    assert_eq!(data, vec![1, 0, 0, 0, 2]);
+    assert_eq!(enc.deserialize::<Details>(&data).unwrap(), d);
+    assert!(enc.deserialize::<Details>(&[2, 0, 0, 0, 2]).is_err());
+    assert_eq!(
+        vpp_api_encoding::wire::from_bytes::<Details>(&data).unwrap(),
+        d
+    );
+
+    /* the name in the human readable ones, which also take the number */
+    let json = serde_json::to_string(&d).unwrap();
+    assert_eq!(json, r#"{"af":"ADDRESS_IP6","typ":"IF_API_TYPE_P2P"}"#);
+    assert_eq!(serde_json::from_str::<Details>(&json).unwrap(), d);
+    assert_eq!(
+        serde_json::from_str::<Details>(r#"{"af":1,"typ":"IF_API_TYPE_P2P"}"#).unwrap(),
+        d
+    );
+    assert!(serde_json::from_str::<Details>(r#"{"af":"ADDRESS_IP5","typ":2}"#).is_err());
+
+    /* an unknown value comes back as it was */
+    let data = enc.serialize(&7u32).unwrap();
+    let duplex: LinkDuplex = enc.deserialize(&data).unwrap();
+    assert_eq!(duplex, LinkDuplex::Unknown(7));
+    assert_eq!(enc.serialize(&duplex).unwrap(), data);
+    assert_eq!(
+        enc.deserialize::<LinkDuplex>(&enc.serialize(&2u32).unwrap())
+            .unwrap(),
+        LinkDuplex::LINK_DUPLEX_API_FULL
+    );
+    assert_eq!(serde_json::to_string(&duplex).unwrap(), "7");
+    assert_eq!(serde_json::from_str::<LinkDuplex>("7").unwrap(), duplex);
+    assert!(enc.deserialize::<StrictDuplex>(&data).is_err());
+    assert!(serde_json::from_str::<StrictDuplex>("7").is_err());
+
+    /* SizedEnum leaves the name to the enum */
+    let af: SizedEnum<AddressFamily, u8> = AddressFamily::ADDRESS_IP4.into();
+    assert_eq!(serde_json::to_string(&af).unwrap(), r#""ADDRESS_IP4""#);
+}
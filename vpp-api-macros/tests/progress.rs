@@ -3,4 +3,6 @@ fn tests() {
     let t = trybuild::TestCases::new();
     t.pass("tests/message-test.rs");
     t.pass("tests/unit-test.rs");
+    t.pass("tests/counted-test.rs");
+    t.pass("tests/enum-test.rs");
 }
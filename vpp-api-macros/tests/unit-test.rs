@@ -16,6 +16,13 @@ fn main() {
     let mut felix = AddressUnion::new_IP4Address([10, 10, 1, 2]);
     println!("{:#?}", felix.get_IP4Address());
 
+    /* the members as they are on the wire, in network byte order */
+    let un = AddressUnion::from_member(&0x0a0bu16).unwrap();
+    assert_eq!(un.0 .0[..4], [0x0a, 0x0b, 0, 0]);
+    assert_eq!(un.to_member::<u16>(), Ok(0x0a0b));
+    assert_eq!(un.to_member::<IP4Address>(), Ok([0x0a, 0x0b, 0, 0]));
+    assert!(AddressUnion::from_member(&[0u8; 17]).is_err());
+
     assert_eq!(32, 32);
     // assert_eq!("Idiot", MyStruct::get_message_name_and_crc());
 }
@@ -1,3 +1,30 @@
 pub trait VppApiMessage {
     fn get_message_name_and_crc() -> String;
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CounterSeverity {
+    Info,
+    Warn,
+    Error,
+}
+
+/// A set of counters from the "counters" section of an API file, e.g. a node's error counters
+pub trait VppApiCounter: Sized + Copy + 'static {
+    /// The name of the counter set, which the "paths" section refers to
+    fn counter_set_name() -> &'static str;
+    fn all() -> &'static [Self];
+    /// The stats segment paths the counters live under, e.g. "/err/ip4-map"
+    fn paths() -> &'static [&'static str];
+
+    fn name(&self) -> &'static str;
+    fn severity(&self) -> CounterSeverity;
+    fn counter_type(&self) -> &'static str;
+    fn units(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+
+    /// The full stats segment name of this counter under one of the paths
+    fn stats_name(&self, path: &str) -> String {
+        format!("{}/{}", path, self.name())
+    }
+}
@@ -0,0 +1,38 @@
+use std::error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    Error(String),
+    /// A read past the end of the segment, most likely a stale pointer
+    OutOfBounds(u64),
+    UnsupportedVersion(u64),
+    /// The writer kept the segment locked for longer than the timeout
+    Busy,
+    IoError(std::io::Error),
+}
+
+impl error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error: {:?}", self)
+    }
+}
+impl From<&str> for Error {
+    fn from(s: &str) -> Self {
+        Self::Error(s.to_string())
+    }
+}
+impl From<String> for Error {
+    fn from(s: String) -> Self {
+        Self::Error(s)
+    }
+}
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::IoError(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,514 @@
+/*
+ * A client for the VPP stats segment.
+ *
+ * VPP publishes the interface, node and error counters in a shared
+ * memory segment, the memfd of which is handed out over stats.sock.
+ * The segment starts with a header:
+ *
+ *   version: u64, base: u64, epoch: u64, in_progress: u64,
+ *   directory_vector: u64, error_vector: u64 (version 1 only)
+ *
+ * All the pointers in the segment are VPP's own addresses, so they
+ * are turned into offsets by subtracting the base. The vectors are
+ * VPP vectors: the u32 length sits 8 bytes before the data.
+ *
+ * The writer bumps the epoch and sets in_progress while it changes
+ * the directory, so a read is only good if in_progress was clear and
+ * the epoch stayed the same throughout - else it is retried.
+ */
+#[macro_use]
+extern crate log;
+
+pub mod error;
+mod segment;
+mod socket;
+
+#[cfg(test)]
+mod testseg;
+
+use crate::error::{Error, Result};
+use segment::Segment;
+use std::sync::atomic::{fence, Ordering};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_STATS_SOCKET: &str = "/run/vpp/stats.sock";
+
+const HDR_VERSION: u64 = 0;
+const HDR_BASE: u64 = 8;
+const HDR_EPOCH: u64 = 16;
+const HDR_IN_PROGRESS: u64 = 24;
+const HDR_DIRECTORY_VECTOR: u64 = 32;
+const HDR_ERROR_VECTOR: u64 = 40;
+
+/* type: u32, padding, union of index/value/pointer: u64, name[128] */
+const ENTRY_SIZE: u64 = 144;
+const ENTRY_VALUE: u64 = 8;
+const ENTRY_NAME: u64 = 16;
+const ENTRY_NAME_LEN: usize = 128;
+
+const VEC_HEADER_SIZE: u64 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryType {
+    Illegal,
+    Scalar,
+    SimpleCounter,
+    CombinedCounter,
+    ErrorIndex,
+    NameVector,
+    Empty,
+    Symlink,
+}
+
+impl EntryType {
+    /* version 2 has dropped the error index, the errors are simple counters there */
+    fn from_raw(version: u64, raw: u32) -> Self {
+        match (version, raw) {
+            (_, 1) => EntryType::Scalar,
+            (_, 2) => EntryType::SimpleCounter,
+            (_, 3) => EntryType::CombinedCounter,
+            (1, 4) => EntryType::ErrorIndex,
+            (1, 5) => EntryType::NameVector,
+            (1, 6) => EntryType::Empty,
+            (2, 4) => EntryType::NameVector,
+            (2, 5) => EntryType::Empty,
+            (2, 6) => EntryType::Symlink,
+            _ => EntryType::Illegal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CombinedCounter {
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatValue {
+    Scalar(f64),
+    /// Indexed by thread, then by the counter index (e.g. sw_if_index)
+    SimpleCounter(Vec<Vec<u64>>),
+    CombinedCounter(Vec<Vec<CombinedCounter>>),
+    /// One value per thread
+    Error(Vec<u64>),
+    NameVector(Vec<Option<String>>),
+    /// Points to the column `index` of the counter in the directory entry `entry`
+    Symlink {
+        entry: u32,
+        index: u32,
+    },
+    Empty,
+}
+
+impl StatValue {
+    /// The sum of the counter at this index over all the threads
+    pub fn simple_total(&self, index: usize) -> Option<u64> {
+        match self {
+            StatValue::SimpleCounter(threads) => Some(
+                threads
+                    .iter()
+                    .filter_map(|t| t.get(index))
+                    .fold(0u64, |acc, v| acc.wrapping_add(*v)),
+            ),
+            _ => None,
+        }
+    }
+
+    pub fn combined_total(&self, index: usize) -> Option<CombinedCounter> {
+        match self {
+            StatValue::CombinedCounter(threads) => {
+                Some(threads.iter().filter_map(|t| t.get(index)).fold(
+                    CombinedCounter::default(),
+                    |acc, v| CombinedCounter {
+                        packets: acc.packets.wrapping_add(v.packets),
+                        bytes: acc.bytes.wrapping_add(v.bytes),
+                    },
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn error_total(&self) -> Option<u64> {
+        match self {
+            StatValue::Error(threads) => {
+                Some(threads.iter().fold(0u64, |acc, v| acc.wrapping_add(*v)))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatEntry {
+    pub name: String,
+    pub value: StatValue,
+}
+
+pub struct StatClient {
+    segment: Segment,
+    timeout: Duration,
+}
+
+impl StatClient {
+    /// Connect to stats.sock (usually DEFAULT_STATS_SOCKET) and map the segment it hands out
+    pub fn connect(sock_path: &str) -> Result<Self> {
+        let file = socket::recv_segment_fd(sock_path)?;
+        Self::from_file(&file)
+    }
+
+    /// Map a stats segment from a file, e.g. a copy of one taken earlier
+    pub fn open(path: &str) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        Self::from_file(&file)
+    }
+
+    fn from_file(file: &std::fs::File) -> Result<Self> {
+        let client = StatClient {
+            segment: Segment::map(file)?,
+            timeout: Duration::from_secs(1),
+        };
+        match client.version()? {
+            1 | 2 => Ok(client),
+            v => Err(Error::UnsupportedVersion(v)),
+        }
+    }
+
+    /// How long to wait for the writer to finish before giving up with Error::Busy
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn version(&self) -> Result<u64> {
+        self.segment.read_u64(HDR_VERSION)
+    }
+
+    pub fn epoch(&self) -> Result<u64> {
+        self.segment.read_volatile_u64(HDR_EPOCH)
+    }
+
+    /// The names of all the entries in the directory
+    pub fn ls(&self) -> Result<Vec<String>> {
+        self.consistent(|| {
+            let dir = self.directory()?;
+            let mut out = vec![];
+            for i in 0..self.vec_len(dir)? as u64 {
+                let ofs = dir + i * ENTRY_SIZE;
+                if self.entry_type(ofs)? != EntryType::Illegal {
+                    out.push(self.entry_name(ofs)?);
+                }
+            }
+            Ok(out)
+        })
+    }
+
+    pub fn dump(&self) -> Result<Vec<StatEntry>> {
+        self.dump_filtered(|_| true)
+    }
+
+    /// Read the entries whose names pass the filter, in one consistent snapshot
+    pub fn dump_filtered<F: Fn(&str) -> bool>(&self, filter: F) -> Result<Vec<StatEntry>> {
+        self.consistent(|| {
+            let dir = self.directory()?;
+            let mut out = vec![];
+            for i in 0..self.vec_len(dir)? as u64 {
+                let ofs = dir + i * ENTRY_SIZE;
+                let typ = self.entry_type(ofs)?;
+                if typ == EntryType::Illegal {
+                    continue;
+                }
+                let name = self.entry_name(ofs)?;
+                if !filter(&name) {
+                    continue;
+                }
+                let value = self.entry_value(ofs, typ)?;
+                out.push(StatEntry { name, value });
+            }
+            Ok(out)
+        })
+    }
+
+    /// Read one entry by its exact name, e.g. "/if/rx"
+    pub fn get(&self, name: &str) -> Result<Option<StatValue>> {
+        Ok(self
+            .dump_filtered(|n| n == name)?
+            .into_iter()
+            .next()
+            .map(|e| e.value))
+    }
+
+    fn consistent<T, F: Fn() -> Result<T>>(&self, f: F) -> Result<T> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let epoch = self.epoch()?;
+            while self.segment.read_volatile_u64(HDR_IN_PROGRESS)? != 0 {
+                if Instant::now() > deadline {
+                    return Err(Error::Busy);
+                }
+                std::thread::yield_now();
+            }
+            fence(Ordering::Acquire);
+            let res = f();
+            fence(Ordering::Acquire);
+            if self.epoch()? == epoch && self.segment.read_volatile_u64(HDR_IN_PROGRESS)? == 0 {
+                return res;
+            }
+            if Instant::now() > deadline {
+                return Err(Error::Busy);
+            }
+            debug!("stats segment changed while reading, retrying");
+        }
+    }
+
+    fn offset(&self, ptr: u64) -> Result<u64> {
+        let base = self.segment.read_u64(HDR_BASE)?;
+        match ptr.checked_sub(base) {
+            Some(ofs) if (ofs as usize) < self.segment.len() => Ok(ofs),
+            _ => Err(Error::OutOfBounds(ptr)),
+        }
+    }
+
+    fn vec_len(&self, ofs: u64) -> Result<u32> {
+        match ofs.checked_sub(VEC_HEADER_SIZE) {
+            Some(hdr) => self.segment.read_u32(hdr),
+            None => Err(Error::OutOfBounds(ofs)),
+        }
+    }
+
+    fn directory(&self) -> Result<u64> {
+        self.offset(self.segment.read_u64(HDR_DIRECTORY_VECTOR)?)
+    }
+
+    fn entry_type(&self, ofs: u64) -> Result<EntryType> {
+        Ok(EntryType::from_raw(
+            self.version()?,
+            self.segment.read_u32(ofs)?,
+        ))
+    }
+
+    fn entry_name(&self, ofs: u64) -> Result<String> {
+        let name = self.segment.bytes(ofs + ENTRY_NAME, ENTRY_NAME_LEN)?;
+        let end = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+        Ok(String::from_utf8_lossy(&name[..end]).to_string())
+    }
+
+    /// The per-thread vector of pointers to the per-thread vectors
+    fn thread_vectors(&self, ptr: u64) -> Result<Vec<u64>> {
+        let ofs = self.offset(ptr)?;
+        let mut out = vec![];
+        for i in 0..self.vec_len(ofs)? as u64 {
+            out.push(self.offset(self.segment.read_u64(ofs + i * 8)?)?);
+        }
+        Ok(out)
+    }
+
+    fn entry_value(&self, ofs: u64, typ: EntryType) -> Result<StatValue> {
+        let value = self.segment.read_u64(ofs + ENTRY_VALUE)?;
+        let v = match typ {
+            EntryType::Scalar if self.version()? == 1 => StatValue::Scalar(f64::from_bits(value)),
+            EntryType::Scalar => StatValue::Scalar(value as f64),
+            EntryType::SimpleCounter => {
+                let mut threads = vec![];
+                for t in self.thread_vectors(value)? {
+                    let mut counters = vec![];
+                    for i in 0..self.vec_len(t)? as u64 {
+                        counters.push(self.segment.read_u64(t + i * 8)?);
+                    }
+                    threads.push(counters);
+                }
+                StatValue::SimpleCounter(threads)
+            }
+            EntryType::CombinedCounter => {
+                let mut threads = vec![];
+                for t in self.thread_vectors(value)? {
+                    let mut counters = vec![];
+                    for i in 0..self.vec_len(t)? as u64 {
+                        counters.push(CombinedCounter {
+                            packets: self.segment.read_u64(t + i * 16)?,
+                            bytes: self.segment.read_u64(t + i * 16 + 8)?,
+                        });
+                    }
+                    threads.push(counters);
+                }
+                StatValue::CombinedCounter(threads)
+            }
+            EntryType::ErrorIndex => {
+                let error_vector = self.segment.read_u64(HDR_ERROR_VECTOR)?;
+                let mut threads = vec![];
+                for t in self.thread_vectors(error_vector)? {
+                    threads.push(self.segment.read_u64(t + value * 8)?);
+                }
+                StatValue::Error(threads)
+            }
+            EntryType::NameVector => {
+                let ofs = self.offset(value)?;
+                let mut names = vec![];
+                for i in 0..self.vec_len(ofs)? as u64 {
+                    let ptr = self.segment.read_u64(ofs + i * 8)?;
+                    if ptr == 0 {
+                        names.push(None);
+                        continue;
+                    }
+                    let s = self.offset(ptr)?;
+                    let bytes = self.segment.bytes(s, self.vec_len(s)? as usize)?;
+                    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+                    names.push(Some(String::from_utf8_lossy(&bytes[..end]).to_string()));
+                }
+                StatValue::NameVector(names)
+            }
+            EntryType::Symlink => StatValue::Symlink {
+                entry: value as u32,
+                index: (value >> 32) as u32,
+            },
+            EntryType::Empty | EntryType::Illegal => StatValue::Empty,
+        };
+        Ok(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testseg::{serve_segment, temp_path, SegmentBuilder};
+
+    #[test]
+    fn test_v2_dump() {
+        let path = SegmentBuilder::new(2)
+            .scalar("/sys/vector_rate", 42.0)
+            .simple("/if/drops", vec![vec![1, 2, 3], vec![10, 20, 30]])
+            .combined(
+                "/if/rx",
+                vec![vec![(5, 500), (6, 600)], vec![(1, 100), (0, 0)]],
+            )
+            .names(
+                "/if/names",
+                vec![Some("local0"), None, Some("GigabitEthernet0/8/0")],
+            )
+            .empty("/sys/nothing")
+            .symlink("/interfaces/local0/rx", 2, 0)
+            .write_temp();
+
+        let c = StatClient::open(&path).unwrap();
+        assert_eq!(c.version().unwrap(), 2);
+        assert_eq!(
+            c.ls().unwrap(),
+            vec![
+                "/sys/vector_rate",
+                "/if/drops",
+                "/if/rx",
+                "/if/names",
+                "/sys/nothing",
+                "/interfaces/local0/rx"
+            ]
+        );
+        assert_eq!(
+            c.get("/sys/vector_rate").unwrap(),
+            Some(StatValue::Scalar(42.0))
+        );
+        let drops = c.get("/if/drops").unwrap().unwrap();
+        assert_eq!(drops.simple_total(2), Some(33));
+        let rx = c.get("/if/rx").unwrap().unwrap();
+        assert_eq!(
+            rx.combined_total(0),
+            Some(CombinedCounter {
+                packets: 6,
+                bytes: 600
+            })
+        );
+        assert_eq!(
+            c.get("/if/names").unwrap(),
+            Some(StatValue::NameVector(vec![
+                Some("local0".to_string()),
+                None,
+                Some("GigabitEthernet0/8/0".to_string())
+            ]))
+        );
+        assert_eq!(c.get("/sys/nothing").unwrap(), Some(StatValue::Empty));
+        assert_eq!(
+            c.get("/interfaces/local0/rx").unwrap(),
+            Some(StatValue::Symlink { entry: 2, index: 0 })
+        );
+        assert_eq!(c.get("/no/such/thing").unwrap(), None);
+        assert_eq!(c.dump_filtered(|n| n.starts_with("/if/")).unwrap().len(), 3);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_v1_errors_and_scalars() {
+        let path = SegmentBuilder::new(1)
+            .errors(vec![vec![0, 7, 0], vec![0, 3, 1]])
+            .scalar("/sys/last_update", 1.5)
+            .error("/err/ip4-input/ip4 ttl <= 1", 1)
+            .error("/err/ip4-input/bad checksum", 2)
+            .write_temp();
+
+        let c = StatClient::open(&path).unwrap();
+        assert_eq!(
+            c.get("/sys/last_update").unwrap(),
+            Some(StatValue::Scalar(1.5))
+        );
+        let ttl = c.get("/err/ip4-input/ip4 ttl <= 1").unwrap().unwrap();
+        assert_eq!(ttl, StatValue::Error(vec![7, 3]));
+        assert_eq!(ttl.error_total(), Some(10));
+        assert_eq!(
+            c.get("/err/ip4-input/bad checksum")
+                .unwrap()
+                .unwrap()
+                .error_total(),
+            Some(1)
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_in_progress_is_busy() {
+        let path = SegmentBuilder::new(2)
+            .scalar("/sys/vector_rate", 1.0)
+            .in_progress()
+            .write_temp();
+        let c = StatClient::open(&path)
+            .unwrap()
+            .with_timeout(Duration::from_millis(10));
+        assert!(matches!(c.dump(), Err(Error::Busy)));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_bad_version_and_pointers() {
+        let path = SegmentBuilder::new(3).write_temp();
+        assert!(matches!(
+            StatClient::open(&path),
+            Err(Error::UnsupportedVersion(3))
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let path = SegmentBuilder::new(2)
+            .simple("/if/drops", vec![vec![1]])
+            .corrupt_directory()
+            .write_temp();
+        let c = StatClient::open(&path)
+            .unwrap()
+            .with_timeout(Duration::from_millis(10));
+        assert!(matches!(c.dump(), Err(Error::OutOfBounds(_))));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_connect() {
+        let seg_path = SegmentBuilder::new(2)
+            .simple("/if/drops", vec![vec![4, 5]])
+            .write_temp();
+        let sock_path = temp_path("sock");
+        serve_segment(&sock_path, &seg_path);
+
+        let c = StatClient::connect(&sock_path).unwrap();
+        assert_eq!(
+            c.get("/if/drops").unwrap(),
+            Some(StatValue::SimpleCounter(vec![vec![4, 5]]))
+        );
+        let _ = std::fs::remove_file(&seg_path);
+        let _ = std::fs::remove_file(&sock_path);
+    }
+}
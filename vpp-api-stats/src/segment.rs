@@ -0,0 +1,82 @@
+/*
+ * The read-only mapping of the stats segment and the raw accessors.
+ *
+ * VPP keeps updating the segment while we read it, so every read is
+ * bounds-checked: a stale pointer ends up as an OutOfBounds error,
+ * and the epoch check in the caller makes it retry.
+ */
+use crate::error::{Error, Result};
+use std::convert::TryInto;
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+
+pub(crate) struct Segment {
+    ptr: *const u8,
+    len: usize,
+}
+
+/* The mapping is read-only and never moves */
+unsafe impl Send for Segment {}
+unsafe impl Sync for Segment {}
+
+impl Segment {
+    pub fn map(file: &File) -> Result<Self> {
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            return Err("stats segment is empty".into());
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(Segment {
+            ptr: ptr as *const u8,
+            len,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn bytes(&self, ofs: u64, len: usize) -> Result<&[u8]> {
+        let start: usize = ofs.try_into().map_err(|_| Error::OutOfBounds(ofs))?;
+        match start.checked_add(len) {
+            Some(end) if end <= self.len => {
+                Ok(unsafe { std::slice::from_raw_parts(self.ptr.add(start), len) })
+            }
+            _ => Err(Error::OutOfBounds(ofs)),
+        }
+    }
+
+    pub fn read_u32(&self, ofs: u64) -> Result<u32> {
+        Ok(u32::from_ne_bytes(self.bytes(ofs, 4)?.try_into().unwrap()))
+    }
+
+    pub fn read_u64(&self, ofs: u64) -> Result<u64> {
+        Ok(u64::from_ne_bytes(self.bytes(ofs, 8)?.try_into().unwrap()))
+    }
+
+    /// For the header fields the writer changes under our feet, which are all aligned
+    pub fn read_volatile_u64(&self, ofs: u64) -> Result<u64> {
+        let b = self.bytes(ofs, 8)?;
+        Ok(unsafe { std::ptr::read_volatile(b.as_ptr() as *const u64) })
+    }
+}
+
+impl Drop for Segment {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+/*
+ * stats.sock is an AF_UNIX SOCK_SEQPACKET socket: as soon as a client
+ * connects, VPP sends it the stats segment memfd over SCM_RIGHTS
+ * and that is all the conversation there is.
+ */
+use crate::error::Result;
+use std::fs::File;
+use std::os::unix::io::FromRawFd;
+
+fn last_os_error<T>() -> Result<T> {
+    Err(std::io::Error::last_os_error().into())
+}
+
+/// Connect to stats.sock and receive the stats segment file descriptor
+pub(crate) fn recv_segment_fd(sock_path: &str) -> Result<File> {
+    let fd = unsafe { libc::socket(libc::AF_UNIX, libc::SOCK_SEQPACKET, 0) };
+    if fd < 0 {
+        return last_os_error();
+    }
+    /* closes the socket on the way out */
+    let sock = unsafe { File::from_raw_fd(fd) };
+
+    let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+    let path = sock_path.as_bytes();
+    if path.len() >= addr.sun_path.len() {
+        return Err(format!("socket path {} is too long", sock_path).into());
+    }
+    for (i, b) in path.iter().enumerate() {
+        addr.sun_path[i] = *b as libc::c_char;
+    }
+    let ret = unsafe {
+        libc::connect(
+            fd,
+            &addr as *const libc::sockaddr_un as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_un>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return last_os_error();
+    }
+
+    let mut data = [0u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+    let cmsg_space = unsafe { libc::CMSG_SPACE(std::mem::size_of::<libc::c_int>() as u32) };
+    let mut cmsg_buf = vec![0u8; cmsg_space as usize];
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = cmsg_buf.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = cmsg_buf.len() as _;
+
+    if unsafe { libc::recvmsg(fd, &mut msg, 0) } < 0 {
+        return last_os_error();
+    }
+    drop(sock);
+
+    let cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+    if cmsg.is_null() {
+        return Err(format!("no file descriptor received from {}", sock_path).into());
+    }
+    let (level, typ) = unsafe { ((*cmsg).cmsg_level, (*cmsg).cmsg_type) };
+    if level != libc::SOL_SOCKET || typ != libc::SCM_RIGHTS {
+        return Err(format!(
+            "unexpected control message from {}: level {} type {}",
+            sock_path, level, typ
+        )
+        .into());
+    }
+    let memfd = unsafe { std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::c_int) };
+    Ok(unsafe { File::from_raw_fd(memfd) })
+}
@@ -0,0 +1,233 @@
+/*
+ * Synthesized stats segments for the unit tests, laid out the way
+ * VPP does it, with the pointers relative to a made-up base address.
+ * Also a stand-in for stats.sock which hands out the segment fd.
+ */
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const TEST_BASE: u64 = 0x7f00_0000_0000;
+const HEADER_SIZE: usize = 64;
+
+static PATH_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+pub fn temp_path(what: &str) -> String {
+    let mut path = std::env::temp_dir();
+    path.push(format!(
+        "vpp-api-stats-{}-{}-{}",
+        what,
+        std::process::id(),
+        PATH_COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+    path.to_str().unwrap().to_string()
+}
+
+pub struct SegmentBuilder {
+    version: u64,
+    buf: Vec<u8>,
+    entries: Vec<(u32, u64, String)>,
+    error_vector: u64,
+    in_progress: bool,
+    corrupt_directory: bool,
+}
+
+impl SegmentBuilder {
+    pub fn new(version: u64) -> Self {
+        SegmentBuilder {
+            version,
+            buf: vec![0; HEADER_SIZE],
+            entries: vec![],
+            error_vector: 0,
+            in_progress: false,
+            corrupt_directory: false,
+        }
+    }
+
+    /// Append a VPP vector: u32 length and padding, then the data; returns its "pointer"
+    fn vec(&mut self, len: usize, data: &[u8]) -> u64 {
+        self.buf.resize((self.buf.len() + 7) & !7, 0);
+        self.buf.extend_from_slice(&(len as u32).to_ne_bytes());
+        self.buf.extend_from_slice(&[0; 4]);
+        let ptr = TEST_BASE + self.buf.len() as u64;
+        self.buf.extend_from_slice(data);
+        ptr
+    }
+
+    fn vec_u64(&mut self, v: &[u64]) -> u64 {
+        let data: Vec<u8> = v.iter().flat_map(|x| x.to_ne_bytes().to_vec()).collect();
+        self.vec(v.len(), &data)
+    }
+
+    fn entry_type(&self, name: &str) -> u32 {
+        let types: &[&str] = if self.version == 1 {
+            &[
+                "", "scalar", "simple", "combined", "error", "names", "empty",
+            ]
+        } else {
+            &[
+                "", "scalar", "simple", "combined", "names", "empty", "symlink",
+            ]
+        };
+        types.iter().position(|t| *t == name).unwrap() as u32
+    }
+
+    fn entry(mut self, typ: &str, value: u64, name: &str) -> Self {
+        let typ = self.entry_type(typ);
+        self.entries.push((typ, value, name.to_string()));
+        self
+    }
+
+    pub fn scalar(self, name: &str, value: f64) -> Self {
+        let raw = if self.version == 1 {
+            value.to_bits()
+        } else {
+            value as u64
+        };
+        self.entry("scalar", raw, name)
+    }
+
+    pub fn simple(mut self, name: &str, threads: Vec<Vec<u64>>) -> Self {
+        let ptrs: Vec<u64> = threads.iter().map(|t| self.vec_u64(t)).collect();
+        let ptr = self.vec_u64(&ptrs);
+        self.entry("simple", ptr, name)
+    }
+
+    pub fn combined(mut self, name: &str, threads: Vec<Vec<(u64, u64)>>) -> Self {
+        let ptrs: Vec<u64> = threads
+            .iter()
+            .map(|t| {
+                let flat: Vec<u64> = t.iter().flat_map(|(p, b)| vec![*p, *b]).collect();
+                let data: Vec<u8> = flat.iter().flat_map(|x| x.to_ne_bytes().to_vec()).collect();
+                self.vec(t.len(), &data)
+            })
+            .collect();
+        let ptr = self.vec_u64(&ptrs);
+        self.entry("combined", ptr, name)
+    }
+
+    /// The per-thread error counters, version 1 only
+    pub fn errors(mut self, threads: Vec<Vec<u64>>) -> Self {
+        let ptrs: Vec<u64> = threads.iter().map(|t| self.vec_u64(t)).collect();
+        self.error_vector = self.vec_u64(&ptrs);
+        self
+    }
+
+    pub fn error(self, name: &str, index: u64) -> Self {
+        self.entry("error", index, name)
+    }
+
+    pub fn names(mut self, name: &str, names: Vec<Option<&str>>) -> Self {
+        let ptrs: Vec<u64> = names
+            .iter()
+            .map(|n| match n {
+                Some(n) => {
+                    let mut s = n.as_bytes().to_vec();
+                    s.push(0);
+                    self.vec(s.len(), &s)
+                }
+                None => 0,
+            })
+            .collect();
+        let ptr = self.vec_u64(&ptrs);
+        self.entry("names", ptr, name)
+    }
+
+    pub fn empty(self, name: &str) -> Self {
+        self.entry("empty", 0, name)
+    }
+
+    pub fn symlink(self, name: &str, entry: u32, index: u32) -> Self {
+        self.entry("symlink", ((index as u64) << 32) | entry as u64, name)
+    }
+
+    pub fn in_progress(mut self) -> Self {
+        self.in_progress = true;
+        self
+    }
+
+    /// Make the directory pointer point way past the end of the segment
+    pub fn corrupt_directory(mut self) -> Self {
+        self.corrupt_directory = true;
+        self
+    }
+
+    pub fn build(mut self) -> Vec<u8> {
+        let mut dir = vec![];
+        for (typ, value, name) in self.entries.iter() {
+            let mut name_a = [0u8; 128];
+            name_a[..name.len()].copy_from_slice(name.as_bytes());
+            dir.extend_from_slice(&typ.to_ne_bytes());
+            dir.extend_from_slice(&[0; 4]);
+            dir.extend_from_slice(&value.to_ne_bytes());
+            dir.extend_from_slice(&name_a);
+        }
+        let mut dir_ptr = self.vec(self.entries.len(), &dir);
+        if self.corrupt_directory {
+            dir_ptr += 1 << 30;
+        }
+
+        let header = [
+            self.version,
+            TEST_BASE,
+            1,
+            self.in_progress as u64,
+            dir_ptr,
+            self.error_vector,
+        ];
+        for (i, v) in header.iter().enumerate() {
+            self.buf[i * 8..i * 8 + 8].copy_from_slice(&v.to_ne_bytes());
+        }
+        self.buf
+    }
+
+    pub fn write_temp(self) -> String {
+        let path = temp_path("segment");
+        std::fs::write(&path, self.build()).unwrap();
+        path
+    }
+}
+
+/// Listen on a SOCK_SEQPACKET socket and hand out the segment fd to one client, like VPP does
+pub fn serve_segment(sock_path: &str, segment_path: &str) {
+    let listener = unsafe { libc::socket(libc::AF_UNIX, libc::SOCK_SEQPACKET, 0) };
+    assert!(listener >= 0);
+    let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+    for (i, b) in sock_path.as_bytes().iter().enumerate() {
+        addr.sun_path[i] = *b as libc::c_char;
+    }
+    let addr_len = std::mem::size_of::<libc::sockaddr_un>() as libc::socklen_t;
+    let addr_ptr = &addr as *const libc::sockaddr_un as *const libc::sockaddr;
+    assert_eq!(unsafe { libc::bind(listener, addr_ptr, addr_len) }, 0);
+    assert_eq!(unsafe { libc::listen(listener, 1) }, 0);
+
+    let segment = File::open(segment_path).unwrap();
+    std::thread::spawn(move || unsafe {
+        let client = libc::accept(listener, std::ptr::null_mut(), std::ptr::null_mut());
+        assert!(client >= 0);
+
+        let fd = segment.as_raw_fd();
+        let mut data = [0u8; 1];
+        let mut iov = libc::iovec {
+            iov_base: data.as_mut_ptr() as *mut libc::c_void,
+            iov_len: data.len(),
+        };
+        let mut cmsg_buf =
+            vec![0u8; libc::CMSG_SPACE(std::mem::size_of::<libc::c_int>() as u32) as usize];
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = cmsg_buf.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = cmsg_buf.len() as _;
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(std::mem::size_of::<libc::c_int>() as u32) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut libc::c_int, fd);
+        assert!(libc::sendmsg(client, &msg, 0) >= 0);
+
+        libc::close(client);
+        libc::close(listener);
+    });
+}
@@ -0,0 +1,177 @@
+use clap::Parser as ClapParser;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use vpp_api_transport::cli::{cli_output_is_error, script_commands, CliRunner};
+use vpp_api_transport::clisock::CliClient;
+use vpp_api_transport::{afunix, shmem, VppApiTransport};
+
+/// A vppctl look-alike which runs the CLI over the binary API
+/// (cli_inband), so it works wherever api.sock is reachable,
+/// or over cli.sock with --cli-socket.
+///
+/// With a command on the command line, runs it and exits; with a
+/// script (-f, or stdin which is not a terminal) runs the commands
+/// one by one; else starts an interactive session with history.
+/// The exit code is 1 if a command printed an error, 2 if the
+/// connection to VPP failed.
+#[derive(Debug, Clone, ClapParser, Serialize, Deserialize)]
+#[clap(version = env!("GIT_VERSION"), author = "Andrew Yourtchenko <ayourtch@gmail.com>")]
+struct Opts {
+    /// The VPP API socket
+    #[clap(short, long, default_value = "/run/vpp/api.sock")]
+    socket_path: String,
+
+    /// Use the shared memory transport instead of the socket
+    #[clap(long)]
+    shmem: bool,
+
+    /// Use the CLI socket at this path instead of the API
+    #[clap(long)]
+    cli_socket: Option<String>,
+
+    /// Run the commands from this file, "-" for stdin
+    #[clap(short, long)]
+    file: Option<String>,
+
+    /// In a script, carry on after a command fails
+    #[clap(short, long)]
+    keep_going: bool,
+
+    /// Echo the script commands before their output
+    #[clap(short, long)]
+    echo: bool,
+
+    /// The command to run
+    command: Vec<String>,
+}
+
+const EXIT_CLI_ERROR: i32 = 1;
+const EXIT_TRANSPORT_ERROR: i32 = 2;
+
+fn history_path() -> Option<String> {
+    std::env::var("HOME")
+        .ok()
+        .map(|home| format!("{}/.vpp_api_ctl_history", home))
+}
+
+/// Run the command and print the output, false if it failed
+fn run(t: &mut dyn CliRunner, cmd: &str) -> bool {
+    match t.run_cli(cmd) {
+        Ok(out) => {
+            print!("{}", out);
+            !cli_output_is_error(&out)
+        }
+        Err(e) => {
+            eprintln!("Error running '{}': {:?}", cmd, e);
+            std::process::exit(EXIT_TRANSPORT_ERROR);
+        }
+    }
+}
+
+fn run_script(opts: &Opts, t: &mut dyn CliRunner, script: &str) -> i32 {
+    let mut exit_code = 0;
+    for cmd in script_commands(script) {
+        if opts.echo {
+            println!("vpp# {}", &cmd);
+        }
+        if !run(t, &cmd) {
+            exit_code = EXIT_CLI_ERROR;
+            if !opts.keep_going {
+                break;
+            }
+        }
+    }
+    exit_code
+}
+
+fn interactive(t: &mut dyn CliRunner) -> i32 {
+    let mut rl = DefaultEditor::new().unwrap();
+    let history = history_path();
+    if let Some(h) = &history {
+        let _ = rl.load_history(h);
+    }
+    let mut exit_code = 0;
+    loop {
+        match rl.readline("vpp# ") {
+            Ok(line) => {
+                let cmd = line.trim();
+                if cmd.is_empty() {
+                    continue;
+                }
+                let _ = rl.add_history_entry(cmd);
+                if cmd == "quit" || cmd == "exit" {
+                    break;
+                }
+                exit_code = if run(t, cmd) { 0 } else { EXIT_CLI_ERROR };
+            }
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                eprintln!("Error reading the input: {:?}", e);
+                break;
+            }
+        }
+    }
+    if let Some(h) = &history {
+        let _ = rl.save_history(h);
+    }
+    exit_code
+}
+
+fn main() {
+    let opts: Opts = Opts::parse();
+
+    let mut api: Option<Box<dyn VppApiTransport>> = None;
+    let mut clisock: Option<CliClient> = None;
+    let res = if let Some(path) = &opts.cli_socket {
+        let c = clisock.insert(CliClient::new(path));
+        c.connect()
+    } else {
+        let t: &mut Box<dyn VppApiTransport> = api.insert(if opts.shmem {
+            Box::new(shmem::Transport::new())
+        } else {
+            Box::new(afunix::Transport::new(&opts.socket_path))
+        });
+        t.connect("vpp-api-ctl", None, 256).map(|_| ())
+    };
+    if let Err(e) = res {
+        eprintln!("Can not connect to VPP: {:?}", e);
+        std::process::exit(EXIT_TRANSPORT_ERROR);
+    }
+    let t: &mut dyn CliRunner = match (&mut api, &mut clisock) {
+        (Some(api), _) => api,
+        (_, Some(c)) => c,
+        _ => unreachable!(),
+    };
+
+    let stdin_is_tty = unsafe { libc::isatty(0) } == 1;
+    let exit_code = if !opts.command.is_empty() {
+        if run(t, &opts.command.join(" ")) {
+            0
+        } else {
+            EXIT_CLI_ERROR
+        }
+    } else if opts.file.is_some() || !stdin_is_tty {
+        let script = match opts.file.as_deref() {
+            Some("-") | None => {
+                let mut s = String::new();
+                std::io::stdin().read_to_string(&mut s).unwrap();
+                s
+            }
+            Some(fname) => std::fs::read_to_string(fname).unwrap(),
+        };
+        run_script(&opts, t, &script)
+    } else {
+        interactive(t)
+    };
+
+    if let Some(mut api) = api {
+        api.disconnect();
+    }
+    if let Some(mut c) = clisock {
+        c.disconnect();
+    }
+    std::process::exit(exit_code);
+}
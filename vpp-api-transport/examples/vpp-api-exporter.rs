@@ -0,0 +1,39 @@
+use clap::Parser as ClapParser;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use vpp_api_transport::exporter::Exporter;
+
+/// This program serves the VPP interface and error counters from the
+/// stats segment on /metrics in the Prometheus text format, with the
+/// interface names and admin/link state from the API socket
+#[derive(Debug, Clone, ClapParser, Serialize, Deserialize)]
+#[clap(version = env!("GIT_VERSION"), author = "Andrew Yourtchenko <ayourtch@gmail.com>")]
+struct Opts {
+    /// Serve the metrics on this address
+    #[clap(short, long, default_value = "0.0.0.0:9482")]
+    listen_addr: String,
+
+    /// The VPP API socket
+    #[clap(short, long, default_value = "/run/vpp/api.sock")]
+    api_path: String,
+
+    /// The VPP stats socket
+    #[clap(short, long, default_value = "/run/vpp/stats.sock")]
+    stats_path: String,
+
+    /// Seconds to wait before reconnecting to the API socket
+    #[clap(short, long, default_value = "5")]
+    reconnect_interval: u64,
+}
+
+fn main() {
+    let opts: Opts = Opts::parse();
+
+    let exporter = Exporter::new(&opts.api_path, &opts.stats_path)
+        .with_reconnect_interval(Duration::from_secs(opts.reconnect_interval));
+    println!(
+        "Serving {} and {} on http://{}/metrics",
+        &opts.api_path, &opts.stats_path, &opts.listen_addr
+    );
+    exporter.run(&opts.listen_addr).unwrap();
+}
@@ -0,0 +1,50 @@
+use clap::Parser as ClapParser;
+use serde::{Deserialize, Serialize};
+use vpp_api_transport::proxy::{Proxy, ProxyConfig};
+
+/// This program sits in front of the VPP API socket and lets
+/// the clients through according to the per-uid/gid allow-lists
+/// of message names from the config file, e.g.:
+///
+/// upstream_path: /run/vpp/api.sock
+/// clients:
+///   - uid: 1001
+///     allow: ["*_dump", "show_*", "control_ping"]
+///   - gid: 0
+///     allow: ["*"]
+#[derive(Debug, Clone, ClapParser, Serialize, Deserialize)]
+#[clap(version = env!("GIT_VERSION"), author = "Andrew Yourtchenko <ayourtch@gmail.com>")]
+struct Opts {
+    /// Accept the clients on this AF_UNIX socket path
+    #[clap(short, long)]
+    listen_path: String,
+
+    /// The proxy config (yaml/json) with the upstream socket and the client policies
+    #[clap(short, long)]
+    config: String,
+
+    /// Override the upstream API socket path from the config
+    #[clap(short, long)]
+    upstream_path: Option<String>,
+}
+
+fn main() {
+    let opts: Opts = Opts::parse();
+
+    let data = std::fs::read_to_string(&opts.config).unwrap();
+    let mut config: ProxyConfig = match serde_json::from_str(&data) {
+        Ok(c) => c,
+        Err(_) => serde_yaml::from_str(&data).unwrap(),
+    };
+    if let Some(upstream_path) = &opts.upstream_path {
+        config.upstream_path = upstream_path.clone();
+    }
+
+    println!(
+        "Proxying {} => {} with {} client policies",
+        &opts.listen_path,
+        &config.upstream_path,
+        config.clients.len()
+    );
+    Proxy::new(config).run(&opts.listen_path).unwrap();
+}
@@ -0,0 +1,53 @@
+use clap::Parser as ClapParser;
+use serde::{Deserialize, Serialize};
+use vpp_api_transport::remote::Relay;
+
+/// This program exposes the local VPP API socket over TCP,
+/// for the vpp_api_transport::remote::Transport to connect to
+#[derive(Debug, Clone, ClapParser, Serialize, Deserialize)]
+#[clap(version = env!("GIT_VERSION"), author = "Andrew Yourtchenko <ayourtch@gmail.com>")]
+struct Opts {
+    /// Listen on this address
+    #[clap(short, long, default_value = "127.0.0.1:9999")]
+    listen_addr: String,
+
+    /// The VPP API socket to relay to
+    #[clap(short, long, default_value = "/run/vpp/api.sock")]
+    upstream_path: String,
+
+    /// Read the shared token the clients must present from this file
+    #[clap(short, long)]
+    token_file: Option<String>,
+
+    /// Serve TLS with this PEM certificate chain (needs the "tls" feature)
+    #[clap(long, requires = "tls-key")]
+    tls_cert: Option<String>,
+
+    /// The PEM private key for the TLS certificate
+    #[clap(long, requires = "tls-cert")]
+    tls_key: Option<String>,
+}
+
+fn main() {
+    let opts: Opts = Opts::parse();
+
+    let mut relay = Relay::new(&opts.upstream_path);
+    if let Some(fname) = &opts.token_file {
+        let token = std::fs::read_to_string(fname).unwrap();
+        relay = relay.with_token(token.trim_end());
+    }
+    if let (Some(cert), Some(key)) = (&opts.tls_cert, &opts.tls_key) {
+        #[cfg(feature = "tls")]
+        {
+            relay = relay.with_tls(cert, key).unwrap();
+        }
+        #[cfg(not(feature = "tls"))]
+        panic!(
+            "Can not use {} and {}, rebuild with --features tls",
+            cert, key
+        );
+    }
+
+    println!("Relaying {} => {}", &opts.listen_addr, &opts.upstream_path);
+    relay.run(&opts.listen_addr).unwrap();
+}
@@ -1,186 +1,304 @@
-use bincode;
-use bincode::Options;
-use serde::{Deserialize, Serialize};
 use std::os::unix::net::UnixStream;
 
-use crate::error::Result;
-use crate::VppApiTransport;
-use std::collections::HashMap;
+pub mod shm;
 
-use crate::get_encoder;
-use std::collections::VecDeque;
-use std::sync::{Arc, Mutex};
+use crate::error::{Error, Result};
+use crate::instrument::ApiMetrics;
+use crate::protocol::{
+    blocking_handshake, blocking_next_msg, sockclnt_delete_msg, split_msg_id, SOCKCLNT_DELETE,
+    SOCKCLNT_DELETE_REPLY,
+};
+use crate::protocol::{encode_frame, FrameDecoder, Protocol};
+use crate::{write_sock_frame, VppApiTransport};
+use log::debug;
+use shm::{ShmChannel, ShmConfig};
 
-mod big_array;
-use big_array::BigArray;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Default)]
-struct GlobalState {
-    created: bool,
-    _receive_buffer: VecDeque<u8>,
-}
-
-lazy_static! {
-    static ref GLOBAL: Arc<Mutex<GlobalState>> = {
-        let gs = GlobalState {
-            ..Default::default()
-        };
+/// How long disconnect() waits for the sockclnt_delete_reply
+pub const SOCKCLNT_DELETE_TIMEOUT: Duration = Duration::from_millis(500);
 
-        Arc::new(Mutex::new(gs))
-    };
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct SockMsgHeader {
-    _q: u64,
-    msglen: u32,
-    gc_mark: u32,
-}
+pub use crate::protocol::{
+    MsgSockClntCreate, MsgSockClntCreateReplyEntry, MsgSockClntCreateReplyHdr,
+};
 
 pub struct Transport {
     connected: bool,
     sock_path: String,
     sock: Option<std::os::unix::net::UnixStream>,
-    message_name_to_id: HashMap<String, u16>,
-    message_max_index: u16,
-    client_index: u32,
+    /* the message table, and the keepalives on the way to the messages read */
+    proto: Protocol,
+    client_name: String,
+    /* after upgrade_to_shm() the messages go over the rings instead */
+    shm: Option<ShmChannel>,
+    shm_rx_buf: Vec<u8>,
+    nonblocking: bool,
+    read_timeout: Option<Duration>,
+    metrics: Option<Arc<dyn ApiMetrics>>,
 }
 
 impl Transport {
     pub fn new(path: &str) -> Self {
-        let mut gs = GLOBAL.lock().unwrap();
-        if gs.created {
-            panic!("One transport already created!");
-        }
-
-        // It's technically okay to have multiple transports for unix socket
-        // The tests can make use of this ability to parallelize
-        // But to retain the compatibility with the shared memory transport,
-        // we keep this (simplistic) lock here for now
-        gs.created = true;
-
         Transport {
             connected: false,
             sock_path: path.to_owned(),
             sock: None,
-            message_name_to_id: HashMap::new(),
-            message_max_index: 0,
-            client_index: 0,
+            proto: Protocol::new(),
+            client_name: String::new(),
+            shm: None,
+            shm_rx_buf: vec![],
+            nonblocking: false,
+            read_timeout: None,
+            metrics: None,
+        }
+    }
+
+    /// Report the calls made over this transport to this sink instead of the process-wide one
+    pub fn with_metrics(mut self, metrics: Arc<dyn ApiMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Fail the reads which take longer than this instead of waiting forever
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        match &self.sock {
+            Some(s) => {
+                self.read_timeout = timeout;
+                Ok(s.set_read_timeout(timeout)?)
+            }
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                "trying to set a timeout on unconnected socket",
+            )
+            .into()),
         }
     }
+
+    /// Switch the message exchange over to the shared-memory rings (sock_init_shm)
+    pub fn upgrade_to_shm(&mut self, config: &ShmConfig) -> Result<()> {
+        if self.shm.is_some() {
+            return Ok(());
+        }
+        let sock = match self.sock.as_mut() {
+            Some(s) => s,
+            None => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotConnected,
+                    "trying to upgrade unconnected socket",
+                )
+                .into())
+            }
+        };
+        sock.set_nonblocking(false)?;
+        let res = shm::upgrade(
+            sock,
+            self.proto.message_table(),
+            self.proto.client_index(),
+            &self.client_name,
+            config,
+        );
+        sock.set_nonblocking(self.nonblocking)?;
+        sock.set_read_timeout(self.read_timeout)?;
+        self.shm = Some(res?);
+        Ok(())
+    }
+
+    pub fn is_shm(&self) -> bool {
+        self.shm.is_some()
+    }
+
+    fn shm_timeout(&self) -> Option<Duration> {
+        if self.nonblocking {
+            Some(Duration::from_secs(0))
+        } else {
+            self.read_timeout
+        }
+    }
+
+    /* drop the shared memory registration, the same way as sockclnt_delete() */
+    fn memclnt_delete(&mut self) -> Result<()> {
+        let shm = match self.shm.as_mut() {
+            Some(shm) => shm,
+            None => return Ok(()),
+        };
+        let delete_id = match self.proto.get_msg_index(shm::MEMCLNT_DELETE) {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        let reply_id = self.proto.get_msg_index(shm::MEMCLNT_DELETE_REPLY);
+        shm.send(&shm.memclnt_delete_msg(delete_id))?;
+
+        let deadline = Instant::now() + SOCKCLNT_DELETE_TIMEOUT;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left == Duration::from_secs(0) {
+                return Err(Error::Error(
+                    "timeout waiting for memclnt_delete_reply".into(),
+                ));
+            }
+            let mut stream = ShmStream::new(shm, &mut self.shm_rx_buf, Some(left));
+            let data = blocking_next_msg(&mut self.proto, &mut stream)?;
+            let (msg_id, _) = split_msg_id(&data)?;
+            if Some(msg_id) == reply_id {
+                return Ok(());
+            }
+        }
+    }
+
+    /* unregister with VPP and wait (briefly) for it to confirm */
+    fn sockclnt_delete(&mut self) -> Result<()> {
+        let delete_id = match self.get_msg_index(SOCKCLNT_DELETE) {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        let reply_id = self.get_msg_index(SOCKCLNT_DELETE_REPLY);
+        let context = self.get_next_context();
+        let msg = sockclnt_delete_msg(delete_id, self.proto.client_index(), context);
+        let sock = self.sock.as_mut().unwrap();
+        sock.set_nonblocking(false)?;
+        sock.set_read_timeout(Some(SOCKCLNT_DELETE_TIMEOUT))?;
+        write_sock_frame(sock, &msg)?;
+
+        let deadline = Instant::now() + SOCKCLNT_DELETE_TIMEOUT;
+        loop {
+            let data = match blocking_next_msg(&mut self.proto, sock) {
+                Ok(data) => data,
+                /* VPP may close the socket right away */
+                Err(Error::ServerClosed) => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            let (msg_id, _) = split_msg_id(&data)?;
+            if Some(msg_id) == reply_id {
+                return Ok(());
+            }
+            if Instant::now() > deadline {
+                return Err(Error::Error(
+                    "timeout waiting for sockclnt_delete_reply".into(),
+                ));
+            }
+        }
+    }
+}
+
+/*
+ * The shared memory rings as a stream of SockMsgHeader-framed messages,
+ * the same as the socket, so that the Protocol can read over either.
+ */
+struct ShmStream<'a> {
+    shm: &'a mut ShmChannel,
+    rx_buf: &'a mut Vec<u8>,
+    tx: FrameDecoder,
+    /* Some(0) when nonblocking */
+    timeout: Option<Duration>,
 }
 
-impl Drop for Transport {
-    fn drop(&mut self) {
-        let mut gs = GLOBAL.lock().unwrap();
-        gs.created = false;
+impl<'a> ShmStream<'a> {
+    fn new(shm: &'a mut ShmChannel, rx_buf: &'a mut Vec<u8>, timeout: Option<Duration>) -> Self {
+        ShmStream {
+            shm,
+            rx_buf,
+            tx: FrameDecoder::new(),
+            timeout,
+        }
     }
 }
 
-impl std::io::Read for Transport {
+impl std::io::Read for ShmStream<'_> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.sock.as_ref().unwrap().read(buf)
+        if self.rx_buf.is_empty() {
+            match self.shm.recv(self.timeout).map_err(to_io_error)? {
+                Some(msg) => *self.rx_buf = encode_frame(&msg),
+                None if self.timeout == Some(Duration::from_secs(0)) => {
+                    return Err(std::io::ErrorKind::WouldBlock.into())
+                }
+                None => return Err(std::io::ErrorKind::TimedOut.into()),
+            }
+        }
+        let n = std::cmp::min(buf.len(), self.rx_buf.len());
+        buf[..n].copy_from_slice(&self.rx_buf[..n]);
+        self.rx_buf.drain(..n);
+        Ok(n)
     }
 }
-impl std::io::Write for Transport {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let hdr = SockMsgHeader {
-            _q: 0,
-            msglen: buf.len() as u32,
-            gc_mark: 0,
-        };
-        let hdre = get_encoder().serialize(&hdr).unwrap();
 
-        self.sock.as_ref().unwrap().write(&hdre)?;
-        self.sock.as_ref().unwrap().write(buf)?;
-        self.sock.as_ref().unwrap().flush()?;
+impl std::io::Write for ShmStream<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.tx.feed(buf);
+        while let Some(msg) = self.tx.next_frame().map_err(to_io_error)? {
+            self.shm.send(&msg).map_err(to_io_error)?;
+        }
         Ok(buf.len())
     }
     fn flush(&mut self) -> std::io::Result<()> {
-        self.sock.as_ref().unwrap().flush()
+        Ok(())
     }
 }
 
-type ArrayOf64U8 = [u8; 64];
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct MsgSockClntCreate {
-    _vl_msg_id: u16,
-    context: u32,
-    #[serde(with = "BigArray")]
-    name: ArrayOf64U8,
+fn to_io_error(e: Error) -> std::io::Error {
+    match e {
+        Error::IoError(e) => e,
+        e => std::io::Error::other(format!("{:?}", e)),
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct MsgSockClntCreateReplyHdr {
-    _vl_msg_id: u16,
-    client_index: u32,
-    context: u32,
-    response: i32,
-    index: u32,
-    count: u16,
+impl std::io::Read for Transport {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let timeout = self.shm_timeout();
+        if let Some(shm) = self.shm.as_mut() {
+            return ShmStream::new(shm, &mut self.shm_rx_buf, timeout).read(buf);
+        }
+        self.sock.as_ref().unwrap().read(buf)
+    }
 }
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct MsgSockClntCreateReplyEntry {
-    index: u16,
-    #[serde(with = "BigArray")]
-    name: ArrayOf64U8,
+impl std::io::Write for Transport {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Some(shm) = self.shm.as_mut() {
+            shm.send(buf).map_err(to_io_error)?;
+            return Ok(buf.len());
+        }
+        write_sock_frame(self.sock.as_mut().unwrap(), buf)?;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.sock.as_ref().unwrap().flush()
+    }
 }
 
 impl VppApiTransport for Transport {
     fn connect(&mut self, name: &str, _chroot_prefix: Option<&str>, _rx_qlen: i32) -> Result<()> {
-        use std::io::Write;
-
-        let s = UnixStream::connect(&self.sock_path)?;
+        let mut s = UnixStream::connect(&self.sock_path)?;
+        let proto = blocking_handshake(&mut s, name)?;
         self.sock = Some(s);
         self.connected = true;
-
-        /* FIXME: this is ugly and odd, there's gotta be a better way... */
-        let mut name1 = name.to_string();
-        let mut name_a: [u8; 64] = [0; 64];
-        while name1.len() < name_a.len() {
-            name1.push('\0');
-        }
-        name_a.copy_from_slice(&name1.as_bytes());
-
-        let sockclnt_create = MsgSockClntCreate {
-            _vl_msg_id: 15,
-            context: 124,
-            name: name_a,
-        };
-
-        let scs = get_encoder().serialize(&sockclnt_create).unwrap();
-
-        self.write(&scs)?;
-        let buf = self.read_one_msg()?;
-        let hdr: MsgSockClntCreateReplyHdr = get_encoder().deserialize(&buf[0..20]).unwrap();
-        self.client_index = hdr.index as u32;
-        let mut i = 0;
-        self.message_max_index = hdr.count;
-        while i < hdr.count as usize {
-            let sz = 66; /* MsgSockClntCreateReplyEntry */
-            let ofs1 = 20 + i * 66;
-            let ofs2 = ofs1 + sz;
-
-            let msg: MsgSockClntCreateReplyEntry =
-                get_encoder().deserialize(&buf[ofs1..ofs2]).unwrap();
-            let msg_name_trailing_zero = String::from_utf8_lossy(&msg.name);
-            let msg_name = msg_name_trailing_zero.trim_end_matches("\u{0}");
-            self.message_name_to_id.insert(msg_name.into(), msg.index);
-            i = i + 1;
-        }
+        self.proto = proto;
+        self.client_name = name.to_string();
         Ok(())
     }
     fn disconnect(&mut self) {
         if self.connected {
+            /* the socket registration outlives the shared memory one */
+            if let Err(e) = self.memclnt_delete() {
+                debug!("memclnt_delete failed: {:?}", e);
+            }
+            self.shm = None;
+            self.shm_rx_buf.clear();
+            if let Err(e) = self.sockclnt_delete() {
+                debug!("sockclnt_delete failed: {:?}", e);
+            }
             self.sock = None;
+            self.proto = Protocol::new();
             self.connected = false;
         }
     }
     fn set_nonblocking(&mut self, nonblocking: bool) -> Result<()> {
         if let Some(ref mut s) = self.sock {
             s.set_nonblocking(nonblocking)?;
+            self.nonblocking = nonblocking;
             Ok(())
         } else {
             Err(std::io::Error::new(
@@ -192,16 +310,152 @@ impl VppApiTransport for Transport {
     }
 
     fn get_client_index(&self) -> u32 {
-        self.client_index
+        match &self.shm {
+            Some(shm) => shm.client_index(),
+            None => self.proto.client_index(),
+        }
     }
     fn get_msg_index(&mut self, name: &str) -> Option<u16> {
-        self.message_name_to_id.get(name).map(|x| x.to_owned())
+        self.proto.get_msg_index(name)
     }
     fn get_table_max_index(&mut self) -> u16 {
-        0
+        self.proto.message_max_index()
     }
     fn dump(&self) {
-        let gs = GLOBAL.lock().unwrap();
-        println!("Global state: {:?}", &gs);
+        println!(
+            "Socket transport to {}: connected: {} client index: {} messages: {}",
+            &self.sock_path,
+            self.connected,
+            self.get_client_index(),
+            self.proto.message_table().len()
+        );
+    }
+
+    fn metrics(&self) -> Option<Arc<dyn ApiMetrics>> {
+        self.metrics.clone()
+    }
+
+    fn read_one_msg_into(&mut self, data: &mut Vec<u8>) -> Result<()> {
+        let timeout = self.shm_timeout();
+        let sock = match self.sock.as_mut() {
+            Some(s) => s,
+            None => return Err(Error::ServerClosed),
+        };
+        *data = match self.shm.as_mut() {
+            Some(shm) => {
+                let mut stream = ShmStream::new(shm, &mut self.shm_rx_buf, timeout);
+                blocking_next_msg(&mut self.proto, &mut stream)?
+            }
+            None => blocking_next_msg(&mut self.proto, sock)?,
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::mock::{MockVpp, MOCK_MESSAGES};
+    use std::io::Write;
+
+    #[test]
+    fn test_disconnect_sends_sockclnt_delete() {
+        let mock = MockVpp::start();
+        let mut t = Transport::new(mock.path());
+        t.connect("delete-test", None, 32).unwrap();
+        t.disconnect();
+        assert!(!t.is_connected());
+        assert_eq!(
+            mock.received(),
+            vec!["sockclnt_create".to_string(), "sockclnt_delete".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_keepalive_answered() {
+        let mock = MockVpp::start();
+        let mut t = Transport::new(mock.path());
+        t.connect("keepalive-test", None, 32).unwrap();
+        t.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        /* the mock sends a memclnt_keepalive and replies once it is answered */
+        let show_version = t.get_msg_index("show_version_51077d14").unwrap();
+        let msg = crate::protocol::control_ping_msg(show_version, t.get_client_index(), 5);
+        t.write_all(&msg).unwrap();
+        let (msg_id, data) = t.read_one_msg_id_and_msg().unwrap();
+        assert_eq!(Some(msg_id), t.get_msg_index("show_version_reply_c919bde1"));
+        assert_eq!(&data[0..4], &5u32.to_be_bytes());
+        assert!(mock
+            .received()
+            .contains(&"memclnt_keepalive_reply".to_string()));
+        t.disconnect();
+    }
+
+    #[test]
+    fn test_server_closed() {
+        let mock = MockVpp::start();
+        let mut t = Transport::new(mock.path());
+        t.connect("closed-test", None, 32).unwrap();
+
+        /* the mock hangs up after replying to the sockclnt_delete */
+        t.sockclnt_delete().unwrap();
+        match t.read_one_msg() {
+            Err(Error::ServerClosed) => {}
+            other => panic!("expected ServerClosed, got {:?}", other),
+        }
+        assert_eq!(
+            format!("{}", Error::ServerClosed),
+            "Error: server closed connection"
+        );
+    }
+
+    #[test]
+    fn test_upgrade_to_shm() {
+        let mock = MockVpp::start();
+        let mut t = Transport::new(mock.path());
+        t.connect("shm-test", None, 32).unwrap();
+        assert_eq!(t.get_table_max_index(), MOCK_MESSAGES.len() as u16);
+        let sock_index = t.get_client_index();
+        t.upgrade_to_shm(&ShmConfig::default()).unwrap();
+        assert!(t.is_shm());
+        assert_ne!(t.get_client_index(), sock_index);
+
+        assert_eq!(
+            t.run_cli_inband("show version").unwrap(),
+            "vpp v0.0-mock built by nobody\n"
+        );
+        let context = t.control_ping().unwrap();
+        t.skip_to_control_ping_reply(context).unwrap();
+
+        /* the keepalive comes and gets its reply over the rings too */
+        let show_version = t.get_msg_index("show_version_51077d14").unwrap();
+        let msg = crate::protocol::control_ping_msg(show_version, t.get_client_index(), 6);
+        t.write_all(&msg).unwrap();
+        let (msg_id, _) = t.read_one_msg_id_and_msg().unwrap();
+        assert_eq!(Some(msg_id), t.get_msg_index("show_version_reply_c919bde1"));
+
+        /* nothing more is queued */
+        t.set_nonblocking(true).unwrap();
+        let mut buf = [0u8; 16];
+        let err = std::io::Read::read(&mut t, &mut buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+        t.set_nonblocking(false).unwrap();
+
+        t.disconnect();
+        assert_eq!(
+            mock.received(),
+            vec![
+                "sockclnt_create",
+                "sock_init_shm",
+                "memclnt_create",
+                "cli_inband",
+                "control_ping",
+                "show_version",
+                "memclnt_keepalive_reply",
+                "memclnt_delete",
+                "sockclnt_delete"
+            ]
+        );
     }
 }
@@ -0,0 +1,748 @@
+/*
+ * The shared-memory message exchange on top of an api.sock connection.
+ *
+ * The client sends sock_init_shm with the sizes of the message rings,
+ * VPP replies and then sends the memfd of a fresh segment in a separate
+ * "fdmsg" message over SCM_RIGHTS. The segment has to be mapped at the
+ * address VPP has it at (the pointers inside are absolute): it holds
+ * an svm region one page in, whose user_ctx is the vl_shmem_hdr_t with
+ * the VPP input queue and the message rings.
+ *
+ * To send, a message is copied into a free slot of a client ring and
+ * its address is added to the VPP input queue. VPP sends the replies
+ * the same way, from its rings into our input queue. Our input queue
+ * has to live in the segment too, and since the heap there belongs to
+ * VPP, it is carved out of a dedicated one-slot client ring, which is
+ * then never freed. The registration itself is a memclnt_create sent
+ * over the rings; the client index it returns is used from then on,
+ * and it is dropped with a memclnt_delete over the rings as well.
+ *
+ * The layouts below are the ones of VPP built for 64-bit Linux/glibc.
+ */
+use crate::error::{Error, Result};
+use crate::protocol::split_msg_id;
+use crate::{read_sock_frame, write_sock_frame};
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::File;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::net::UnixStream;
+use std::ptr::{read_volatile, write_volatile};
+use std::time::{Duration, Instant};
+
+pub const SOCK_INIT_SHM: &str = "sock_init_shm_51646d92";
+pub const SOCK_INIT_SHM_REPLY: &str = "sock_init_shm_reply_e8d4e804";
+pub const MEMCLNT_CREATE: &str = "memclnt_create_9c5e1c2f";
+pub const MEMCLNT_CREATE_REPLY: &str = "memclnt_create_reply_42ec4560";
+pub const MEMCLNT_DELETE: &str = "memclnt_delete_7e1c04e3";
+pub const MEMCLNT_DELETE_REPLY: &str = "memclnt_delete_reply_3d3b6312";
+
+/// How long to wait for the memfd and for the memclnt_create_reply
+pub const SHM_SETUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/* not all of the fields are needed on the client side */
+#[allow(dead_code)]
+pub(crate) mod layout {
+    /* ssvm_shared_header_t, at the start of the segment */
+    pub const SSVM_VA: usize = 24;
+    pub const SSVM_SIZE: usize = 32;
+    pub const SSVM_READY: usize = 112;
+    /* svm_region_t, one page into the segment */
+    pub const REGION_USER_CTX: usize = 152;
+    /* vl_shmem_hdr_t */
+    pub const HDR_VL_INPUT_QUEUE: usize = 8;
+    pub const HDR_VL_RINGS: usize = 16;
+    pub const HDR_CLIENT_RINGS: usize = 24;
+    pub const HDR_LEN: usize = 48;
+    /* ring_alloc_t, in a clib vector with the u32 length 8 bytes before the first one */
+    pub const RING_RP: usize = 0;
+    pub const RING_SIZE: usize = 8;
+    pub const RING_NITEMS: usize = 10;
+    pub const RING_ALLOC_LEN: usize = 24;
+    pub const VEC_HEADER_LEN: usize = 8;
+    /* svm_queue_t */
+    pub const Q_MUTEX: usize = 0;
+    pub const Q_CONDVAR: usize = 40;
+    pub const Q_HEAD: usize = 88;
+    pub const Q_TAIL: usize = 92;
+    pub const Q_CURSIZE: usize = 96;
+    pub const Q_MAXSIZE: usize = 100;
+    pub const Q_ELSIZE: usize = 104;
+    pub const Q_CONSUMER_PID: usize = 108;
+    pub const Q_PRODUCER_EVTFD: usize = 112;
+    pub const Q_CONSUMER_EVTFD: usize = 116;
+    pub const Q_DATA: usize = 120;
+    /* msgbuf_t, in front of every message in a ring */
+    pub const MSGBUF_Q: usize = 0;
+    pub const MSGBUF_DATA_LEN: usize = 8;
+    pub const MSGBUF_GC_MARK: usize = 12;
+    pub const MSGBUF_DATA: usize = 16;
+}
+use layout::*;
+
+fn last_os_error<T>() -> Result<T> {
+    Err(std::io::Error::last_os_error().into())
+}
+
+pub(crate) fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShmElemType {
+    /// VPP allocates the messages to the client from these
+    VlibRing = 0,
+    /// The client allocates the messages to VPP from these
+    ClientRing = 1,
+    /// The VPP input queue
+    Queue = 2,
+}
+
+/// One vl_api_shm_elem_config_t: the ring of "count" slots of "size" bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShmElemConfig {
+    pub typ: ShmElemType,
+    pub count: u16,
+    pub size: u32,
+}
+
+impl ShmElemConfig {
+    /* the struct is sent as a u64 in the network order */
+    fn to_u64(self) -> u64 {
+        let mut b = [0u8; 8];
+        b[0] = self.typ as u8;
+        b[2..4].copy_from_slice(&self.count.to_ne_bytes());
+        b[4..8].copy_from_slice(&self.size.to_ne_bytes());
+        u64::from_ne_bytes(b)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ShmConfig {
+    pub requested_size: u32,
+    /// The rings and the VPP input queue; our input queue ring is added to these
+    pub elems: Vec<ShmElemConfig>,
+    /// How many messages our input queue holds
+    pub input_queue_len: u32,
+}
+
+impl Default for ShmConfig {
+    fn default() -> Self {
+        use ShmElemType::*;
+        let e = |typ, count, size| ShmElemConfig { typ, count, size };
+        /*
+         * The ring sizes are u16 in VPP; whatever does not fit the largest
+         * VPP ring comes from the VPP heap in the segment, and we can not
+         * free that, so make it large enough for the usual replies.
+         */
+        ShmConfig {
+            requested_size: 64 << 20,
+            elems: vec![
+                e(Queue, 1024, 8),
+                e(VlibRing, 128, 256),
+                e(VlibRing, 64, 1024),
+                e(VlibRing, 32, 4096),
+                e(VlibRing, 16, 65520),
+                e(ClientRing, 64, 256),
+                e(ClientRing, 32, 1024),
+                e(ClientRing, 16, 4096),
+                e(ClientRing, 4, 65520),
+            ],
+            input_queue_len: 1024,
+        }
+    }
+}
+
+impl ShmConfig {
+    fn input_queue_ring(&self) -> ShmElemConfig {
+        ShmElemConfig {
+            typ: ShmElemType::ClientRing,
+            count: 1,
+            size: (MSGBUF_DATA + Q_DATA + self.input_queue_len as usize * 8) as u32,
+        }
+    }
+
+    /* all the rings, ours last */
+    fn all_elems(&self) -> Result<Vec<ShmElemConfig>> {
+        let mut elems = self.elems.clone();
+        elems.push(self.input_queue_ring());
+        if let Some(e) = elems.iter().find(|e| e.size > u16::MAX as u32) {
+            return Err(format!("ring slot size {} does not fit in u16", e.size).into());
+        }
+        Ok(elems)
+    }
+}
+
+/*
+ * An svm_queue_t somewhere in the segment: a process-shared mutex and
+ * condvar in front of a circular buffer of fixed-size elements.
+ */
+pub(crate) struct Queue {
+    base: *mut u8,
+}
+
+impl Queue {
+    /// # Safety
+    /// base must point to an svm_queue_t which stays mapped for the lifetime of the Queue
+    pub unsafe fn from_raw(base: *mut u8) -> Self {
+        Queue { base }
+    }
+
+    /// # Safety
+    /// base must point to enough mapped shared memory for the header and the elements
+    pub unsafe fn init(base: *mut u8, maxsize: u32, elsize: u32) -> Result<Self> {
+        std::ptr::write_bytes(base, 0, Q_DATA);
+        let q = Queue { base };
+        let mut attr: libc::pthread_mutexattr_t = std::mem::zeroed();
+        let mut cattr: libc::pthread_condattr_t = std::mem::zeroed();
+        if libc::pthread_mutexattr_init(&mut attr) != 0
+            || libc::pthread_mutexattr_setpshared(&mut attr, libc::PTHREAD_PROCESS_SHARED) != 0
+            || libc::pthread_mutex_init(q.mutex(), &attr) != 0
+            || libc::pthread_condattr_init(&mut cattr) != 0
+            || libc::pthread_condattr_setpshared(&mut cattr, libc::PTHREAD_PROCESS_SHARED) != 0
+            || libc::pthread_cond_init(q.condvar(), &cattr) != 0
+        {
+            return Err("could not initialize the shared queue".into());
+        }
+        q.set(Q_MAXSIZE, maxsize);
+        q.set(Q_ELSIZE, elsize);
+        q.set(Q_CONSUMER_PID, std::process::id());
+        q.set(Q_PRODUCER_EVTFD, -1i32 as u32);
+        q.set(Q_CONSUMER_EVTFD, -1i32 as u32);
+        Ok(q)
+    }
+
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.base
+    }
+
+    fn mutex(&self) -> *mut libc::pthread_mutex_t {
+        unsafe { self.base.add(Q_MUTEX) as *mut libc::pthread_mutex_t }
+    }
+
+    fn condvar(&self) -> *mut libc::pthread_cond_t {
+        unsafe { self.base.add(Q_CONDVAR) as *mut libc::pthread_cond_t }
+    }
+
+    fn get(&self, ofs: usize) -> u32 {
+        unsafe { read_volatile(self.base.add(ofs) as *const u32) }
+    }
+
+    fn set(&self, ofs: usize, v: u32) {
+        unsafe { write_volatile(self.base.add(ofs) as *mut u32, v) }
+    }
+
+    pub fn maxsize(&self) -> u32 {
+        self.get(Q_MAXSIZE)
+    }
+
+    pub fn elsize(&self) -> u32 {
+        self.get(Q_ELSIZE)
+    }
+
+    pub fn cursize(&self) -> u32 {
+        self.get(Q_CURSIZE)
+    }
+
+    fn signal(&self) {
+        /* with the eventfds set up the other side polls them, they are not ours to write */
+        if self.get(Q_PRODUCER_EVTFD) as i32 == -1 {
+            unsafe { libc::pthread_cond_broadcast(self.condvar()) };
+        }
+    }
+
+    /* wait on the condvar with the mutex held; false if the deadline passed */
+    fn wait(&self, deadline: Option<Instant>) -> bool {
+        match deadline {
+            None => {
+                unsafe { libc::pthread_cond_wait(self.condvar(), self.mutex()) };
+                true
+            }
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return false;
+                }
+                let left = deadline - now;
+                let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+                unsafe { libc::clock_gettime(libc::CLOCK_REALTIME, &mut ts) };
+                let nsec = ts.tv_nsec as u64 + left.subsec_nanos() as u64;
+                ts.tv_sec += (left.as_secs() + nsec / 1_000_000_000) as libc::time_t;
+                ts.tv_nsec = (nsec % 1_000_000_000) as _;
+                unsafe { libc::pthread_cond_timedwait(self.condvar(), self.mutex(), &ts) };
+                true
+            }
+        }
+    }
+
+    fn slot(&self, index: u32) -> *mut u8 {
+        unsafe {
+            self.base
+                .add(Q_DATA + index as usize * self.elsize() as usize)
+        }
+    }
+
+    /// Add an element, waiting up to the timeout (None = forever) while the queue is full
+    pub fn add(&self, elem: &[u8], timeout: Option<Duration>) -> Result<()> {
+        let elsize = self.elsize() as usize;
+        if elem.len() != elsize {
+            return Err(format!("queue element is {} bytes, not {}", elsize, elem.len()).into());
+        }
+        let deadline = timeout.map(|t| Instant::now() + t);
+        unsafe { libc::pthread_mutex_lock(self.mutex()) };
+        while self.cursize() >= self.maxsize() {
+            if !self.wait(deadline) {
+                unsafe { libc::pthread_mutex_unlock(self.mutex()) };
+                return Err(std::io::Error::from(std::io::ErrorKind::TimedOut).into());
+            }
+        }
+        let tail = self.get(Q_TAIL);
+        unsafe { std::ptr::copy_nonoverlapping(elem.as_ptr(), self.slot(tail), elsize) };
+        self.set(
+            Q_TAIL,
+            if tail + 1 == self.maxsize() {
+                0
+            } else {
+                tail + 1
+            },
+        );
+        let cursize = self.cursize() + 1;
+        self.set(Q_CURSIZE, cursize);
+        if cursize == 1 {
+            self.signal();
+        }
+        unsafe { libc::pthread_mutex_unlock(self.mutex()) };
+        Ok(())
+    }
+
+    /// Take an element, waiting up to the timeout (None = forever); false if there was none
+    pub fn sub(&self, elem: &mut [u8], timeout: Option<Duration>) -> Result<bool> {
+        let elsize = self.elsize() as usize;
+        if elem.len() != elsize {
+            return Err(format!("queue element is {} bytes, not {}", elsize, elem.len()).into());
+        }
+        let deadline = timeout.map(|t| Instant::now() + t);
+        unsafe { libc::pthread_mutex_lock(self.mutex()) };
+        while self.cursize() == 0 {
+            if !self.wait(deadline) {
+                unsafe { libc::pthread_mutex_unlock(self.mutex()) };
+                return Ok(false);
+            }
+        }
+        let head = self.get(Q_HEAD);
+        unsafe { std::ptr::copy_nonoverlapping(self.slot(head), elem.as_mut_ptr(), elsize) };
+        self.set(
+            Q_HEAD,
+            if head + 1 == self.maxsize() {
+                0
+            } else {
+                head + 1
+            },
+        );
+        let was_full = self.cursize() == self.maxsize();
+        self.set(Q_CURSIZE, self.cursize() - 1);
+        if was_full {
+            self.signal();
+        }
+        unsafe { libc::pthread_mutex_unlock(self.mutex()) };
+        Ok(true)
+    }
+}
+
+/// Read the message out of a ring slot, and free the slot for the sender to reuse
+///
+/// # Safety
+/// data must point to the data of a msgbuf_t in mapped memory, with max_len bytes available
+pub(crate) unsafe fn take_msgbuf(data: *mut u8, max_len: usize) -> Result<Vec<u8>> {
+    let msgbuf = data.sub(MSGBUF_DATA);
+    let len = u32::from_be(read_volatile(msgbuf.add(MSGBUF_DATA_LEN) as *const u32)) as usize;
+    if len > max_len {
+        return Err(format!("shared memory message of {} bytes is out of bounds", len).into());
+    }
+    let msg = std::slice::from_raw_parts(data, len).to_vec();
+    if read_volatile(msgbuf.add(MSGBUF_Q) as *const u64) == 0 {
+        warn!("message from the VPP heap, can not free it");
+    }
+    write_volatile(msgbuf.add(MSGBUF_Q) as *mut u64, 0);
+    write_volatile(msgbuf.add(MSGBUF_GC_MARK) as *mut u32, 0);
+    Ok(msg)
+}
+
+/// Claim a free slot of the ring and copy the message there, returning the data address
+///
+/// # Safety
+/// ring must be an initialized ring queue whose slots are msgbuf_t
+pub(crate) unsafe fn put_msgbuf(ring: &Queue, msg: &[u8]) -> Option<*mut u8> {
+    let (maxsize, elsize) = (ring.maxsize(), ring.elsize() as usize);
+    if msg.len() + MSGBUF_DATA > elsize {
+        return None;
+    }
+    /* only we allocate from this ring, the other side just clears the q when done */
+    let head = ring.get(Q_HEAD);
+    for i in 0..maxsize {
+        let index = (head + i) % maxsize;
+        let msgbuf = ring.slot(index);
+        if read_volatile(msgbuf.add(MSGBUF_Q) as *const u64) != 0 {
+            continue;
+        }
+        ring.set(Q_HEAD, (index + 1) % maxsize);
+        let data = msgbuf.add(MSGBUF_DATA);
+        std::ptr::copy_nonoverlapping(msg.as_ptr(), data, msg.len());
+        write_volatile(
+            msgbuf.add(MSGBUF_DATA_LEN) as *mut u32,
+            (msg.len() as u32).to_be(),
+        );
+        write_volatile(msgbuf.add(MSGBUF_GC_MARK) as *mut u32, 0);
+        write_volatile(msgbuf.add(MSGBUF_Q) as *mut u64, ring.as_ptr() as u64);
+        return Some(data);
+    }
+    None
+}
+
+pub struct ShmChannel {
+    base: *mut u8,
+    len: usize,
+    vpp_queue: Queue,
+    input_queue: Queue,
+    /* the client rings we send from, smallest first */
+    rings: Vec<Queue>,
+    client_index: u32,
+    /* the registration, as VPP gave it in the memclnt_create_reply */
+    handle: u64,
+}
+
+/* the raw pointers are into our own mapping, which lives as long as the channel */
+unsafe impl Send for ShmChannel {}
+
+impl ShmChannel {
+    /// The client index of the shared-memory registration, as it goes into the messages
+    pub fn client_index(&self) -> u32 {
+        self.client_index
+    }
+
+    /// The memclnt_delete of this registration
+    pub fn memclnt_delete_msg(&self, msg_id: u16) -> Vec<u8> {
+        let mut msg = msg_id.to_be_bytes().to_vec();
+        /* index, handle: both as they came in the memclnt_create_reply */
+        msg.extend_from_slice(&self.client_index.to_be_bytes());
+        msg.extend_from_slice(&self.handle.to_ne_bytes());
+        /* do_cleanup */
+        msg.push(1);
+        msg
+    }
+
+    fn contains(&self, addr: u64, len: usize) -> bool {
+        let start = self.base as u64;
+        addr >= start && addr.saturating_add(len as u64) <= start + self.len as u64
+    }
+
+    fn ptr(&self, addr: u64, len: usize) -> Result<*mut u8> {
+        if self.contains(addr, len) {
+            Ok(addr as *mut u8)
+        } else {
+            Err(format!(
+                "pointer {:#x} is outside of the shared memory segment",
+                addr
+            )
+            .into())
+        }
+    }
+
+    /// Send one message (msg id, payload)
+    pub fn send(&mut self, msg: &[u8]) -> Result<()> {
+        let data = self
+            .rings
+            .iter()
+            .find_map(|ring| unsafe { put_msgbuf(ring, msg) })
+            .ok_or_else(|| {
+                Error::Error(format!(
+                    "no free client ring slot for a {} byte message",
+                    msg.len()
+                ))
+            })?;
+        self.vpp_queue
+            .add(&(data as u64).to_ne_bytes(), Some(SHM_SETUP_TIMEOUT))
+    }
+
+    /// Receive one message (msg id, payload), None if nothing came within the timeout
+    pub fn recv(&mut self, timeout: Option<Duration>) -> Result<Option<Vec<u8>>> {
+        let mut elem = [0u8; 8];
+        if !self.input_queue.sub(&mut elem, timeout)? {
+            return Ok(None);
+        }
+        let addr = u64::from_ne_bytes(elem);
+        let data = self.ptr(addr, 0)?;
+        let available = self.len - (addr - self.base as u64) as usize;
+        self.ptr(addr - MSGBUF_DATA as u64, MSGBUF_DATA)?;
+        Ok(Some(unsafe { take_msgbuf(data, available)? }))
+    }
+}
+
+impl Drop for ShmChannel {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.base as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+fn sock_init_shm_msg(
+    msg_id: u16,
+    client_index: u32,
+    context: u32,
+    requested_size: u32,
+    elems: &[ShmElemConfig],
+) -> Vec<u8> {
+    let mut msg = msg_id.to_be_bytes().to_vec();
+    msg.extend_from_slice(&client_index.to_be_bytes());
+    msg.extend_from_slice(&context.to_be_bytes());
+    msg.extend_from_slice(&requested_size.to_be_bytes());
+    msg.push(elems.len() as u8);
+    for e in elems.iter() {
+        msg.extend_from_slice(&e.to_u64().to_be_bytes());
+    }
+    msg
+}
+
+fn memclnt_create_msg(msg_id: u16, context: u32, input_queue: u64, name: &str) -> Vec<u8> {
+    let mut msg = msg_id.to_be_bytes().to_vec();
+    msg.extend_from_slice(&context.to_be_bytes());
+    /* ctx_quota */
+    msg.extend_from_slice(&0i32.to_be_bytes());
+    /* a pointer, VPP takes it as is */
+    msg.extend_from_slice(&input_queue.to_ne_bytes());
+    let mut name_buf = [0u8; 64];
+    let n = std::cmp::min(name.len(), 63);
+    name_buf[..n].copy_from_slice(&name.as_bytes()[..n]);
+    msg.extend_from_slice(&name_buf);
+    /* api_versions */
+    msg.extend_from_slice(&[0u8; 32]);
+    msg
+}
+
+/// Receive the file descriptor VPP sends along with the "fdmsg"
+pub(crate) fn recv_fd(sock: &UnixStream) -> Result<File> {
+    let mut data = [0u8; 5];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+    let cmsg_space = unsafe { libc::CMSG_SPACE(std::mem::size_of::<libc::c_int>() as u32) };
+    let mut cmsg_buf = vec![0u8; cmsg_space as usize];
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = cmsg_buf.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = cmsg_buf.len() as _;
+
+    if unsafe { libc::recvmsg(sock.as_raw_fd(), &mut msg, 0) } < 0 {
+        return last_os_error();
+    }
+    let cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+    if cmsg.is_null() {
+        return Err(format!("no file descriptor with the {:x?}", &data).into());
+    }
+    let (level, typ) = unsafe { ((*cmsg).cmsg_level, (*cmsg).cmsg_type) };
+    if level != libc::SOL_SOCKET || typ != libc::SCM_RIGHTS {
+        return Err(format!("unexpected control message: level {} type {}", level, typ).into());
+    }
+    let fd = unsafe { std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::c_int) };
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/* map the segment at the address VPP has it at */
+fn map_segment(file: &File) -> Result<(*mut u8, usize)> {
+    let page = page_size();
+    let hdr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            page,
+            libc::PROT_READ,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    if hdr == libc::MAP_FAILED {
+        return last_os_error();
+    }
+    let (va, size, ready) = unsafe {
+        let hdr = hdr as *const u8;
+        (
+            read_volatile(hdr.add(SSVM_VA) as *const u64),
+            read_volatile(hdr.add(SSVM_SIZE) as *const u64) as usize,
+            read_volatile(hdr.add(SSVM_READY) as *const u32),
+        )
+    };
+    unsafe { libc::munmap(hdr, page) };
+    debug!("shm segment at {:#x}, {} bytes, ready {}", va, size, ready);
+    if ready == 0 || size < 2 * page {
+        return Err(format!("shm segment is not ready (size {})", size).into());
+    }
+
+    let base = unsafe {
+        libc::mmap(
+            va as *mut libc::c_void,
+            size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED | libc::MAP_FIXED_NOREPLACE,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    if base == libc::MAP_FAILED {
+        return last_os_error();
+    }
+    if base as u64 != va {
+        /* old kernels take the flag as a hint */
+        unsafe { libc::munmap(base, size) };
+        return Err(format!("could not map the shm segment at {:#x}", va).into());
+    }
+    Ok((base as *mut u8, size))
+}
+
+/*
+ * Do the sock_init_shm exchange on a connected socket, map the segment
+ * and register over it with memclnt_create.
+ */
+pub(crate) fn upgrade(
+    sock: &mut UnixStream,
+    table: &HashMap<String, u16>,
+    client_index: u32,
+    name: &str,
+    config: &ShmConfig,
+) -> Result<ShmChannel> {
+    if std::mem::size_of::<libc::pthread_mutex_t>() != Q_CONDVAR - Q_MUTEX
+        || std::mem::size_of::<libc::pthread_cond_t>() != Q_HEAD - Q_CONDVAR
+    {
+        return Err("shared memory queues are not supported on this platform".into());
+    }
+    let id = |name: &str| -> Result<u16> {
+        table
+            .get(name)
+            .copied()
+            .ok_or_else(|| Error::Error(format!("VPP does not know {}", name)))
+    };
+    let (init_id, init_reply_id) = (id(SOCK_INIT_SHM)?, id(SOCK_INIT_SHM_REPLY)?);
+    let (create_id, create_reply_id) = (id(MEMCLNT_CREATE)?, id(MEMCLNT_CREATE_REPLY)?);
+
+    let elems = config.all_elems()?;
+    let context = 0x73686d;
+    let msg = sock_init_shm_msg(
+        init_id,
+        client_index,
+        context,
+        config.requested_size,
+        &elems,
+    );
+    write_sock_frame(sock, &msg)?;
+    sock.set_read_timeout(Some(SHM_SETUP_TIMEOUT))?;
+    let res = (|| -> Result<File> {
+        loop {
+            let data = read_sock_frame(sock)?;
+            let (msg_id, payload) = split_msg_id(&data)?;
+            if msg_id != init_reply_id {
+                debug!(
+                    "skipping message {} while waiting for sock_init_shm_reply",
+                    msg_id
+                );
+                continue;
+            }
+            /* context, retval */
+            if payload.len() < 8 {
+                return Err(Error::InvalidMessage);
+            }
+            let retval = i32::from_be_bytes(payload[4..8].try_into().unwrap());
+            if retval != 0 {
+                return Err(format!("sock_init_shm failed: retval {}", retval).into());
+            }
+            return recv_fd(sock);
+        }
+    })();
+    sock.set_read_timeout(None)?;
+    let memfd = res?;
+
+    let (base, len) = map_segment(&memfd)?;
+    drop(memfd);
+    let mut channel = ShmChannel {
+        base,
+        len,
+        vpp_queue: unsafe { Queue::from_raw(base) },
+        input_queue: unsafe { Queue::from_raw(base) },
+        rings: vec![],
+        client_index: 0,
+        handle: 0,
+    };
+
+    let read_ptr = |ch: &ShmChannel, addr: u64| -> Result<u64> {
+        let p = ch.ptr(addr, 8)?;
+        Ok(unsafe { read_volatile(p as *const u64) })
+    };
+    let region = base as u64 + page_size() as u64;
+    let hdr = read_ptr(&channel, region + REGION_USER_CTX as u64)?;
+    channel.ptr(hdr, HDR_LEN)?;
+    let vpp_queue = read_ptr(&channel, hdr + HDR_VL_INPUT_QUEUE as u64)?;
+    channel.vpp_queue = unsafe { Queue::from_raw(channel.ptr(vpp_queue, Q_DATA)?) };
+    let rings = read_ptr(&channel, hdr + HDR_CLIENT_RINGS as u64)?;
+    let n_rings =
+        unsafe { read_volatile(channel.ptr(rings - VEC_HEADER_LEN as u64, 4)? as *const u32) };
+    let mut client_rings = vec![];
+    for i in 0..n_rings as u64 {
+        let ring = channel.ptr(rings + i * RING_ALLOC_LEN as u64, RING_ALLOC_LEN)?;
+        let rp = unsafe { read_volatile(ring.add(RING_RP) as *const u64) };
+        let q = unsafe { Queue::from_raw(channel.ptr(rp, Q_DATA)?) };
+        channel.ptr(
+            rp + Q_DATA as u64,
+            q.maxsize() as usize * q.elsize() as usize,
+        )?;
+        client_rings.push(q);
+    }
+
+    /* our input queue ring is the last client ring asked for */
+    let input_ring = client_rings
+        .pop()
+        .ok_or_else(|| Error::Error("no client rings in the shm segment".into()))?;
+    let queue_mem = unsafe {
+        put_msgbuf(&input_ring, &[])
+            .ok_or_else(|| Error::Error("the input queue ring is not free".into()))?
+    };
+    if (input_ring.elsize() as usize) < MSGBUF_DATA + Q_DATA + config.input_queue_len as usize * 8 {
+        return Err("the input queue ring is too small".into());
+    }
+    channel.input_queue = unsafe { Queue::init(queue_mem, config.input_queue_len, 8)? };
+    client_rings.sort_by_key(|q| q.elsize());
+    channel.rings = client_rings;
+
+    channel.send(&memclnt_create_msg(
+        create_id,
+        context,
+        channel.input_queue.as_ptr() as u64,
+        name,
+    ))?;
+    let deadline = Instant::now() + SHM_SETUP_TIMEOUT;
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        let data = match channel.recv(Some(left))? {
+            Some(data) => data,
+            None => return Err(std::io::Error::from(std::io::ErrorKind::TimedOut).into()),
+        };
+        let (msg_id, payload) = split_msg_id(&data)?;
+        if msg_id != create_reply_id {
+            continue;
+        }
+        /* context, response, handle, index, message_table */
+        if payload.len() < 20 {
+            return Err(Error::InvalidMessage);
+        }
+        let response = i32::from_be_bytes(payload[4..8].try_into().unwrap());
+        if response != 0 {
+            return Err(format!("memclnt_create failed: {}", response).into());
+        }
+        /* VPP does not byte-swap the index, keep the bytes as they are */
+        channel.handle = u64::from_ne_bytes(payload[8..16].try_into().unwrap());
+        channel.client_index = u32::from_be_bytes(payload[16..20].try_into().unwrap());
+        debug!("shm client index {:#x}", channel.client_index);
+        return Ok(channel);
+    }
+}
@@ -0,0 +1,96 @@
+/*
+ * Helpers for running the VPP CLI over run_cli_inband the way vppctl
+ * does. The reply retval is zero even if the command failed, so the
+ * only way to tell is to look at the output: VPP prints the error
+ * instead of the command output, as the first line.
+ */
+pub mod parse;
+
+use crate::clisock::CliClient;
+use crate::error::Result;
+use crate::VppApiTransport;
+
+/// Runs a CLI command and returns its output, over the API or over cli.sock
+pub trait CliRunner {
+    fn run_cli(&mut self, cmd: &str) -> Result<String>;
+}
+
+impl<T: VppApiTransport + ?Sized> CliRunner for T {
+    fn run_cli(&mut self, cmd: &str) -> Result<String> {
+        self.run_cli_inband(cmd)
+    }
+}
+
+/* a trait object can not be turned into another one, so for the boxed transports */
+impl CliRunner for Box<dyn VppApiTransport> {
+    fn run_cli(&mut self, cmd: &str) -> Result<String> {
+        self.run_cli_inband(cmd)
+    }
+}
+
+impl CliRunner for CliClient {
+    fn run_cli(&mut self, cmd: &str) -> Result<String> {
+        CliClient::run_cli(self, cmd)
+    }
+}
+
+/// How the failed commands start, e.g. "unknown input `foo'"
+pub const CLI_ERROR_PREFIXES: &[&str] = &["unknown", "parse error", "error", "invalid", "failed"];
+
+/// Whether the CLI output is an error message rather than the command output
+pub fn cli_output_is_error(output: &str) -> bool {
+    let first = match output.lines().map(|l| l.trim()).find(|l| !l.is_empty()) {
+        Some(l) => l.to_lowercase(),
+        None => return false,
+    };
+    /* the command-specific errors often come as "<command>: <error>" */
+    let msg = match first.split_once(": ") {
+        Some((_, msg)) => msg,
+        None => &first[..],
+    };
+    CLI_ERROR_PREFIXES
+        .iter()
+        .any(|p| first.starts_with(p) || msg.starts_with(p))
+}
+
+/// The commands of a CLI script: one per line, skipping the blank lines and the # comments
+pub fn script_commands(script: &str) -> Vec<String> {
+    script
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| l.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_output_is_error() {
+        assert!(cli_output_is_error("unknown input `foo'\n"));
+        assert!(cli_output_is_error(
+            "\nset interface state: unknown interface `xx'\n"
+        ));
+        assert!(cli_output_is_error("show foo: unknown input `bar'"));
+        assert!(cli_output_is_error("Error: no such table\n"));
+        assert!(cli_output_is_error(
+            "set int ip address: failed to add 10.0.0.1/24\n"
+        ));
+        assert!(!cli_output_is_error(""));
+        assert!(!cli_output_is_error("vpp v21.01 built by root\n"));
+        assert!(!cli_output_is_error(
+            "   Count    Node    Reason    Severity\n     10   ip4-input  invalid header  error\n"
+        ));
+    }
+
+    #[test]
+    fn test_script_commands() {
+        let script = "# set up\nshow version\n\n  set int state local0 up  \n#done\n";
+        assert_eq!(
+            script_commands(script),
+            vec!["show version", "set int state local0 up"]
+        );
+    }
+}
@@ -0,0 +1,395 @@
+/*
+ * Parsers for the output of some common "show" commands, as returned
+ * by run_cli_inband or cli.sock, into typed structs.
+ *
+ * The formats are meant for humans and change between the releases,
+ * so the parsers are lenient: the lines they do not understand are
+ * skipped rather than failing the whole output. The error output
+ * of the command itself is an error though.
+ */
+use super::{cli_output_is_error, CliRunner};
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+fn check_output(output: &str) -> Result<()> {
+    if cli_output_is_error(output) {
+        return Err(format!("CLI error: {}", output.trim()).into());
+    }
+    Ok(())
+}
+
+/// A table row starts at the first column, its continuation lines are indented
+fn is_continuation(line: &str) -> bool {
+    line.starts_with(' ') || line.starts_with('\t')
+}
+
+/// The "show version" output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Version {
+    pub program: String,
+    pub version: String,
+    pub built_by: Option<String>,
+    pub build_host: Option<String>,
+    pub build_date: Option<String>,
+}
+
+/// "vpp v21.01-release built by root on 4fb3bf2f1d57 at 2021-01-27T16:51:11"
+pub fn parse_show_version(output: &str) -> Result<Version> {
+    check_output(output)?;
+    let line = output.lines().map(|l| l.trim()).find(|l| !l.is_empty());
+    let words: Vec<&str> = line.unwrap_or("").split_whitespace().collect();
+    if words.len() < 2 {
+        return Err(format!("unexpected show version output: {:?}", output).into());
+    }
+    let after = |what: &[&str]| {
+        words
+            .windows(what.len() + 1)
+            .find(|w| w[..what.len()] == *what)
+            .map(|w| w[what.len()].to_string())
+    };
+    Ok(Version {
+        program: words[0].to_string(),
+        version: words[1].to_string(),
+        built_by: after(&["built", "by"]),
+        build_host: after(&["on"]),
+        build_date: after(&["at"]),
+    })
+}
+
+/// A row of "show interface"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interface {
+    pub name: String,
+    pub sw_if_index: u32,
+    pub admin_up: bool,
+    /// L3/IP4/IP6/MPLS
+    pub mtu: Vec<u32>,
+    pub counters: BTreeMap<String, u64>,
+}
+
+/// "rx packets    10" => ("rx packets", 10)
+fn parse_counter(words: &[&str]) -> Option<(String, u64)> {
+    let (count, name) = words.split_last()?;
+    if name.is_empty() {
+        return None;
+    }
+    Some((name.join(" "), count.parse().ok()?))
+}
+
+pub fn parse_show_interface(output: &str) -> Result<Vec<Interface>> {
+    check_output(output)?;
+    let mut interfaces: Vec<Interface> = vec![];
+    for line in output.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        if is_continuation(line) {
+            if let (Some(intf), Some((name, count))) =
+                (interfaces.last_mut(), parse_counter(&words))
+            {
+                intf.counters.insert(name, count);
+            }
+            continue;
+        }
+        /* Name Idx State MTU [Counter Count] */
+        if words.len() < 4 {
+            continue;
+        }
+        let sw_if_index = match words[1].parse() {
+            Ok(i) => i,
+            Err(_) => continue,
+        };
+        let mut intf = Interface {
+            name: words[0].to_string(),
+            sw_if_index,
+            admin_up: words[2] == "up",
+            mtu: words[3].split('/').filter_map(|m| m.parse().ok()).collect(),
+            counters: BTreeMap::new(),
+        };
+        if let Some((name, count)) = parse_counter(&words[4..]) {
+            intf.counters.insert(name, count);
+        }
+        interfaces.push(intf);
+    }
+    Ok(interfaces)
+}
+
+/// A row of "show hardware-interfaces"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HardwareInterface {
+    pub name: String,
+    pub hw_if_index: u32,
+    pub link_up: bool,
+    pub hardware: String,
+    pub link_speed: Option<String>,
+    pub ethernet_address: Option<String>,
+    /// All the indented lines, trimmed
+    pub details: Vec<String>,
+}
+
+pub fn parse_show_hardware_interfaces(output: &str) -> Result<Vec<HardwareInterface>> {
+    check_output(output)?;
+    let mut interfaces: Vec<HardwareInterface> = vec![];
+    for line in output.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if is_continuation(line) {
+            if let Some(intf) = interfaces.last_mut() {
+                let detail = line.trim();
+                if let Some(speed) = detail.strip_prefix("Link speed:") {
+                    intf.link_speed = Some(speed.trim().to_string());
+                }
+                if let Some(mac) = detail.strip_prefix("Ethernet address ") {
+                    intf.ethernet_address = Some(mac.trim().to_string());
+                }
+                intf.details.push(detail.to_string());
+            }
+            continue;
+        }
+        /* Name Idx Link Hardware */
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.len() < 4 {
+            continue;
+        }
+        let hw_if_index = match words[1].parse() {
+            Ok(i) => i,
+            Err(_) => continue,
+        };
+        interfaces.push(HardwareInterface {
+            name: words[0].to_string(),
+            hw_if_index,
+            link_up: words[2] == "up",
+            hardware: words[3..].join(" "),
+            link_speed: None,
+            ethernet_address: None,
+            details: vec![],
+        });
+    }
+    Ok(interfaces)
+}
+
+/// A node row of "show runtime"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeRuntime {
+    pub name: String,
+    pub state: String,
+    pub calls: u64,
+    pub vectors: u64,
+    pub suspends: u64,
+    pub clocks: f64,
+    pub vectors_per_call: f64,
+}
+
+/// The "show runtime" section of one thread
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThreadRuntime {
+    pub index: u32,
+    pub name: String,
+    pub time: Option<f64>,
+    pub vector_rate: Option<f64>,
+    pub loops_per_sec: Option<f64>,
+    pub nodes: Vec<NodeRuntime>,
+}
+
+impl ThreadRuntime {
+    fn new(index: u32, name: &str) -> Self {
+        ThreadRuntime {
+            index,
+            name: name.to_owned(),
+            time: None,
+            vector_rate: None,
+            loops_per_sec: None,
+            nodes: vec![],
+        }
+    }
+}
+
+/// "Name State Calls Vectors Suspends Clocks Vectors/Call", where the state may be two words
+fn parse_node_runtime(words: &[&str]) -> Option<NodeRuntime> {
+    if words.len() < 7 {
+        return None;
+    }
+    let n = words.len();
+    Some(NodeRuntime {
+        name: words[0].to_string(),
+        state: words[1..n - 5].join(" "),
+        calls: words[n - 5].parse().ok()?,
+        vectors: words[n - 4].parse().ok()?,
+        suspends: words[n - 3].parse().ok()?,
+        clocks: words[n - 2].parse().ok()?,
+        vectors_per_call: words[n - 1].parse().ok()?,
+    })
+}
+
+/// "Time 39.8, 10 sec internal node vector rate 0.00 loops/sec 1131745.41"
+fn parse_runtime_time(thread: &mut ThreadRuntime, words: &[&str]) {
+    let after = |what: &str| {
+        words
+            .windows(2)
+            .find(|w| w[0] == what)
+            .and_then(|w| w[1].trim_end_matches(',').parse().ok())
+    };
+    thread.time = after("Time");
+    thread.vector_rate = after("rate");
+    thread.loops_per_sec = after("loops/sec");
+}
+
+/// One entry per thread; without the "Thread" headers (single thread) it is thread 0
+pub fn parse_show_runtime(output: &str) -> Result<Vec<ThreadRuntime>> {
+    check_output(output)?;
+    let mut threads: Vec<ThreadRuntime> = vec![];
+    for line in output.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.first() {
+            None => continue,
+            /* Thread 1 vpp_wk_0 (lcore 2) */
+            Some(&"Thread") if words.len() >= 3 => {
+                if let Ok(index) = words[1].parse() {
+                    threads.push(ThreadRuntime::new(index, words[2]));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        if threads.is_empty() {
+            threads.push(ThreadRuntime::new(0, ""));
+        }
+        let thread = threads.last_mut().unwrap();
+        match words[0] {
+            "Time" => parse_runtime_time(thread, &words),
+            "Name" | "vector" => {}
+            _ => {
+                if let Some(node) = parse_node_runtime(&words) {
+                    thread.nodes.push(node);
+                }
+            }
+        }
+    }
+    Ok(threads)
+}
+
+/// A row of "show errors"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorCounter {
+    pub count: u64,
+    pub node: String,
+    pub reason: String,
+    /// Only in the newer releases
+    pub severity: Option<String>,
+}
+
+pub fn parse_show_errors(output: &str) -> Result<Vec<ErrorCounter>> {
+    check_output(output)?;
+    let mut has_severity = false;
+    let mut counters = vec![];
+    for line in output.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.first() == Some(&"Count") {
+            has_severity = words.last() == Some(&"Severity");
+            continue;
+        }
+        if words.len() < 3 {
+            continue;
+        }
+        let count = match words[0].parse() {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+        let (reason, severity) = if has_severity && words.len() > 3 {
+            let (severity, reason) = words[2..].split_last().unwrap();
+            (reason.join(" "), Some(severity.to_string()))
+        } else {
+            (words[2..].join(" "), None)
+        };
+        counters.push(ErrorCounter {
+            count,
+            node: words[1].to_string(),
+            reason,
+            severity,
+        });
+    }
+    Ok(counters)
+}
+
+pub fn show_version(cli: &mut dyn CliRunner) -> Result<Version> {
+    parse_show_version(&cli.run_cli("show version")?)
+}
+
+pub fn show_interface(cli: &mut dyn CliRunner) -> Result<Vec<Interface>> {
+    parse_show_interface(&cli.run_cli("show interface")?)
+}
+
+pub fn show_hardware_interfaces(cli: &mut dyn CliRunner) -> Result<Vec<HardwareInterface>> {
+    parse_show_hardware_interfaces(&cli.run_cli("show hardware-interfaces")?)
+}
+
+pub fn show_runtime(cli: &mut dyn CliRunner) -> Result<Vec<ThreadRuntime>> {
+    parse_show_runtime(&cli.run_cli("show runtime")?)
+}
+
+pub fn show_errors(cli: &mut dyn CliRunner) -> Result<Vec<ErrorCounter>> {
+    parse_show_errors(&cli.run_cli("show errors")?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+    use std::path::PathBuf;
+
+    fn testdata(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata/cli")
+            .join(name)
+    }
+
+    /// Compare with the golden json next to the captured output, UPDATE_GOLDEN=1 rewrites it
+    fn check_golden<T, F>(name: &str, parse: F)
+    where
+        T: Serialize,
+        F: Fn(&str) -> Result<T>,
+    {
+        let output = std::fs::read_to_string(testdata(&format!("{}.txt", name))).unwrap();
+        let parsed = parse(&output).unwrap();
+        let json = serde_json::to_string_pretty(&parsed).unwrap() + "\n";
+        let golden = testdata(&format!("{}.json", name));
+        if std::env::var("UPDATE_GOLDEN").is_ok() {
+            std::fs::write(&golden, &json).unwrap();
+        }
+        assert_eq!(json, std::fs::read_to_string(&golden).unwrap(), "{}", name);
+    }
+
+    #[test]
+    fn test_golden() {
+        check_golden("show_version", parse_show_version);
+        check_golden("show_interface", parse_show_interface);
+        check_golden("show_hardware_interfaces", parse_show_hardware_interfaces);
+        check_golden("show_runtime", parse_show_runtime);
+        check_golden("show_errors", parse_show_errors);
+        check_golden("show_errors_severity", parse_show_errors);
+    }
+
+    #[test]
+    fn test_single_thread_runtime() {
+        let output = "Time 1.6, 10 sec internal node vector rate 0.00 loops/sec 100.50\n  \
+            vector rates in 0.0000e0, out 0.0000e0, drop 0.0000e0, punt 0.0000e0\n  \
+            Name   State   Calls   Vectors   Suspends   Clocks   Vectors/Call\n\
+            ip4-input   active   5   10   0   2.35e3   2.00\n";
+        let threads = parse_show_runtime(output).unwrap();
+        assert_eq!(threads.len(), 1);
+        assert_eq!(threads[0].index, 0);
+        assert_eq!(threads[0].loops_per_sec, Some(100.5));
+        assert_eq!(threads[0].nodes[0].vectors, 10);
+        assert_eq!(threads[0].nodes[0].vectors_per_call, 2.0);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse_show_errors("show errorz: unknown input `z'\n").is_err());
+        assert!(parse_show_version("").is_err());
+        assert!(parse_show_interface("").unwrap().is_empty());
+    }
+}
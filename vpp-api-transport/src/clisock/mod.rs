@@ -0,0 +1,221 @@
+/*
+ * A client for the telnet-like VPP CLI on cli.sock, for when the
+ * API socket is not available.
+ *
+ * The session is the one a human would get: a banner, a prompt,
+ * the command echoed back, the output, and a new prompt. So the
+ * output of a command is whatever comes between the echo and the
+ * next prompt. The prompt is taken from the end of the banner
+ * unless set explicitly. If the pager kicks in despite the huge
+ * window size we announce, we page through it.
+ */
+use crate::error::Result;
+use log::debug;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+pub(crate) mod telnet;
+pub use telnet::Telnet;
+
+pub const DEFAULT_CLI_SOCKET: &str = "/run/vpp/cli.sock";
+
+const TERMINAL_TYPE: &str = "dumb";
+const WINDOW_WIDTH: u16 = 1000;
+const WINDOW_HEIGHT: u16 = 65535;
+const PAGER_PROMPT: &str = "-- more --";
+
+/// A line as it is displayed: whatever follows the last carriage return
+fn displayed(line: &str) -> &str {
+    let line = line.trim_end_matches('\r');
+    match line.rfind('\r') {
+        Some(i) => &line[i + 1..],
+        None => line,
+    }
+}
+
+fn looks_like_prompt(line: &str) -> bool {
+    line.trim().len() > 1 && (line.ends_with("# ") || line.ends_with("> "))
+}
+
+pub struct CliClient {
+    sock_path: String,
+    prompt: Option<String>,
+    timeout: Duration,
+    sock: Option<UnixStream>,
+    telnet: Telnet,
+    pending: String,
+}
+
+impl CliClient {
+    pub fn new(sock_path: &str) -> Self {
+        CliClient {
+            sock_path: sock_path.to_owned(),
+            prompt: None,
+            timeout: Duration::from_secs(10),
+            sock: None,
+            telnet: Telnet::new(TERMINAL_TYPE, WINDOW_WIDTH, WINDOW_HEIGHT),
+            pending: String::new(),
+        }
+    }
+
+    /// Expect this prompt instead of taking it from the end of the banner
+    pub fn with_prompt(mut self, prompt: &str) -> Self {
+        self.prompt = Some(prompt.to_owned());
+        self
+    }
+
+    /// How long to wait for VPP to say something
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn prompt(&self) -> Option<&str> {
+        self.prompt.as_deref()
+    }
+
+    /// Connect and wait for the first prompt
+    pub fn connect(&mut self) -> Result<()> {
+        let sock = UnixStream::connect(&self.sock_path)?;
+        sock.set_read_timeout(Some(self.timeout))?;
+        self.sock = Some(sock);
+        self.telnet = Telnet::new(TERMINAL_TYPE, WINDOW_WIDTH, WINDOW_HEIGHT);
+        self.pending.clear();
+        let banner = self.read_until_prompt()?;
+        debug!("Connected to {}, banner: {:?}", &self.sock_path, banner);
+        Ok(())
+    }
+
+    pub fn disconnect(&mut self) {
+        if let Some(mut sock) = self.sock.take() {
+            let _ = sock.write_all(b"quit\n");
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.sock.is_some()
+    }
+
+    /// Run the command and return its output, the same as VppApiTransport::run_cli_inband
+    pub fn run_cli(&mut self, cmd: &str) -> Result<String> {
+        let cmd = cmd.trim();
+        self.write_all(format!("{}\n", cmd).as_bytes())?;
+        let out = self.read_until_prompt()?;
+        /* drop the echo of the command */
+        let out = match out.split_once('\n') {
+            Some((first, rest)) if first.trim() == cmd => rest.to_string(),
+            None if out.trim() == cmd => String::new(),
+            _ => out,
+        };
+        Ok(out)
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> Result<()> {
+        match self.sock.as_mut() {
+            Some(sock) => Ok(sock.write_all(data)?),
+            None => Err(format!("{} is not connected", &self.sock_path).into()),
+        }
+    }
+
+    fn read_until_prompt(&mut self) -> Result<String> {
+        let mut buf = [0u8; 4096];
+        loop {
+            if let Some(out) = self.take_until_prompt()? {
+                return Ok(out);
+            }
+            let n = match self.sock.as_mut() {
+                Some(sock) => sock.read(&mut buf)?,
+                None => return Err(format!("{} is not connected", &self.sock_path).into()),
+            };
+            if n == 0 {
+                self.sock = None;
+                return Err(format!("{} closed by VPP", &self.sock_path).into());
+            }
+            let text = self.telnet.feed(&buf[..n]);
+            let replies = self.telnet.take_replies();
+            if !replies.is_empty() {
+                self.write_all(&replies)?;
+            }
+            self.pending.push_str(&String::from_utf8_lossy(&text));
+        }
+    }
+
+    /// If the last line of what we have got is the prompt, the text before it
+    fn take_until_prompt(&mut self) -> Result<Option<String>> {
+        let last_start = self.pending.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let last = displayed(&self.pending[last_start..]).to_string();
+        if last.contains(PAGER_PROMPT) {
+            /* the pager erases its prompt itself once we ask for more */
+            self.pending.truncate(last_start);
+            self.write_all(b" ")?;
+            return Ok(None);
+        }
+        if self.prompt.is_none() && looks_like_prompt(&last) {
+            self.prompt = Some(last.clone());
+        }
+        if self.prompt.as_deref() != Some(&last[..]) {
+            return Ok(None);
+        }
+        let mut out = String::new();
+        for line in self.pending[..last_start].lines() {
+            out.push_str(displayed(line));
+            out.push('\n');
+        }
+        self.pending.clear();
+        Ok(Some(out))
+    }
+}
+
+impl Drop for CliClient {
+    fn drop(&mut self) {
+        self.disconnect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::telnet::*;
+    use super::*;
+    use crate::mock::MockCli;
+
+    #[test]
+    fn test_telnet() {
+        let mut t = Telnet::new("dumb", 1000, 65535);
+        let text = t.feed(&[
+            IAC, WILL, OPT_ECHO, b'a', IAC, DO, OPT_NAWS, 0x1b, b'[', b'1', b';', b'3', b'2', b'm',
+            b'b', IAC, IAC, IAC, DO, 42, IAC, SB, OPT_TTYPE, 1, IAC, SE, b'c',
+        ]);
+        assert_eq!(text, vec![b'a', b'b', IAC, b'c']);
+        let mut expected = vec![IAC, DO, OPT_ECHO, IAC, WILL, OPT_NAWS];
+        expected.extend_from_slice(&[IAC, SB, OPT_NAWS, 0x03, 0xe8, IAC, IAC, IAC, IAC, IAC, SE]);
+        expected.extend_from_slice(&[IAC, WONT, 42]);
+        expected.extend_from_slice(&[IAC, SB, OPT_TTYPE, 0, b'd', b'u', b'm', b'b', IAC, SE]);
+        assert_eq!(t.take_replies(), expected);
+        assert!(t.take_replies().is_empty());
+    }
+
+    #[test]
+    fn test_cli_client() {
+        let mock = MockCli::start();
+        let mut c = CliClient::new(mock.path()).with_timeout(Duration::from_secs(5));
+        c.connect().unwrap();
+        assert_eq!(c.prompt(), Some("vpp# "));
+
+        let out = c.run_cli("show version").unwrap();
+        assert_eq!(out, "vpp v0.0-mock built by nobody\n");
+        let out = c.run_cli("show lines").unwrap();
+        assert_eq!(out, "line 1\nline 2\nline 3\nline 4\nline 5\n");
+        let out = c.run_cli("show foo").unwrap();
+        assert_eq!(out, "unknown input `show foo'\n");
+        assert_eq!(c.run_cli("").unwrap(), "");
+
+        let negotiation = mock.negotiation();
+        assert!(negotiation.contains(&vec![IAC, WILL, OPT_TTYPE]));
+        assert!(negotiation.contains(&vec![IAC, DO, OPT_ECHO]));
+        assert!(negotiation.contains(&b"\xff\xfa\x18\x00dumb\xff\xf0".to_vec()));
+        c.disconnect();
+        assert!(!c.is_connected());
+        assert!(c.run_cli("show version").is_err());
+    }
+}
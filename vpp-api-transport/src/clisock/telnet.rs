@@ -0,0 +1,152 @@
+/*
+ * The telnet side of cli.sock, without any IO: separates the text
+ * from the option negotiation, answers the negotiation, and drops
+ * the ANSI escape sequences VPP sprinkles over the output.
+ *
+ * We agree to TTYPE (as a non-ANSI terminal) and NAWS (as a huge
+ * window, so that the pager rarely kicks in), let VPP do the ECHO
+ * and SGA, and refuse everything else.
+ */
+
+pub const IAC: u8 = 255;
+pub const DONT: u8 = 254;
+pub const DO: u8 = 253;
+pub const WONT: u8 = 252;
+pub const WILL: u8 = 251;
+pub const SB: u8 = 250;
+pub const SE: u8 = 240;
+
+pub const OPT_ECHO: u8 = 1;
+pub const OPT_SGA: u8 = 3;
+pub const OPT_TTYPE: u8 = 24;
+pub const OPT_NAWS: u8 = 31;
+
+const TTYPE_IS: u8 = 0;
+const TTYPE_SEND: u8 = 1;
+
+const ESC: u8 = 0x1b;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Data,
+    Iac,
+    Option(u8),
+    Sb,
+    SbIac,
+    Esc,
+    Csi,
+}
+
+#[derive(Debug)]
+pub struct Telnet {
+    state: State,
+    sb: Vec<u8>,
+    replies: Vec<u8>,
+    terminal_type: String,
+    window: (u16, u16),
+}
+
+impl Telnet {
+    pub fn new(terminal_type: &str, width: u16, height: u16) -> Self {
+        Telnet {
+            state: State::Data,
+            sb: vec![],
+            replies: vec![],
+            terminal_type: terminal_type.to_owned(),
+            window: (width, height),
+        }
+    }
+
+    /// Process the received bytes, return the text in them
+    pub fn feed(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut text = vec![];
+        for &b in data {
+            self.state = match (self.state, b) {
+                (State::Data, IAC) => State::Iac,
+                (State::Data, ESC) => State::Esc,
+                (State::Data, 0) => State::Data,
+                (State::Data, b) => {
+                    text.push(b);
+                    State::Data
+                }
+                (State::Iac, IAC) => {
+                    text.push(IAC);
+                    State::Data
+                }
+                (State::Iac, DO) | (State::Iac, DONT) | (State::Iac, WILL) | (State::Iac, WONT) => {
+                    State::Option(b)
+                }
+                (State::Iac, SB) => {
+                    self.sb.clear();
+                    State::Sb
+                }
+                (State::Iac, _) => State::Data,
+                (State::Option(cmd), opt) => {
+                    self.negotiate(cmd, opt);
+                    State::Data
+                }
+                (State::Sb, IAC) => State::SbIac,
+                (State::Sb, b) => {
+                    self.sb.push(b);
+                    State::Sb
+                }
+                (State::SbIac, SE) => {
+                    self.subnegotiate();
+                    State::Data
+                }
+                (State::SbIac, b) => {
+                    self.sb.push(b);
+                    State::Sb
+                }
+                (State::Esc, b'[') => State::Csi,
+                (State::Esc, _) => State::Data,
+                (State::Csi, 0x40..=0x7e) => State::Data,
+                (State::Csi, _) => State::Csi,
+            };
+        }
+        text
+    }
+
+    /// The negotiation replies to send, the buffer is empty afterwards
+    pub fn take_replies(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.replies)
+    }
+
+    fn negotiate(&mut self, cmd: u8, opt: u8) {
+        let reply = match (cmd, opt) {
+            (DO, OPT_TTYPE) | (DO, OPT_NAWS) => WILL,
+            (DO, _) => WONT,
+            (WILL, OPT_ECHO) | (WILL, OPT_SGA) => DO,
+            (WILL, _) => DONT,
+            /* nothing to agree with for DONT/WONT */
+            _ => return,
+        };
+        self.replies.extend_from_slice(&[IAC, reply, opt]);
+        if (cmd, opt) == (DO, OPT_NAWS) {
+            let (w, h) = self.window;
+            let mut sb = vec![OPT_NAWS];
+            sb.extend_from_slice(&w.to_be_bytes());
+            sb.extend_from_slice(&h.to_be_bytes());
+            self.send_sb(&sb);
+        }
+    }
+
+    fn subnegotiate(&mut self) {
+        if self.sb.get(0..2) == Some(&[OPT_TTYPE, TTYPE_SEND][..]) {
+            let mut sb = vec![OPT_TTYPE, TTYPE_IS];
+            sb.extend_from_slice(self.terminal_type.as_bytes());
+            self.send_sb(&sb);
+        }
+    }
+
+    fn send_sb(&mut self, data: &[u8]) {
+        self.replies.extend_from_slice(&[IAC, SB]);
+        for &b in data {
+            if b == IAC {
+                self.replies.push(IAC);
+            }
+            self.replies.push(b);
+        }
+        self.replies.extend_from_slice(&[IAC, SE]);
+    }
+}
@@ -0,0 +1,395 @@
+/*
+ * A transport which never talks to VPP: the messages written to it are
+ * recorded as the would-be frames, and answered with synthetic replies,
+ * so that the code generating the config can run in a "plan" mode.
+ *
+ * The message ids come from the given message table (e.g. one saved
+ * from a real connection), or are assigned on first use. A request
+ * "foo" is answered with "foo_reply" (if that name has been resolved,
+ * which the reqrecv helpers and the generated code do before sending)
+ * with the request context, a zero retval (or the configured one)
+ * and zeroes for the rest of the fields; the dumps get no details.
+ * Read gives the replies with the SockMsgHeader in front, the same as
+ * the socket would.
+ */
+use crate::error::{Error, Result};
+use crate::protocol::{encode_frame, split_msg_id, CONTROL_PING_REPLY};
+use crate::proxy::{pattern_matches, strip_crc};
+use crate::VppApiTransport;
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as FmtWrite;
+
+/* ids 0..15 are not used, sockclnt_create is at 15 */
+const FIRST_AUTO_MSG_ID: u16 = 16;
+const DEFAULT_REPLY_PADDING: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub msg_id: u16,
+    pub name: Option<String>,
+    /// The message as it would go on the wire inside SockMsgHeader: msg id, payload
+    pub data: Vec<u8>,
+}
+
+impl Frame {
+    pub fn payload(&self) -> &[u8] {
+        &self.data[2..]
+    }
+
+    pub fn hex(&self) -> String {
+        let mut out = String::new();
+        for b in &self.data {
+            let _ = write!(out, "{:02x}", b);
+        }
+        out
+    }
+
+    /// Decode the payload back into the message struct, e.g. to show it with {:#?}
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(vpp_api_encoding::wire::from_bytes::<T>(self.payload())?)
+    }
+}
+
+pub struct Transport {
+    connected: bool,
+    auto_assign: bool,
+    name_to_id: HashMap<String, u16>,
+    retvals: Vec<(String, i32)>,
+    reply_padding: usize,
+    frames: Vec<Frame>,
+    rx: VecDeque<Vec<u8>>,
+    /* the framed reply being read byte-wise */
+    rx_buf: Vec<u8>,
+    next_context: u32,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport {
+    /// Assign the message ids on first use
+    pub fn new() -> Self {
+        Transport {
+            connected: false,
+            auto_assign: true,
+            name_to_id: HashMap::new(),
+            retvals: vec![],
+            reply_padding: DEFAULT_REPLY_PADDING,
+            frames: vec![],
+            rx: VecDeque::new(),
+            rx_buf: vec![],
+            next_context: 1,
+        }
+    }
+
+    /// Use the ids from this table, and reject the names which are not in it
+    pub fn with_message_table(table: HashMap<String, u16>) -> Self {
+        Transport {
+            auto_assign: false,
+            name_to_id: table,
+            ..Self::new()
+        }
+    }
+
+    /// Answer the requests matching the pattern (without CRC, '*' globs) with this retval
+    pub fn with_retval(mut self, pattern: &str, retval: i32) -> Self {
+        self.retvals.push((pattern.to_string(), retval));
+        self
+    }
+
+    /// How many zero bytes follow the retval in the synthetic replies
+    pub fn with_reply_padding(mut self, padding: usize) -> Self {
+        self.reply_padding = padding;
+        self
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn take_frames(&mut self) -> Vec<Frame> {
+        std::mem::take(&mut self.frames)
+    }
+
+    /// One line per frame: name, id and the bytes in hex
+    pub fn render_plan(&self) -> String {
+        let mut out = String::new();
+        for f in &self.frames {
+            let _ = writeln!(
+                out,
+                "{} (id {}): {}",
+                f.name.as_deref().unwrap_or("?"),
+                f.msg_id,
+                f.hex()
+            );
+        }
+        out
+    }
+
+    fn name_of(&self, msg_id: u16) -> Option<&str> {
+        self.name_to_id
+            .iter()
+            .find(|(_, id)| **id == msg_id)
+            .map(|(n, _)| n.as_str())
+    }
+
+    fn id_of_base(&self, base: &str) -> Option<u16> {
+        self.name_to_id
+            .iter()
+            .find(|(n, _)| strip_crc(n) == base)
+            .map(|(_, id)| *id)
+    }
+
+    fn retval_for(&self, base: &str) -> i32 {
+        self.retvals
+            .iter()
+            .find(|(p, _)| pattern_matches(p, base))
+            .map(|(_, r)| *r)
+            .unwrap_or(0)
+    }
+
+    fn synthesize_reply(&mut self, frame: &Frame) {
+        let base = match &frame.name {
+            Some(n) => strip_crc(n).to_string(),
+            None => return,
+        };
+        if base.ends_with("_dump") {
+            return;
+        }
+        /* _vl_msg_id, client_index, context */
+        let payload = frame.payload();
+        if payload.len() < 8 {
+            return;
+        }
+        let reply_id = if base == "control_ping" {
+            self.name_to_id.get(CONTROL_PING_REPLY).copied()
+        } else {
+            self.id_of_base(&format!("{}_reply", base))
+        };
+        if let Some(reply_id) = reply_id {
+            let mut reply = reply_id.to_be_bytes().to_vec();
+            reply.extend_from_slice(&payload[4..8]);
+            reply.extend_from_slice(&self.retval_for(&base).to_be_bytes());
+            if base == "cli_inband" {
+                /* the reply is parsed strictly: an empty output and nothing after it */
+                reply.extend_from_slice(&0u32.to_be_bytes());
+            } else {
+                reply.resize(reply.len() + self.reply_padding, 0);
+            }
+            self.rx.push_back(reply);
+        }
+    }
+}
+
+impl std::io::Read for Transport {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.rx_buf.is_empty() {
+            match self.rx.pop_front() {
+                Some(msg) => self.rx_buf = encode_frame(&msg),
+                None => return Err(std::io::ErrorKind::WouldBlock.into()),
+            }
+        }
+        let n = std::cmp::min(buf.len(), self.rx_buf.len());
+        buf[..n].copy_from_slice(&self.rx_buf[..n]);
+        self.rx_buf.drain(..n);
+        Ok(n)
+    }
+}
+
+impl std::io::Write for Transport {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let (msg_id, _) = split_msg_id(buf).map_err(|e| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{:?}", e))
+        })?;
+        let frame = Frame {
+            msg_id,
+            name: self.name_of(msg_id).map(|n| n.to_string()),
+            data: buf.to_vec(),
+        };
+        self.synthesize_reply(&frame);
+        self.frames.push(frame);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl VppApiTransport for Transport {
+    fn connect(&mut self, _name: &str, _chroot_prefix: Option<&str>, _rx_qlen: i32) -> Result<()> {
+        self.connected = true;
+        Ok(())
+    }
+
+    fn disconnect(&mut self) {
+        self.connected = false;
+        self.rx.clear();
+        self.rx_buf.clear();
+    }
+
+    fn set_nonblocking(&mut self, _nonblocking: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_msg_index(&mut self, name: &str) -> Option<u16> {
+        if let Some(id) = self.name_to_id.get(name) {
+            return Some(*id);
+        }
+        if !self.auto_assign {
+            return None;
+        }
+        let id = self
+            .name_to_id
+            .values()
+            .max()
+            .map(|m| m + 1)
+            .unwrap_or(FIRST_AUTO_MSG_ID)
+            .max(FIRST_AUTO_MSG_ID);
+        self.name_to_id.insert(name.to_string(), id);
+        Some(id)
+    }
+
+    fn get_table_max_index(&mut self) -> u16 {
+        self.name_to_id.values().max().copied().unwrap_or(0)
+    }
+
+    fn get_client_index(&self) -> u32 {
+        0
+    }
+
+    fn get_next_context(&mut self) -> u32 {
+        let context = self.next_context;
+        self.next_context += 1;
+        context
+    }
+
+    fn dump(&self) {
+        print!("{}", self.render_plan());
+    }
+
+    fn read_one_msg_into(&mut self, data: &mut Vec<u8>) -> Result<()> {
+        if !self.rx_buf.is_empty() {
+            self.rx_buf.clear();
+            return Err(Error::Error(
+                "dry run: the rest of a reply partly read with read() is dropped".into(),
+            ));
+        }
+        match self.rx.pop_front() {
+            Some(msg) => {
+                *data = msg;
+                Ok(())
+            }
+            None => Err(Error::Error(
+                "dry run: no reply to read for the messages sent".into(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{AddDelAddress, AddDelAddressReply, SwInterfaceDetails, SwInterfaceDump};
+    use crate::reqrecv::{send_recv_many, send_recv_one};
+    use std::io::{Read, Write};
+
+    #[test]
+    fn test_dry_run_plan() {
+        let mut t = Transport::new();
+        let req = AddDelAddress {
+            client_index: 0,
+            context: 5,
+            sw_if_index: 1,
+            is_add: true,
+        };
+        let reply: AddDelAddressReply = send_recv_one(&req, &mut t).unwrap();
+        assert_eq!((reply.context, reply.retval), (5, 0));
+
+        let dump = SwInterfaceDump {
+            client_index: 0,
+            context: 6,
+        };
+        let details: Vec<SwInterfaceDetails> = send_recv_many(&dump, &mut t).unwrap();
+        assert!(details.is_empty());
+        assert_eq!(t.run_cli_inband("show version").unwrap(), "");
+
+        let frames = t.frames();
+        let names: Vec<_> = frames
+            .iter()
+            .map(|f| strip_crc(f.name.as_deref().unwrap()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "sw_interface_add_del_address",
+                "sw_interface_dump",
+                "control_ping",
+                "cli_inband"
+            ]
+        );
+        assert_eq!(
+            frames[0].msg_id,
+            t.name_to_id["sw_interface_add_del_address_5803d5c4"]
+        );
+        assert_eq!(&frames[0].hex()[4..], "00000000000000050000000101");
+        assert_eq!(frames[0].decode::<AddDelAddress>().unwrap(), req);
+        assert!(t
+            .render_plan()
+            .starts_with("sw_interface_add_del_address_5803d5c4 (id 16): 0010"));
+    }
+
+    #[test]
+    fn test_dry_run_table_and_retval() {
+        let mut table = HashMap::new();
+        table.insert("sw_interface_add_del_address_5803d5c4".to_string(), 100);
+        table.insert(
+            "sw_interface_add_del_address_reply_e8d4e804".to_string(),
+            101,
+        );
+        let mut t = Transport::with_message_table(table).with_retval("*_add_del_*", -7);
+        assert_eq!(t.get_msg_index("control_ping_51077d14"), None);
+
+        let req = AddDelAddress {
+            client_index: 0,
+            context: 9,
+            sw_if_index: 2,
+            is_add: false,
+        };
+        let reply: AddDelAddressReply = send_recv_one(&req, &mut t).unwrap();
+        assert_eq!((reply.context, reply.retval), (9, -7));
+        assert_eq!(t.frames()[0].msg_id, 100);
+        assert_eq!(t.take_frames().len(), 1);
+        assert!(t.frames().is_empty());
+        assert!(t.read_one_msg().is_err());
+    }
+
+    #[test]
+    fn test_dry_run_read_is_framed() {
+        let mut t = Transport::new();
+        let ping = t.get_msg_index("control_ping_51077d14").unwrap();
+        let ping_reply = t.get_msg_index("control_ping_reply_f6b0b8ca").unwrap();
+        t.write_all(&crate::protocol::control_ping_msg(ping, 0, 3))
+            .unwrap();
+        t.write_all(&crate::protocol::control_ping_msg(ping, 0, 4))
+            .unwrap();
+
+        let mut header = [0u8; crate::protocol::SOCK_MSG_HEADER_LEN];
+        t.read_exact(&mut header).unwrap();
+        let msglen = crate::protocol::decode_frame_header(&header).unwrap();
+        assert_eq!(msglen, 10 + DEFAULT_REPLY_PADDING);
+        let mut first = vec![0u8; 6];
+        t.read_exact(&mut first).unwrap();
+        assert_eq!(&first[0..2], &ping_reply.to_be_bytes());
+        assert_eq!(&first[2..6], &3u32.to_be_bytes());
+
+        /* the rest of the first reply is gone, then the second one */
+        assert!(t.read_one_msg().is_err());
+        let (msg_id, data) = t.read_one_msg_id_and_msg().unwrap();
+        assert_eq!(msg_id, ping_reply);
+        assert_eq!(&data[0..4], &4u32.to_be_bytes());
+    }
+}
@@ -6,15 +6,23 @@ pub enum Error {
     Error(String),
     InvalidHeader,
     InvalidMessage,
+    /// The peer closed the connection (EOF on the socket)
+    ServerClosed,
     IoError(std::io::Error),
     BinCodeError(Box<bincode::ErrorKind>),
+    WireError(vpp_api_encoding::wire::Error),
+    #[cfg(feature = "tls")]
+    TlsError(rustls::Error),
 }
 
 impl error::Error for Error {}
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Error: {:?}", self)
+        match self {
+            Error::ServerClosed => write!(f, "Error: server closed connection"),
+            _ => write!(f, "Error: {:?}", self),
+        }
     }
 }
 impl From<Box<bincode::ErrorKind>> for Error {
@@ -22,6 +30,17 @@ impl From<Box<bincode::ErrorKind>> for Error {
         Self::BinCodeError(e)
     }
 }
+impl From<vpp_api_encoding::wire::Error> for Error {
+    fn from(e: vpp_api_encoding::wire::Error) -> Self {
+        Self::WireError(e)
+    }
+}
+#[cfg(feature = "tls")]
+impl From<rustls::Error> for Error {
+    fn from(e: rustls::Error) -> Self {
+        Self::TlsError(e)
+    }
+}
 impl From<&str> for Error {
     fn from(s: &str) -> Self {
         Self::Error(s.to_string())
@@ -0,0 +1,231 @@
+/*
+ * The interface table kept current over the API socket: one
+ * sw_interface_dump for the names and the flags, and then the
+ * sw_interface_event notifications for the admin/link changes.
+ * An event for an interface we do not know yet triggers another
+ * dump, since the event does not carry the name.
+ */
+use super::ApiState;
+use crate::error::{Error, Result};
+use crate::get_encoder;
+use crate::protocol::big_array::BigArray;
+use crate::protocol::{blocking_handshake, Event, Protocol, RequestKind};
+use bincode::Options;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex};
+
+pub const SW_INTERFACE_DUMP: &str = "sw_interface_dump_aa610c27";
+pub const SW_INTERFACE_DETAILS: &str = "sw_interface_details_17b69fa2";
+pub const WANT_INTERFACE_EVENTS: &str = "want_interface_events_476f5a08";
+pub const WANT_INTERFACE_EVENTS_REPLY: &str = "want_interface_events_reply_e8d4e804";
+pub const SW_INTERFACE_EVENT: &str = "sw_interface_event_f709f78d";
+
+const IF_STATUS_API_FLAG_ADMIN_UP: u32 = 1;
+const IF_STATUS_API_FLAG_LINK_UP: u32 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceInfo {
+    pub sw_if_index: u32,
+    pub name: String,
+    pub admin_up: bool,
+    pub link_up: bool,
+}
+
+impl InterfaceInfo {
+    fn set_flags(&mut self, flags: u32) {
+        self.admin_up = flags & IF_STATUS_API_FLAG_ADMIN_UP != 0;
+        self.link_up = flags & IF_STATUS_API_FLAG_LINK_UP != 0;
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RawSwInterfaceDump {
+    sw_if_index: u32,
+    name_filter_valid: bool,
+    name_filter_len: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RawWantInterfaceEvents {
+    enable_disable: u32,
+    pid: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RawWantInterfaceEventsReply {
+    context: u32,
+    retval: i32,
+}
+
+/* Only the fields up to the name, the rest are of no interest here */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RawSwInterfaceDetails {
+    context: u32,
+    sw_if_index: u32,
+    sup_sw_if_index: u32,
+    l2_address: [u8; 6],
+    flags: u32,
+    typ: u32,
+    link_duplex: u32,
+    link_speed: u32,
+    link_mtu: u16,
+    mtu: [u32; 4],
+    sub_id: u32,
+    sub_number_of_tags: u8,
+    sub_outer_vlan_id: u16,
+    sub_inner_vlan_id: u16,
+    sub_if_flags: u32,
+    vtr_op: u32,
+    vtr_push_dot1q: u32,
+    vtr_tag1: u32,
+    vtr_tag2: u32,
+    outer_tag: u16,
+    b_dmac: [u8; 6],
+    b_smac: [u8; 6],
+    b_vlanid: u16,
+    i_sid: u32,
+    #[serde(with = "BigArray")]
+    interface_name: [u8; 64],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RawSwInterfaceEvent {
+    client_index: u32,
+    pid: u32,
+    sw_if_index: u32,
+    flags: u32,
+    deleted: bool,
+}
+
+/// The request body (past the context) of a sw_interface_dump for all the interfaces
+pub fn sw_interface_dump_body() -> Vec<u8> {
+    let msg = RawSwInterfaceDump {
+        sw_if_index: !0,
+        name_filter_valid: false,
+        name_filter_len: 0,
+    };
+    get_encoder().serialize(&msg).unwrap()
+}
+
+pub fn want_interface_events_body(enable: bool) -> Vec<u8> {
+    let msg = RawWantInterfaceEvents {
+        enable_disable: enable as u32,
+        pid: std::process::id(),
+    };
+    get_encoder().serialize(&msg).unwrap()
+}
+
+/// Decode a sw_interface_details (without the message id)
+pub fn parse_sw_interface_details(data: &[u8]) -> Result<InterfaceInfo> {
+    let d: RawSwInterfaceDetails = get_encoder().allow_trailing_bytes().deserialize(data)?;
+    let name_len = d.interface_name.iter().position(|&b| b == 0).unwrap_or(64);
+    let mut info = InterfaceInfo {
+        sw_if_index: d.sw_if_index,
+        name: String::from_utf8_lossy(&d.interface_name[..name_len]).to_string(),
+        admin_up: false,
+        link_up: false,
+    };
+    info.set_flags(d.flags);
+    Ok(info)
+}
+
+struct MsgIds {
+    details: u16,
+    events_reply: u16,
+    event: u16,
+}
+
+fn msg_id(p: &Protocol, name: &str) -> Result<u16> {
+    p.get_msg_index(name)
+        .ok_or_else(|| Error::Error(format!("{} is not in the table", name)))
+}
+
+fn start_dump(p: &mut Protocol) -> Result<u32> {
+    let dump_id = msg_id(p, SW_INTERFACE_DUMP)?;
+    p.send_request(dump_id, &sw_interface_dump_body(), RequestKind::Dump)
+}
+
+/// Connect to the API socket and keep the interface table in the state current until the socket fails
+pub fn watch_interfaces(api_path: &str, state: Arc<Mutex<ApiState>>) -> Result<()> {
+    let mut sock = UnixStream::connect(api_path)?;
+    let mut p = blocking_handshake(&mut sock, "vpp-api-exporter")?;
+    let ids = MsgIds {
+        details: msg_id(&p, SW_INTERFACE_DETAILS)?,
+        events_reply: msg_id(&p, WANT_INTERFACE_EVENTS_REPLY)?,
+        event: msg_id(&p, SW_INTERFACE_EVENT)?,
+    };
+    let want_id = msg_id(&p, WANT_INTERFACE_EVENTS)?;
+    p.send_request(
+        want_id,
+        &want_interface_events_body(true),
+        RequestKind::Single,
+    )?;
+    let mut dump_context = Some(start_dump(&mut p)?);
+    let mut redump = false;
+    let mut dumped: HashMap<u32, InterfaceInfo> = HashMap::new();
+    sock.write_all(&p.take_outgoing())?;
+    info!("Watching the interfaces on {}", api_path);
+    state.lock().unwrap().connected = true;
+
+    let mut buf = vec![0u8; 65536];
+    loop {
+        let n = sock.read(&mut buf)?;
+        if n == 0 {
+            return Err(format!("{} closed by VPP", api_path).into());
+        }
+        p.receive(&buf[..n])?;
+        while let Some(event) = p.poll_event() {
+            match event {
+                Event::Details { msg_id, data, .. } if msg_id == ids.details => {
+                    let info = parse_sw_interface_details(&data)?;
+                    debug!("Interface {:?}", &info);
+                    dumped.insert(info.sw_if_index, info);
+                }
+                Event::DumpDone { context } if Some(context) == dump_context => {
+                    state.lock().unwrap().interfaces = std::mem::take(&mut dumped);
+                    dump_context = None;
+                    if redump {
+                        redump = false;
+                        dump_context = Some(start_dump(&mut p)?);
+                    }
+                }
+                Event::Reply { msg_id, data, .. } if msg_id == ids.events_reply => {
+                    let r: RawWantInterfaceEventsReply = get_encoder().deserialize(&data)?;
+                    if r.retval != 0 {
+                        warn!("want_interface_events retval {}", r.retval);
+                    }
+                }
+                /* events carry the client_index where the replies have the context */
+                Event::Message { msg_id, data } | Event::Details { msg_id, data, .. }
+                    if msg_id == ids.event =>
+                {
+                    let ev: RawSwInterfaceEvent = get_encoder().deserialize(&data)?;
+                    debug!("Interface event {:?}", &ev);
+                    let mut state = state.lock().unwrap();
+                    /* during a dump, the event may be older or newer than the details */
+                    let interfaces = match dump_context {
+                        Some(_) => &mut dumped,
+                        None => &mut state.interfaces,
+                    };
+                    if ev.deleted {
+                        interfaces.remove(&ev.sw_if_index);
+                    } else if let Some(info) = interfaces.get_mut(&ev.sw_if_index) {
+                        info.set_flags(ev.flags);
+                    } else if dump_context.is_some() {
+                        redump = true;
+                    } else {
+                        dump_context = Some(start_dump(&mut p)?);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if p.wants_write() {
+            sock.write_all(&p.take_outgoing())?;
+        }
+    }
+}
@@ -0,0 +1,191 @@
+/*
+ * Rendering of the stats segment and the interface table in the
+ * Prometheus text exposition format.
+ *
+ * /if/<name> counters become vpp_if_<name>_total (combined ones get
+ * the _packets/_bytes suffixes) labelled with the interface name and
+ * sw_if_index, /err/<node>/<counter> become vpp_errors_total with the
+ * node and counter labels, and the scalars become gauges.
+ */
+use super::ApiState;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use vpp_api_stats::{StatEntry, StatValue};
+
+/// "/if/rx-miss" => "vpp_if_rx_miss"
+pub fn metric_name(stat_name: &str) -> String {
+    let mut name = "vpp".to_string();
+    for c in stat_name.chars() {
+        if c.is_ascii_alphanumeric() {
+            name.push(c);
+        } else if !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+    name.trim_end_matches('_').to_string()
+}
+
+pub fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+struct Family {
+    typ: &'static str,
+    samples: Vec<(String, String)>,
+}
+
+/* Metric families by name, so that the output is stable and each gets one TYPE line */
+#[derive(Default)]
+struct Families(BTreeMap<String, Family>);
+
+impl Families {
+    fn add(&mut self, name: String, typ: &'static str, labels: String, value: String) {
+        self.0
+            .entry(name)
+            .or_insert_with(|| Family {
+                typ,
+                samples: vec![],
+            })
+            .samples
+            .push((labels, value));
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        for (name, family) in self.0.iter() {
+            writeln!(out, "# TYPE {} {}", name, family.typ).unwrap();
+            for (labels, value) in family.samples.iter() {
+                if labels.is_empty() {
+                    writeln!(out, "{} {}", name, value).unwrap();
+                } else {
+                    writeln!(out, "{}{{{}}} {}", name, labels, value).unwrap();
+                }
+            }
+        }
+        out
+    }
+}
+
+fn counter_len(value: &StatValue) -> usize {
+    match value {
+        StatValue::SimpleCounter(threads) => threads.iter().map(|t| t.len()).max(),
+        StatValue::CombinedCounter(threads) => threads.iter().map(|t| t.len()).max(),
+        _ => None,
+    }
+    .unwrap_or(0)
+}
+
+pub fn render_metrics(entries: &[StatEntry], api: &ApiState) -> String {
+    /* the names from the API, else from the stats segment */
+    let stats_names: &[Option<String>] = entries
+        .iter()
+        .find_map(|e| match (&e.name[..], &e.value) {
+            ("/if/names", StatValue::NameVector(names)) => Some(&names[..]),
+            _ => None,
+        })
+        .unwrap_or(&[]);
+    let if_labels = |sw_if_index: usize| {
+        let name = match api.interfaces.get(&(sw_if_index as u32)) {
+            Some(info) => &info.name[..],
+            None => stats_names
+                .get(sw_if_index)
+                .and_then(|n| n.as_deref())
+                .unwrap_or(""),
+        };
+        format!(
+            "interface=\"{}\",sw_if_index=\"{}\"",
+            escape_label(name),
+            sw_if_index
+        )
+    };
+
+    let mut f = Families::default();
+    for e in entries {
+        if let Some(err) = e.name.strip_prefix("/err/") {
+            let total = match &e.value {
+                StatValue::Error(_) => e.value.error_total(),
+                /* version 2 has the error counters as simple counters */
+                StatValue::SimpleCounter(_) => e.value.simple_total(0),
+                _ => None,
+            };
+            if let Some(total) = total {
+                let mut parts = err.splitn(2, '/');
+                let node = parts.next().unwrap_or("");
+                let counter = parts.next().unwrap_or("");
+                let labels = format!(
+                    "node=\"{}\",counter=\"{}\"",
+                    escape_label(node),
+                    escape_label(counter)
+                );
+                f.add(
+                    "vpp_errors_total".into(),
+                    "counter",
+                    labels,
+                    total.to_string(),
+                );
+            }
+            continue;
+        }
+        let name = metric_name(&e.name);
+        match &e.value {
+            StatValue::Scalar(v) => f.add(name, "gauge", String::new(), v.to_string()),
+            StatValue::SimpleCounter(_) if e.name.starts_with("/if/") => {
+                for i in 0..counter_len(&e.value) {
+                    let total = e.value.simple_total(i).unwrap();
+                    f.add(
+                        format!("{}_total", name),
+                        "counter",
+                        if_labels(i),
+                        total.to_string(),
+                    );
+                }
+            }
+            StatValue::CombinedCounter(_) if e.name.starts_with("/if/") => {
+                for i in 0..counter_len(&e.value) {
+                    let total = e.value.combined_total(i).unwrap();
+                    f.add(
+                        format!("{}_packets_total", name),
+                        "counter",
+                        if_labels(i),
+                        total.packets.to_string(),
+                    );
+                    f.add(
+                        format!("{}_bytes_total", name),
+                        "counter",
+                        if_labels(i),
+                        total.bytes.to_string(),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    f.add(
+        "vpp_api_connected".into(),
+        "gauge",
+        String::new(),
+        (api.connected as u8).to_string(),
+    );
+    let mut interfaces: Vec<_> = api.interfaces.values().collect();
+    interfaces.sort_by_key(|i| i.sw_if_index);
+    for info in interfaces {
+        let labels = if_labels(info.sw_if_index as usize);
+        f.add(
+            "vpp_interface_admin_up".into(),
+            "gauge",
+            labels.clone(),
+            (info.admin_up as u8).to_string(),
+        );
+        f.add(
+            "vpp_interface_link_up".into(),
+            "gauge",
+            labels,
+            (info.link_up as u8).to_string(),
+        );
+    }
+    f.render()
+}
@@ -0,0 +1,313 @@
+/*
+ * A Prometheus exporter for VPP.
+ *
+ * The counters come from the stats segment on every scrape, the
+ * interface names and the admin/link state come from the API socket,
+ * where a watcher thread keeps them current (see interfaces.rs).
+ * If VPP goes away, the watcher reconnects, and the stats segment is
+ * mapped again on the next scrape.
+ *
+ * The HTTP side is the bare minimum for a scraper: GET /metrics.
+ */
+use crate::error::Result;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use vpp_api_stats::StatClient;
+
+mod interfaces;
+mod metrics;
+pub use interfaces::{
+    parse_sw_interface_details, sw_interface_dump_body, want_interface_events_body,
+    watch_interfaces, InterfaceInfo,
+};
+pub use metrics::{escape_label, metric_name, render_metrics};
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+const HTTP_MAX_REQUEST: usize = 8192;
+
+/// What is known over the API socket
+#[derive(Debug, Default, Clone)]
+pub struct ApiState {
+    pub connected: bool,
+    pub interfaces: HashMap<u32, InterfaceInfo>,
+}
+
+pub struct Exporter {
+    api_path: String,
+    stats_path: String,
+    reconnect_interval: Duration,
+    state: Arc<Mutex<ApiState>>,
+    stats: Mutex<Option<StatClient>>,
+}
+
+impl Exporter {
+    pub fn new(api_path: &str, stats_path: &str) -> Self {
+        Exporter {
+            api_path: api_path.to_owned(),
+            stats_path: stats_path.to_owned(),
+            reconnect_interval: Duration::from_secs(5),
+            state: Arc::new(Mutex::new(ApiState::default())),
+            stats: Mutex::new(None),
+        }
+    }
+
+    /// How long to wait before connecting to the API socket again
+    pub fn with_reconnect_interval(mut self, interval: Duration) -> Self {
+        self.reconnect_interval = interval;
+        self
+    }
+
+    pub fn api_state(&self) -> ApiState {
+        self.state.lock().unwrap().clone()
+    }
+
+    /// Start the thread which keeps the interface table current
+    pub fn start_watcher(&self) {
+        let api_path = self.api_path.clone();
+        let state = self.state.clone();
+        let interval = self.reconnect_interval;
+        std::thread::spawn(move || loop {
+            if let Err(e) = watch_interfaces(&api_path, state.clone()) {
+                warn!("Interface watcher on {}: {:?}", &api_path, e);
+            }
+            state.lock().unwrap().connected = false;
+            std::thread::sleep(interval);
+        });
+    }
+
+    /// The current metrics in the Prometheus text format
+    pub fn metrics(&self) -> Result<String> {
+        let mut stats = self.stats.lock().unwrap();
+        if stats.is_none() {
+            let client = StatClient::connect(&self.stats_path)
+                .map_err(|e| format!("{}: {:?}", &self.stats_path, e))?;
+            *stats = Some(client);
+        }
+        let entries = match stats.as_ref().unwrap().dump() {
+            Ok(entries) => entries,
+            Err(e) => {
+                /* VPP restart leaves us with a stale segment, map the new one next time */
+                *stats = None;
+                return Err(format!("{}: {:?}", &self.stats_path, e).into());
+            }
+        };
+        Ok(render_metrics(&entries, &self.api_state()))
+    }
+
+    /// Start the watcher and serve the metrics on this address forever
+    pub fn run(&self, listen_addr: &str) -> Result<()> {
+        let listener = TcpListener::bind(listen_addr)?;
+        self.start_watcher();
+        self.serve(listener)
+    }
+
+    pub fn serve(&self, listener: TcpListener) -> Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let peer = stream.peer_addr().ok();
+            if let Err(e) = self.handle_http(stream) {
+                info!("HTTP client {:?}: {:?}", peer, e);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_http(&self, mut stream: TcpStream) -> Result<()> {
+        stream.set_read_timeout(Some(HTTP_TIMEOUT))?;
+        let mut req = vec![];
+        let mut buf = [0u8; 1024];
+        while !req.windows(4).any(|w| w == b"\r\n\r\n") {
+            let n = stream.read(&mut buf)?;
+            if n == 0 {
+                return Err("connection closed before the end of the request".into());
+            }
+            req.extend_from_slice(&buf[..n]);
+            if req.len() > HTTP_MAX_REQUEST {
+                return Err("request too long".into());
+            }
+        }
+        let req = String::from_utf8_lossy(&req);
+        let mut words = req.split_whitespace();
+        let method = words.next().unwrap_or("");
+        let path = words.next().unwrap_or("");
+        let path = path.split('?').next().unwrap();
+
+        let (status, content_type, body) = match (method, path) {
+            ("GET", "/metrics") => match self.metrics() {
+                Ok(body) => ("200 OK", "text/plain; version=0.0.4", body),
+                Err(e) => {
+                    warn!("Scrape failed: {:?}", e);
+                    (
+                        "500 Internal Server Error",
+                        "text/plain",
+                        format!("{:?}\n", e),
+                    )
+                }
+            },
+            ("GET", _) => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+            _ => (
+                "405 Method Not Allowed",
+                "text/plain",
+                "Method not allowed\n".to_string(),
+            ),
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{mock_sw_interface_details, MockVpp};
+    use std::time::Instant;
+    use vpp_api_stats::{CombinedCounter, StatEntry, StatValue};
+
+    #[test]
+    fn test_render_metrics() {
+        assert_eq!(metric_name("/if/rx-miss"), "vpp_if_rx_miss");
+        assert_eq!(metric_name("/sys/vector_rate"), "vpp_sys_vector_rate");
+        assert_eq!(escape_label("a\"b\\c"), "a\\\"b\\\\c");
+
+        let entries = vec![
+            StatEntry {
+                name: "/sys/vector_rate".into(),
+                value: StatValue::Scalar(1.5),
+            },
+            StatEntry {
+                name: "/if/names".into(),
+                value: StatValue::NameVector(vec![Some("local0".into()), Some("tap0".into())]),
+            },
+            StatEntry {
+                name: "/if/drops".into(),
+                value: StatValue::SimpleCounter(vec![vec![1, 2], vec![10, 20]]),
+            },
+            StatEntry {
+                name: "/if/rx".into(),
+                value: StatValue::CombinedCounter(vec![vec![
+                    CombinedCounter::default(),
+                    CombinedCounter {
+                        packets: 3,
+                        bytes: 300,
+                    },
+                ]]),
+            },
+            StatEntry {
+                name: "/err/ip4-map/bad protocol".into(),
+                value: StatValue::Error(vec![4, 5]),
+            },
+            StatEntry {
+                name: "/err/ip4-input/ip4 ttl <= 1".into(),
+                value: StatValue::SimpleCounter(vec![vec![6], vec![1]]),
+            },
+        ];
+        let mut api = ApiState {
+            connected: true,
+            interfaces: HashMap::new(),
+        };
+        api.interfaces.insert(
+            1,
+            InterfaceInfo {
+                sw_if_index: 1,
+                name: "host-vpp1out".into(),
+                admin_up: true,
+                link_up: false,
+            },
+        );
+        let out = render_metrics(&entries, &api);
+        let expected = [
+            "# TYPE vpp_sys_vector_rate gauge\nvpp_sys_vector_rate 1.5\n",
+            "# TYPE vpp_if_drops_total counter\n",
+            "vpp_if_drops_total{interface=\"local0\",sw_if_index=\"0\"} 11\n",
+            "vpp_if_drops_total{interface=\"host-vpp1out\",sw_if_index=\"1\"} 22\n",
+            "vpp_if_rx_packets_total{interface=\"host-vpp1out\",sw_if_index=\"1\"} 3\n",
+            "vpp_if_rx_bytes_total{interface=\"host-vpp1out\",sw_if_index=\"1\"} 300\n",
+            "vpp_errors_total{node=\"ip4-map\",counter=\"bad protocol\"} 9\n",
+            "vpp_errors_total{node=\"ip4-input\",counter=\"ip4 ttl <= 1\"} 7\n",
+            "vpp_api_connected 1\n",
+            "vpp_interface_admin_up{interface=\"host-vpp1out\",sw_if_index=\"1\"} 1\n",
+            "vpp_interface_link_up{interface=\"host-vpp1out\",sw_if_index=\"1\"} 0\n",
+        ];
+        for e in expected.iter() {
+            assert!(out.contains(e), "{:?} not in:\n{}", e, out);
+        }
+        assert_eq!(out.matches("# TYPE vpp_errors_total counter").count(), 1);
+        assert!(!out.contains("if_names"));
+    }
+
+    #[test]
+    fn test_parse_sw_interface_details() {
+        let details = mock_sw_interface_details(7, 5, 1, "loop0");
+        let info = parse_sw_interface_details(&details[2..]).unwrap();
+        assert_eq!(
+            info,
+            InterfaceInfo {
+                sw_if_index: 5,
+                name: "loop0".into(),
+                admin_up: true,
+                link_up: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_watch_interfaces() {
+        let mock = MockVpp::start();
+        let exporter = Exporter::new(mock.path(), "/nonexistent/stats.sock");
+        exporter.start_watcher();
+
+        /* local0 comes up with the event sent along with the subscription */
+        let start = Instant::now();
+        let state = loop {
+            let state = exporter.api_state();
+            if state.interfaces.get(&0).map(|i| i.link_up) == Some(true) {
+                break state;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5), "{:?}", state);
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert!(state.connected);
+        assert_eq!(state.interfaces.len(), 2);
+        assert_eq!(state.interfaces[&0].name, "local0");
+        assert!(state.interfaces[&0].admin_up);
+        assert_eq!(state.interfaces[&1].name, "host-vpp1out");
+        let received = mock.received();
+        assert!(received.contains(&"want_interface_events".to_string()));
+        assert!(received.contains(&"sw_interface_dump".to_string()));
+
+        assert!(exporter.metrics().is_err());
+    }
+
+    fn http_get(addr: &std::net::SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).unwrap();
+        resp
+    }
+
+    #[test]
+    fn test_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let exporter = Exporter::new("/nonexistent/api.sock", "/nonexistent/stats.sock");
+            exporter.serve(listener).unwrap();
+        });
+        assert!(http_get(&addr, "/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+        let resp = http_get(&addr, "/metrics");
+        assert!(resp.starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+        assert!(resp.contains("/nonexistent/stats.sock"));
+    }
+}
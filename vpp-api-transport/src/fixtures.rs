@@ -0,0 +1,53 @@
+/*
+ * The message types the unit tests send through the reqrecv helpers,
+ * cut down to the fields the tests look at. The names and CRCs are
+ * those of the mock message table (see mock.rs).
+ */
+use serde::{Deserialize, Serialize};
+use vpp_api_message::VppApiMessage;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AddDelAddress {
+    pub client_index: u32,
+    pub context: u32,
+    pub sw_if_index: u32,
+    pub is_add: bool,
+}
+impl VppApiMessage for AddDelAddress {
+    fn get_message_name_and_crc() -> String {
+        "sw_interface_add_del_address_5803d5c4".into()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddDelAddressReply {
+    pub context: u32,
+    pub retval: i32,
+}
+impl VppApiMessage for AddDelAddressReply {
+    fn get_message_name_and_crc() -> String {
+        "sw_interface_add_del_address_reply_e8d4e804".into()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwInterfaceDump {
+    pub client_index: u32,
+    pub context: u32,
+}
+impl VppApiMessage for SwInterfaceDump {
+    fn get_message_name_and_crc() -> String {
+        "sw_interface_dump_aa610c27".into()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwInterfaceDetails {
+    pub context: u32,
+    pub sw_if_index: u32,
+}
+impl VppApiMessage for SwInterfaceDetails {
+    fn get_message_name_and_crc() -> String {
+        "sw_interface_details_17b69fa2".into()
+    }
+}
@@ -0,0 +1,382 @@
+/*
+ * Instrumentation of the API calls made through the reqrecv helpers.
+ *
+ * Every call gets a "vpp_api_call" tracing span with the message name,
+ * the request context and (once the reply is in) the retval, and is
+ * reported to the transport's own ApiMetrics sink (see
+ * VppApiTransport::metrics()), or else to the process-wide one, if
+ * one is set.
+ * CallMetrics is a ready-made sink which keeps the per-message call
+ * and error counters and latency histograms, and renders them in the
+ * Prometheus text format; anything else (OpenTelemetry, an existing
+ * registry...) can be plugged in by implementing ApiMetrics.
+ */
+use crate::proxy::strip_crc;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// Latency histogram bucket bounds in seconds, from 100us to 2.5s
+pub const DEFAULT_LATENCY_BUCKETS: &[f64] = &[
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallOutcome {
+    /// The reply came back, with a retval of zero or without a retval at all
+    Ok,
+    /// The reply came back with a negative retval
+    ApiError(i32),
+    /// No (decodable) reply: write/read/deserialization error
+    TransportError,
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiCall<'a> {
+    /// The request message name with the CRC
+    pub msg_name: &'a str,
+    pub context: u32,
+    pub retval: Option<i32>,
+    pub outcome: CallOutcome,
+    /// The number of details messages received, for the dumps
+    pub details: usize,
+    pub elapsed: Duration,
+}
+
+/// A sink for the per-call measurements
+pub trait ApiMetrics: Send + Sync {
+    fn record_call(&self, call: &ApiCall);
+}
+
+lazy_static! {
+    static ref METRICS: RwLock<Option<Arc<dyn ApiMetrics>>> = RwLock::new(None);
+}
+
+/// Report all the subsequent API calls to this sink
+pub fn set_metrics(metrics: Arc<dyn ApiMetrics>) {
+    *METRICS.write().unwrap() = Some(metrics);
+}
+
+pub fn clear_metrics() {
+    *METRICS.write().unwrap() = None;
+}
+
+fn current_metrics() -> Option<Arc<dyn ApiMetrics>> {
+    METRICS.read().unwrap().clone()
+}
+
+/// The retval of a "*_reply" message body (after the msg id): context, retval, ...
+pub(crate) fn reply_retval(reply_name: &str, data: &[u8]) -> Option<i32> {
+    if strip_crc(reply_name).ends_with("_reply") && data.len() >= 8 {
+        Some(i32::from_be_bytes([data[4], data[5], data[6], data[7]]))
+    } else {
+        None
+    }
+}
+
+/*
+ * One call in flight. The span is entered for the lifetime of the tracker,
+ * and the measurement is reported when it is dropped - so the early returns
+ * with '?' are accounted for as the transport errors.
+ */
+pub(crate) struct CallTracker {
+    span: tracing::span::EnteredSpan,
+    metrics: Option<Arc<dyn ApiMetrics>>,
+    msg_name: String,
+    context: u32,
+    start: Instant,
+    replied: bool,
+    retval: Option<i32>,
+    details: usize,
+}
+
+impl CallTracker {
+    /// Start tracking the request with this body (after the msg id): client_index, context, ...
+    pub fn start(msg_name: &str, body: &[u8], metrics: Option<Arc<dyn ApiMetrics>>) -> Self {
+        let context = if body.len() >= 8 {
+            u32::from_be_bytes([body[4], body[5], body[6], body[7]])
+        } else {
+            0
+        };
+        let span = tracing::debug_span!(
+            "vpp_api_call",
+            msg = msg_name,
+            context,
+            retval = tracing::field::Empty,
+            details = tracing::field::Empty,
+            outcome = tracing::field::Empty,
+        )
+        .entered();
+        CallTracker {
+            span,
+            metrics,
+            msg_name: msg_name.to_string(),
+            context,
+            start: Instant::now(),
+            replied: false,
+            retval: None,
+            details: 0,
+        }
+    }
+
+    pub fn detail(&mut self) {
+        self.details += 1;
+    }
+
+    /// The final reply has been received and decoded
+    pub fn reply(&mut self, retval: Option<i32>) {
+        self.replied = true;
+        self.retval = retval;
+    }
+}
+
+impl Drop for CallTracker {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        let outcome = match (self.replied, self.retval) {
+            (false, _) => CallOutcome::TransportError,
+            (true, Some(r)) if r < 0 => CallOutcome::ApiError(r),
+            (true, _) => CallOutcome::Ok,
+        };
+        if let Some(r) = self.retval {
+            self.span.record("retval", r);
+        }
+        self.span.record("details", self.details);
+        self.span.record("outcome", tracing::field::debug(&outcome));
+        tracing::debug!(elapsed_us = elapsed.as_micros() as u64, "vpp api call done");
+
+        if let Some(metrics) = self.metrics.clone().or_else(current_metrics) {
+            metrics.record_call(&ApiCall {
+                msg_name: &self.msg_name,
+                context: self.context,
+                retval: self.retval,
+                outcome,
+                details: self.details,
+                elapsed,
+            });
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CallStats {
+    pub calls: u64,
+    pub api_errors: u64,
+    pub transport_errors: u64,
+    /// Non-cumulative counts per bucket; one more than the bucket bounds, for +Inf
+    pub bucket_counts: Vec<u64>,
+    pub sum_seconds: f64,
+}
+
+/// The in-memory counters and latency histograms per message name
+pub struct CallMetrics {
+    buckets: Vec<f64>,
+    stats: Mutex<BTreeMap<String, CallStats>>,
+}
+
+impl Default for CallMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CallMetrics {
+    pub fn new() -> Self {
+        Self::with_buckets(DEFAULT_LATENCY_BUCKETS.to_vec())
+    }
+
+    /// Use these (ascending) bucket bounds in seconds for the latency histograms
+    pub fn with_buckets(buckets: Vec<f64>) -> Self {
+        CallMetrics {
+            buckets,
+            stats: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn buckets(&self) -> &[f64] {
+        &self.buckets
+    }
+
+    /// The stats so far, keyed by the message name without the CRC
+    pub fn snapshot(&self) -> BTreeMap<String, CallStats> {
+        self.stats.lock().unwrap().clone()
+    }
+
+    pub fn render_prometheus(&self) -> String {
+        let stats = self.snapshot();
+        let mut out = String::new();
+
+        let _ = writeln!(out, "# HELP vpp_api_calls_total VPP API calls made");
+        let _ = writeln!(out, "# TYPE vpp_api_calls_total counter");
+        for (msg, s) in &stats {
+            let _ = writeln!(out, "vpp_api_calls_total{{msg=\"{}\"}} {}", msg, s.calls);
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP vpp_api_call_errors_total VPP API calls which failed, by the kind of failure"
+        );
+        let _ = writeln!(out, "# TYPE vpp_api_call_errors_total counter");
+        for (msg, s) in &stats {
+            let _ = writeln!(
+                out,
+                "vpp_api_call_errors_total{{msg=\"{}\",kind=\"api\"}} {}",
+                msg, s.api_errors
+            );
+            let _ = writeln!(
+                out,
+                "vpp_api_call_errors_total{{msg=\"{}\",kind=\"transport\"}} {}",
+                msg, s.transport_errors
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP vpp_api_call_duration_seconds VPP API call latency, from the request to the final reply"
+        );
+        let _ = writeln!(out, "# TYPE vpp_api_call_duration_seconds histogram");
+        for (msg, s) in &stats {
+            let mut cumulative = 0;
+            for (i, count) in s.bucket_counts.iter().enumerate() {
+                cumulative += count;
+                let le = match self.buckets.get(i) {
+                    Some(b) => b.to_string(),
+                    None => "+Inf".to_string(),
+                };
+                let _ = writeln!(
+                    out,
+                    "vpp_api_call_duration_seconds_bucket{{msg=\"{}\",le=\"{}\"}} {}",
+                    msg, le, cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "vpp_api_call_duration_seconds_sum{{msg=\"{}\"}} {}",
+                msg, s.sum_seconds
+            );
+            let _ = writeln!(
+                out,
+                "vpp_api_call_duration_seconds_count{{msg=\"{}\"}} {}",
+                msg, s.calls
+            );
+        }
+        out
+    }
+}
+
+impl ApiMetrics for CallMetrics {
+    fn record_call(&self, call: &ApiCall) {
+        let secs = call.elapsed.as_secs_f64();
+        let bucket = self
+            .buckets
+            .iter()
+            .position(|b| secs <= *b)
+            .unwrap_or(self.buckets.len());
+
+        let mut stats = self.stats.lock().unwrap();
+        let s = stats
+            .entry(strip_crc(call.msg_name).to_string())
+            .or_insert_with(|| CallStats {
+                bucket_counts: vec![0; self.buckets.len() + 1],
+                ..Default::default()
+            });
+        s.calls += 1;
+        match call.outcome {
+            CallOutcome::Ok => {}
+            CallOutcome::ApiError(_) => s.api_errors += 1,
+            CallOutcome::TransportError => s.transport_errors += 1,
+        }
+        s.bucket_counts[bucket] += 1;
+        s.sum_seconds += secs;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::afunix;
+    use crate::fixtures::{AddDelAddress, AddDelAddressReply, SwInterfaceDetails, SwInterfaceDump};
+    use crate::mock::MockVpp;
+    use crate::reqrecv::{send_recv_many, send_recv_one};
+    use crate::VppApiTransport;
+
+    fn record(m: &CallMetrics, name: &str, outcome: CallOutcome, ms: u64) {
+        m.record_call(&ApiCall {
+            msg_name: name,
+            context: 1,
+            retval: None,
+            outcome,
+            details: 0,
+            elapsed: Duration::from_millis(ms),
+        });
+    }
+
+    #[test]
+    fn test_call_metrics() {
+        let m = CallMetrics::with_buckets(vec![0.001, 0.01]);
+        record(&m, "control_ping_51077d14", CallOutcome::Ok, 0);
+        record(&m, "control_ping_51077d14", CallOutcome::ApiError(-1), 5);
+        record(&m, "control_ping_51077d14", CallOutcome::TransportError, 50);
+        let stats = m.snapshot();
+        let s = &stats["control_ping"];
+        assert_eq!(s.calls, 3);
+        assert_eq!(s.api_errors, 1);
+        assert_eq!(s.transport_errors, 1);
+        assert_eq!(s.bucket_counts, vec![1, 1, 1]);
+
+        let text = m.render_prometheus();
+        assert!(text.contains("vpp_api_calls_total{msg=\"control_ping\"} 3\n"));
+        assert!(text.contains("vpp_api_call_errors_total{msg=\"control_ping\",kind=\"api\"} 1\n"));
+        assert!(text.contains(
+            "vpp_api_call_duration_seconds_bucket{msg=\"control_ping\",le=\"0.01\"} 2\n"
+        ));
+        assert!(text.contains(
+            "vpp_api_call_duration_seconds_bucket{msg=\"control_ping\",le=\"+Inf\"} 3\n"
+        ));
+        assert!(text.contains("vpp_api_call_duration_seconds_count{msg=\"control_ping\"} 3\n"));
+    }
+
+    #[test]
+    fn test_reqrecv_is_instrumented() {
+        let mock = MockVpp::start();
+        let metrics = Arc::new(CallMetrics::new());
+
+        /* not the process-wide sink, the other tests make the same calls */
+        let mut t = afunix::Transport::new(mock.path()).with_metrics(metrics.clone());
+        t.connect("instrument-test", None, 32).unwrap();
+        let req = AddDelAddress {
+            client_index: t.get_client_index(),
+            context: 42,
+            sw_if_index: 1,
+            is_add: true,
+        };
+        let reply: AddDelAddressReply = send_recv_one(&req, &mut t).unwrap();
+        assert_eq!(reply.context, 42);
+        let req = SwInterfaceDump {
+            client_index: t.get_client_index(),
+            context: 43,
+        };
+        let details: Vec<SwInterfaceDetails> = send_recv_many(&req, &mut t).unwrap();
+        assert_eq!(details.len(), 2);
+        t.disconnect();
+
+        let stats = metrics.snapshot();
+        let s = &stats["sw_interface_add_del_address"];
+        assert_eq!((s.calls, s.api_errors, s.transport_errors), (1, 0, 0));
+        let s = &stats["sw_interface_dump"];
+        assert_eq!((s.calls, s.api_errors, s.transport_errors), (1, 0, 0));
+        assert_eq!(s.bucket_counts.iter().sum::<u64>(), 1);
+    }
+
+    #[test]
+    fn test_reply_retval() {
+        let data = [0, 0, 0, 7, 0xff, 0xff, 0xff, 0xfe];
+        assert_eq!(reply_retval("control_ping_reply_f6b0b8ca", &data), Some(-2));
+        assert_eq!(reply_retval("sw_interface_details_17b69fa2", &data), None);
+        assert_eq!(
+            reply_retval("control_ping_reply_f6b0b8ca", &data[..4]),
+            None
+        );
+    }
+}
@@ -0,0 +1,511 @@
+/*
+ * An interceptor chain around any VppApiTransport.
+ *
+ * Every message written through the wrapper goes through the interceptors
+ * in order, and every message read goes through them in reverse, so the
+ * first interceptor is the outermost one. Each of them sees the message
+ * name, id, context and payload and may modify it, drop it, or answer it
+ * itself (short-circuit); a delay is just a sleep inside the hook.
+ *
+ * The transports only resolve the names to ids, so the wrapper learns
+ * the names from the get_msg_index() calls going through it - which the
+ * reqrecv helpers and the generated code make for both the request and
+ * the reply before sending anything.
+ */
+use crate::error::{Error, Result};
+use crate::instrument::ApiMetrics;
+use crate::protocol::{encode_frame, split_msg_id};
+use crate::proxy::{pattern_matches, strip_crc};
+use crate::{read_sock_frame, VppApiTransport};
+use log::info;
+use std::collections::{HashMap, VecDeque};
+use std::io::{Read, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub msg_id: u16,
+    /// The name with the CRC, if the wrapper has seen it resolved
+    pub name: Option<String>,
+    /// Everything after the msg id
+    pub payload: Vec<u8>,
+}
+
+impl Message {
+    /// A message as it is written to / read from the transport: msg id, payload
+    pub fn from_bytes(data: &[u8], table: &MessageTable) -> Result<Self> {
+        let (msg_id, payload) = split_msg_id(data)?;
+        Ok(Message {
+            msg_id,
+            name: table.name(msg_id).map(|n| n.to_string()),
+            payload: payload.to_vec(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.msg_id.to_be_bytes().to_vec();
+        out.extend_from_slice(&self.payload);
+        out
+    }
+
+    /* the requests have the client_index first, the replies have the context right away */
+    fn context_offset(&self, request: bool) -> usize {
+        if request {
+            4
+        } else {
+            0
+        }
+    }
+
+    pub fn context(&self, request: bool) -> Option<u32> {
+        let ofs = self.context_offset(request);
+        self.payload
+            .get(ofs..ofs + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn set_context(&mut self, request: bool, context: u32) {
+        let ofs = self.context_offset(request);
+        if let Some(b) = self.payload.get_mut(ofs..ofs + 4) {
+            b.copy_from_slice(&context.to_be_bytes());
+        }
+    }
+
+    /// A "<name>_reply" for this request with the same context and the given retval
+    pub fn synthetic_reply(&self, table: &MessageTable, retval: i32) -> Option<Message> {
+        let name = self.name.as_ref()?;
+        let reply_name = format!("{}_reply", strip_crc(name));
+        let (reply_name, reply_id) = table.find_by_base(&reply_name)?;
+        let mut payload = self.context(true)?.to_be_bytes().to_vec();
+        payload.extend_from_slice(&retval.to_be_bytes());
+        Some(Message {
+            msg_id: reply_id,
+            name: Some(reply_name.to_string()),
+            payload,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Pass the (possibly modified) message on
+    Continue,
+    /// Stop here: the request is not sent, the reply is not delivered
+    Drop,
+    /// Stop here and deliver these messages to the caller instead, as if they came from VPP
+    Respond(Vec<Message>),
+}
+
+pub trait Interceptor: Send {
+    fn on_request(&mut self, _msg: &mut Message, _table: &MessageTable) -> Result<Action> {
+        Ok(Action::Continue)
+    }
+    fn on_reply(&mut self, _msg: &mut Message, _table: &MessageTable) -> Result<Action> {
+        Ok(Action::Continue)
+    }
+}
+
+/// The message names the wrapper has seen resolved
+#[derive(Debug, Default, Clone)]
+pub struct MessageTable {
+    id_to_name: HashMap<u16, String>,
+}
+
+impl MessageTable {
+    pub fn name(&self, msg_id: u16) -> Option<&str> {
+        self.id_to_name.get(&msg_id).map(|n| n.as_str())
+    }
+
+    pub fn id(&self, name: &str) -> Option<u16> {
+        self.id_to_name
+            .iter()
+            .find(|(_, n)| n.as_str() == name)
+            .map(|(id, _)| *id)
+    }
+
+    /// Find the message by its name without the CRC
+    pub fn find_by_base(&self, base: &str) -> Option<(&str, u16)> {
+        self.id_to_name
+            .iter()
+            .find(|(_, n)| strip_crc(n) == base)
+            .map(|(id, n)| (n.as_str(), *id))
+    }
+
+    pub fn insert(&mut self, name: &str, msg_id: u16) {
+        self.id_to_name.insert(msg_id, name.to_string());
+    }
+}
+
+pub struct Intercepted<T: VppApiTransport> {
+    inner: T,
+    chain: Vec<Box<dyn Interceptor>>,
+    table: MessageTable,
+    /* the messages to deliver, with the number of interceptors they still have to pass */
+    pending: VecDeque<(Message, usize)>,
+    /* the framed data for the plain Read users */
+    rx_buf: Vec<u8>,
+}
+
+impl<T: VppApiTransport> Intercepted<T> {
+    pub fn new(inner: T) -> Self {
+        Intercepted {
+            inner,
+            chain: vec![],
+            table: Default::default(),
+            pending: Default::default(),
+            rx_buf: vec![],
+        }
+    }
+
+    /// Add an interceptor inside the ones added so far
+    pub fn with<I: Interceptor + 'static>(mut self, interceptor: I) -> Self {
+        self.push(interceptor);
+        self
+    }
+
+    pub fn push<I: Interceptor + 'static>(&mut self, interceptor: I) {
+        self.chain.push(Box::new(interceptor));
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    pub fn message_table(&self) -> &MessageTable {
+        &self.table
+    }
+
+    fn send(&mut self, data: &[u8]) -> Result<()> {
+        let mut msg = Message::from_bytes(data, &self.table)?;
+        for i in 0..self.chain.len() {
+            match self.chain[i].on_request(&mut msg, &self.table)? {
+                Action::Continue => {}
+                Action::Drop => return Ok(()),
+                Action::Respond(replies) => {
+                    /* the replies only go through the interceptors outside this one */
+                    self.pending.extend(replies.into_iter().map(|m| (m, i)));
+                    return Ok(());
+                }
+            }
+        }
+        self.inner.write_all(&msg.to_bytes())?;
+        Ok(())
+    }
+
+    /* run a reply through the interceptors [0..level) from the inside out */
+    fn deliver(&mut self, mut msg: Message, level: usize) -> Result<Option<Message>> {
+        for i in (0..level).rev() {
+            match self.chain[i].on_reply(&mut msg, &self.table)? {
+                Action::Continue => {}
+                Action::Drop => return Ok(None),
+                Action::Respond(replies) => {
+                    self.pending.extend(replies.into_iter().map(|m| (m, i)));
+                    return Ok(None);
+                }
+            }
+        }
+        Ok(Some(msg))
+    }
+
+    fn receive(&mut self) -> Result<Message> {
+        loop {
+            let (msg, level) = match self.pending.pop_front() {
+                Some(p) => p,
+                None => {
+                    let data = self.inner.read_one_msg()?;
+                    (Message::from_bytes(&data, &self.table)?, self.chain.len())
+                }
+            };
+            if let Some(msg) = self.deliver(msg, level)? {
+                return Ok(msg);
+            }
+        }
+    }
+}
+
+fn to_io_error(e: Error) -> std::io::Error {
+    match e {
+        Error::IoError(e) => e,
+        e => std::io::Error::other(format!("{:?}", e)),
+    }
+}
+
+impl<T: VppApiTransport> Read for Intercepted<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.rx_buf.is_empty() {
+            let msg = self.receive().map_err(to_io_error)?;
+            self.rx_buf = encode_frame(&msg.to_bytes());
+        }
+        let n = std::cmp::min(buf.len(), self.rx_buf.len());
+        buf[..n].copy_from_slice(&self.rx_buf[..n]);
+        self.rx_buf.drain(..n);
+        Ok(n)
+    }
+}
+
+impl<T: VppApiTransport> Write for Intercepted<T> {
+    /// Every write is one message: msg id, payload
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.send(buf).map_err(to_io_error)?;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<T: VppApiTransport> VppApiTransport for Intercepted<T> {
+    fn connect(&mut self, name: &str, chroot_prefix: Option<&str>, rx_qlen: i32) -> Result<()> {
+        self.inner.connect(name, chroot_prefix, rx_qlen)
+    }
+
+    fn disconnect(&mut self) {
+        self.pending.clear();
+        self.rx_buf.clear();
+        self.inner.disconnect()
+    }
+
+    fn set_nonblocking(&mut self, nonblocking: bool) -> Result<()> {
+        self.inner.set_nonblocking(nonblocking)
+    }
+
+    fn get_msg_index(&mut self, name: &str) -> Option<u16> {
+        let msg_id = self.inner.get_msg_index(name)?;
+        self.table.insert(name, msg_id);
+        Some(msg_id)
+    }
+
+    fn get_table_max_index(&mut self) -> u16 {
+        self.inner.get_table_max_index()
+    }
+
+    fn get_client_index(&self) -> u32 {
+        self.inner.get_client_index()
+    }
+
+    fn get_next_context(&mut self) -> u32 {
+        self.inner.get_next_context()
+    }
+
+    fn dump(&self) {
+        self.inner.dump()
+    }
+
+    fn metrics(&self) -> Option<Arc<dyn ApiMetrics>> {
+        self.inner.metrics()
+    }
+
+    fn read_one_msg_into(&mut self, data: &mut Vec<u8>) -> Result<()> {
+        if !self.rx_buf.is_empty() {
+            /* someone has been reading byte-wise, finish that frame first */
+            *data = read_sock_frame(self)?;
+            return Ok(());
+        }
+        *data = self.receive()?.to_bytes();
+        Ok(())
+    }
+}
+
+/// Log every request and reply with its name and context
+#[derive(Debug, Default)]
+pub struct AuditLog;
+
+impl Interceptor for AuditLog {
+    fn on_request(&mut self, msg: &mut Message, _table: &MessageTable) -> Result<Action> {
+        info!(
+            "vpp api request {} (id {}) context {:?}, {} bytes",
+            msg.name.as_deref().unwrap_or("?"),
+            msg.msg_id,
+            msg.context(true),
+            msg.payload.len()
+        );
+        Ok(Action::Continue)
+    }
+
+    fn on_reply(&mut self, msg: &mut Message, _table: &MessageTable) -> Result<Action> {
+        info!(
+            "vpp api reply {} (id {}) context {:?}, {} bytes",
+            msg.name.as_deref().unwrap_or("?"),
+            msg.msg_id,
+            msg.context(false),
+            msg.payload.len()
+        );
+        Ok(Action::Continue)
+    }
+}
+
+/// Hold every request for a while before sending it on, e.g. to simulate a slow VPP
+#[derive(Debug)]
+pub struct Delay(pub Duration);
+
+impl Interceptor for Delay {
+    fn on_request(&mut self, _msg: &mut Message, _table: &MessageTable) -> Result<Action> {
+        std::thread::sleep(self.0);
+        Ok(Action::Continue)
+    }
+}
+
+/*
+ * Let through only the requests matching one of the patterns, same as the
+ * proxy policies. The others are answered with a "<name>_reply" carrying
+ * the configured retval, or dropped if there is no such reply (dumps).
+ */
+#[derive(Debug, Clone)]
+pub struct AllowOnly {
+    pub allow: Vec<String>,
+    pub denied_retval: i32,
+}
+
+impl AllowOnly {
+    pub fn new(allow: &[&str], denied_retval: i32) -> Self {
+        AllowOnly {
+            allow: allow.iter().map(|s| s.to_string()).collect(),
+            denied_retval,
+        }
+    }
+}
+
+impl Interceptor for AllowOnly {
+    fn on_request(&mut self, msg: &mut Message, table: &MessageTable) -> Result<Action> {
+        let name = msg.name.as_deref().map(strip_crc).unwrap_or("");
+        if self.allow.iter().any(|p| pattern_matches(p, name)) {
+            return Ok(Action::Continue);
+        }
+        match msg.synthetic_reply(table, self.denied_retval) {
+            Some(reply) => Ok(Action::Respond(vec![reply])),
+            None => Ok(Action::Drop),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::afunix;
+    use crate::mock::MockVpp;
+    use crate::protocol::{CLI_INBAND, CONTROL_PING};
+    use std::sync::{Arc, Mutex};
+
+    /* records what it sees, and rewrites the cli_inband command */
+    struct Recorder {
+        seen: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Interceptor for Recorder {
+        fn on_request(&mut self, msg: &mut Message, _table: &MessageTable) -> Result<Action> {
+            let name = msg.name.clone().unwrap_or_default();
+            self.seen.lock().unwrap().push(format!(
+                "> {} {}",
+                strip_crc(&name),
+                msg.context(true).unwrap()
+            ));
+            if name == CLI_INBAND {
+                /* client_index, context, cmd length, cmd */
+                let cmd = b"show version";
+                msg.payload.truncate(8);
+                msg.payload
+                    .extend_from_slice(&(cmd.len() as u32).to_be_bytes());
+                msg.payload.extend_from_slice(cmd);
+            }
+            Ok(Action::Continue)
+        }
+
+        fn on_reply(&mut self, msg: &mut Message, _table: &MessageTable) -> Result<Action> {
+            let name = msg.name.clone().unwrap_or_default();
+            self.seen.lock().unwrap().push(format!(
+                "< {} {}",
+                strip_crc(&name),
+                msg.context(false).unwrap()
+            ));
+            Ok(Action::Continue)
+        }
+    }
+
+    fn connect(mock: &MockVpp) -> afunix::Transport {
+        let mut t = afunix::Transport::new(mock.path());
+        t.connect("intercept-test", None, 32).unwrap();
+        t
+    }
+
+    #[test]
+    fn test_intercept_modify() {
+        let mock = MockVpp::start();
+        let seen = Arc::new(Mutex::new(vec![]));
+        let mut t = Intercepted::new(connect(&mock))
+            .with(AuditLog)
+            .with(Recorder { seen: seen.clone() });
+
+        let out = t.run_cli_inband("show nothing").unwrap();
+        assert!(out.starts_with("vpp v0.0-mock"));
+        let context = t.control_ping().unwrap();
+        t.skip_to_control_ping_reply(context).unwrap();
+        t.disconnect();
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                "> cli_inband 42",
+                "< cli_inband_reply 42",
+                "> control_ping 42",
+                "< control_ping_reply 42",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_intercept_short_circuit() {
+        let mock = MockVpp::start();
+        let seen = Arc::new(Mutex::new(vec![]));
+        let mut t = Intercepted::new(connect(&mock))
+            .with(Recorder { seen: seen.clone() })
+            .with(AllowOnly::new(&["control_ping", "*_dump"], -42));
+
+        let add_del = t
+            .get_msg_index("sw_interface_add_del_address_5803d5c4")
+            .unwrap();
+        let reply_id = t
+            .get_msg_index("sw_interface_add_del_address_reply_e8d4e804")
+            .unwrap();
+        let mut req = add_del.to_be_bytes().to_vec();
+        req.extend_from_slice(&0u32.to_be_bytes());
+        req.extend_from_slice(&7u32.to_be_bytes());
+        req.extend_from_slice(&[0u8; 26]);
+        t.write_all(&req).unwrap();
+        let (msg_id, data) = t.read_one_msg_id_and_msg().unwrap();
+        assert_eq!(msg_id, reply_id);
+        assert_eq!(&data[0..4], &7u32.to_be_bytes());
+        assert_eq!(&data[4..8], &(-42i32).to_be_bytes());
+
+        /* the allowed ones still go through, and the plain reads see the same frames */
+        t.get_msg_index(CONTROL_PING).unwrap();
+        t.control_ping().unwrap();
+        let mut frame = vec![0u8; crate::protocol::SOCK_MSG_HEADER_LEN];
+        t.read_exact(&mut frame).unwrap();
+        let len = crate::protocol::decode_frame_header(&frame).unwrap();
+        frame.resize(len, 0);
+        t.read_exact(&mut frame).unwrap();
+        assert_eq!(&frame[6..10], &0i32.to_be_bytes());
+        t.disconnect();
+
+        assert!(!mock
+            .received()
+            .contains(&"sw_interface_add_del_address".to_string()));
+        assert!(mock.received().contains(&"control_ping".to_string()));
+        assert_eq!(
+            seen.lock().unwrap()[..2],
+            [
+                "> sw_interface_add_del_address 7".to_string(),
+                "< sw_interface_add_del_address_reply 7".to_string()
+            ]
+        );
+    }
+}
@@ -4,10 +4,26 @@ extern crate lazy_static;
 #[macro_use]
 mod macros;
 pub mod afunix;
+pub mod cli;
+pub mod clisock;
+pub mod dryrun;
+pub mod exporter;
+pub mod instrument;
+pub mod intercept;
+pub mod pool;
+pub mod protocol;
+pub mod proxy;
+pub mod remote;
 pub mod shmem;
 // Interactions. May be evicted later on...
 pub mod error;
 pub mod reqrecv;
+
+#[cfg(test)]
+mod fixtures;
+#[cfg(test)]
+mod mock;
+
 use crate::error::Error;
 use crate::error::Result;
 use bincode;
@@ -15,17 +31,9 @@ use bincode::Options;
 use lazy_static::__Deref;
 use log::debug;
 use log::warn;
-use serde::{Deserialize, Serialize};
-use std::convert::TryInto;
 use std::io::{Read, Write};
 use std::ops::DerefMut;
-
-#[derive(Serialize, Deserialize, Debug)]
-struct SockMsgHeader {
-    _q: u64,
-    msglen: u32,
-    gc_mark: u32,
-}
+use std::sync::Arc;
 
 fn get_encoder() -> impl bincode::config::Options {
     bincode::DefaultOptions::new()
@@ -33,108 +41,32 @@ fn get_encoder() -> impl bincode::config::Options {
         .with_fixint_encoding()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct RawControlPing {
-    _vl_msg_id: u16,
-    client_index: u32,
-    context: u32,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct RawControlPingReply {
-    context: u32,
-    retval: i32,
-    client_index: u32,
-    vpe_pid: u32,
-}
-
-/* This is a pretty hacky way to convince bincode, but oh well... */
-#[derive(Debug, Clone)]
-enum VarLen32 {
-    VarLenData(Vec<u8>),
+/// Read one SockMsgHeader-framed message from a stream and return its payload
+pub(crate) fn read_sock_frame<R: Read + ?Sized>(r: &mut R) -> std::io::Result<Vec<u8>> {
+    read_sock_frame_max(r, protocol::MAX_MSG_LEN)
 }
 
-use serde::ser::{SerializeTuple, Serializer};
-
-impl Serialize for VarLen32 {
-    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let data = match self {
-            VarLen32::VarLenData(v) => v,
-        };
-
-        let len = data.len();
-        let mut seq = serializer.serialize_tuple(len + 4)?;
-        let b0: u8 = (len >> 24) as u8;
-        let b1: u8 = ((len >> 16) & 0xff) as u8;
-        let b2: u8 = ((len >> 8) & 0xff) as u8;
-        let b3: u8 = (len & 0xff) as u8;
-        seq.serialize_element(&b0)?;
-        seq.serialize_element(&b1)?;
-        seq.serialize_element(&b2)?;
-        seq.serialize_element(&b3)?;
-        for b in data {
-            seq.serialize_element(b)?;
+/// Like read_sock_frame(), for a peer which is not trusted: a longer message is an error
+pub(crate) fn read_sock_frame_max<R: Read + ?Sized>(
+    r: &mut R,
+    max_len: usize,
+) -> std::io::Result<Vec<u8>> {
+    let mut decoder = protocol::FrameDecoder::with_max_len(max_len);
+    let invalid = |e| std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+    loop {
+        if let Some(data) = decoder.next_frame().map_err(invalid)? {
+            return Ok(data);
         }
-        seq.end()
+        let mut buf = vec![0; decoder.wanted().map_err(invalid)?];
+        r.read_exact(&mut buf)?;
+        decoder.feed(&buf);
     }
 }
 
-use serde::de::{Deserializer, SeqAccess, Visitor};
-use std::fmt;
-
-impl<'de> Deserialize<'de> for VarLen32 {
-    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        struct VarLen32Visitor;
-        impl<'de> Visitor<'de> for VarLen32Visitor {
-            type Value = VarLen32;
-            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("VarLen32")
-            }
-
-            fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Self::Value, A::Error>
-            where
-                A: SeqAccess<'de>,
-            {
-                let mut res: Vec<u8> = vec![];
-
-                let length: u32 = seq
-                    .next_element()?
-                    .ok_or_else(|| serde::de::Error::invalid_length(1, &self))?;
-
-                for _i in 0..length {
-                    res.push(
-                        seq.next_element()?
-                            .ok_or_else(|| serde::de::Error::invalid_length(1, &self))?,
-                    );
-                }
-
-                return Ok(VarLen32::VarLenData(res));
-            }
-        }
-
-        return Ok(deserializer.deserialize_tuple(1 << 16, VarLen32Visitor)?);
-    }
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct RawCliInband {
-    _vl_msg_id: u16,
-    client_index: u32,
-    context: u32,
-    cmd: VarLen32,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct RawCliInbandReply {
-    context: u32,
-    retval: i32,
-    reply: VarLen32,
+/// Write the payload with a SockMsgHeader in front of it, as a single write
+pub(crate) fn write_sock_frame<W: Write>(w: &mut W, data: &[u8]) -> std::io::Result<()> {
+    w.write_all(&protocol::encode_frame(data))?;
+    w.flush()
 }
 
 pub trait VppApiTransport: Read + Write {
@@ -152,20 +84,15 @@ pub trait VppApiTransport: Read + Write {
     }
 
     fn control_ping(&mut self) -> std::io::Result<u32> {
-        let control_ping_id = self.get_msg_index("control_ping_51077d14").unwrap();
+        let control_ping_id = self.get_msg_index(protocol::CONTROL_PING).unwrap();
         let context = self.get_next_context();
-        let msg = RawControlPing {
-            _vl_msg_id: control_ping_id,
-            client_index: self.get_client_index(),
-            context,
-        };
-        let data = get_encoder().serialize(&msg).unwrap();
-        self.write(&data)?;
+        let data = protocol::control_ping_msg(control_ping_id, self.get_client_index(), context);
+        self.write_all(&data)?;
         Ok(context)
     }
 
     fn skip_to_control_ping_reply(&mut self, _context: u32) -> Result<()> {
-        let control_ping_reply_id = self.get_msg_index("control_ping_reply_f6b0b8ca").unwrap();
+        let control_ping_reply_id = self.get_msg_index(protocol::CONTROL_PING_REPLY).unwrap();
         loop {
             match self.read_one_msg_id_and_msg() {
                 Err(e) => return Err(e),
@@ -180,19 +107,13 @@ pub trait VppApiTransport: Read + Write {
     }
 
     fn run_cli_inband(&mut self, cmd: &str) -> Result<String> {
-        let cli_inband_id = self.get_msg_index("cli_inband_f8377302").unwrap();
-        let cli_inband_reply_id = self.get_msg_index("cli_inband_reply_05879051").unwrap();
+        let cli_inband_id = self.get_msg_index(protocol::CLI_INBAND).unwrap();
+        let cli_inband_reply_id = self.get_msg_index(protocol::CLI_INBAND_REPLY).unwrap();
 
         let context = self.get_next_context();
-        let msg = RawCliInband {
-            _vl_msg_id: cli_inband_id,
-            client_index: self.get_client_index(),
-            context,
-            cmd: VarLen32::VarLenData(cmd.as_bytes().to_vec()),
-        };
-        let data = get_encoder().serialize(&msg).unwrap();
+        let data = protocol::cli_inband_msg(cli_inband_id, self.get_client_index(), context, cmd);
         // println!("Sending data: {:?}", &data);
-        self.write(&data)?;
+        self.write_all(&data)?;
 
         loop {
             match self.read_one_msg_id_and_msg() {
@@ -203,13 +124,7 @@ pub trait VppApiTransport: Read + Write {
                 Ok((msg_id, data)) => {
                     if msg_id == cli_inband_reply_id {
                         // println!("Message: {:?}", &data);
-                        let r: RawCliInbandReply = get_encoder().deserialize(&data).unwrap();
-                        let v = match r.reply {
-                            VarLen32::VarLenData(d) => d,
-                        };
-                        let s = String::from_utf8_lossy(&v);
-                        // println!("Command reply: {}", &s);
-                        return Ok(s.to_string());
+                        return protocol::parse_cli_inband_reply(&data);
                     }
                 }
             }
@@ -218,33 +133,47 @@ pub trait VppApiTransport: Read + Write {
 
     fn dump(&self);
 
-    fn read_one_msg_into(&mut self, data: &mut Vec<u8>) -> Result<()> {
-        let mut header_buf = [0; 16];
-
-        if let Err(e) = self.read_exact(&mut header_buf) {
-            warn!("read invalid header: {:?} err: {:?}", header_buf, e);
-            return Err(Error::InvalidHeader);
-        }
-
-        let hdr: SockMsgHeader = get_encoder().deserialize(&header_buf[..])?;
-        debug!("Got header: {:?}", hdr);
+    /// The sink for the calls made over this transport, instead of the process-wide one
+    fn metrics(&self) -> Option<Arc<dyn instrument::ApiMetrics>> {
+        None
+    }
 
-        match hdr.msglen.try_into() {
-            Ok(msglen) => {
-                if msglen == 0 {
-                    return Err(Error::InvalidMessage);
+    /*
+     * The transports which keep a Protocol read through it instead,
+     * this is for the ones which only have the framed bytes.
+     */
+    fn read_one_msg_into(&mut self, data: &mut Vec<u8>) -> Result<()> {
+        let keepalive_id = self.get_msg_index(protocol::MEMCLNT_KEEPALIVE);
+        loop {
+            let msg = match read_sock_frame(self) {
+                Ok(msg) => msg,
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    debug!("server closed connection");
+                    return Err(Error::ServerClosed);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                    warn!("read invalid header: {:?}", e);
+                    return Err(Error::InvalidHeader);
                 }
-                data.resize(msglen, 0);
-                if let Err(e) = self.read_exact(data) {
-                    warn!("expected {} byte message, got error: {:?}", msglen, e);
-                    return Err(Error::InvalidMessage);
+                Err(e) => return Err(e.into()),
+            };
+            debug!("Got message with msglen {}", msg.len());
+            if msg.is_empty() {
+                return Err(Error::InvalidMessage);
+            }
+            let (msg_id, rest) = protocol::split_msg_id(&msg)?;
+            if Some(msg_id) == keepalive_id {
+                /* memclnt_keepalive: client_index, context */
+                let context = rest
+                    .get(4..8)
+                    .map_or(0, |c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]));
+                if let Some(reply_id) = self.get_msg_index(protocol::MEMCLNT_KEEPALIVE_REPLY) {
+                    self.write_all(&protocol::keepalive_reply_msg(reply_id, context))?;
                 }
-                Ok(())
+                continue;
             }
-            Err(e) => Err(Error::Error(format!(
-                "msg length {} couldn't be converted to usize: {}",
-                hdr.msglen, e
-            ))),
+            *data = msg;
+            return Ok(());
         }
     }
 
@@ -256,11 +185,8 @@ pub trait VppApiTransport: Read + Write {
 
     fn read_one_msg_id_and_msg(&mut self) -> Result<(u16, Vec<u8>)> {
         let ret = self.read_one_msg()?;
-        if ret.len() < 3 {
-            return Err(format!("short read message len: {}  {:x?}", ret.len(), ret).into());
-        }
-        let msg_id: u16 = ((ret[0] as u16) << 8) + (ret[1] as u16);
-        Ok((msg_id, ret[2..].to_vec()))
+        let (msg_id, data) = protocol::split_msg_id(&ret)?;
+        Ok((msg_id, data.to_vec()))
     }
 }
 
@@ -295,6 +221,14 @@ where
     fn dump(&self) {
         self.deref().dump()
     }
+
+    fn metrics(&self) -> Option<Arc<dyn instrument::ApiMetrics>> {
+        self.deref().metrics()
+    }
+
+    fn read_one_msg_into(&mut self, data: &mut Vec<u8>) -> Result<()> {
+        self.deref_mut().read_one_msg_into(data)
+    }
 }
 
 #[cfg(test)]
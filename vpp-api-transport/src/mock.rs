@@ -0,0 +1,753 @@
+/*
+ * A minimal stand-in for the VPP API socket, used by the unit tests.
+ *
+ * It speaks just enough of the AF_UNIX protocol to be useful:
+ * the sockclnt_create handshake with a small message table,
+ * control_ping, cli_inband, sockclnt_delete, sw_interface_dump
+ * with two interfaces, want_interface_events which brings local0 up
+ * and sends the event for it, show_version which gets a
+ * memclnt_keepalive and its reply only once the keepalive has been
+ * answered, and a bare "context + retval" reply
+ * for anything else that has a *_reply. sock_init_shm sets up the
+ * shared memory segment, with memclnt_create/memclnt_delete,
+ * control_ping, cli_inband and show_version served over it as well.
+ */
+use crate::afunix::shm::layout::*;
+use crate::afunix::shm::{page_size, put_msgbuf, take_msgbuf, Queue};
+use crate::afunix::shm::{ShmElemConfig, ShmElemType};
+use crate::protocol::parse_sockclnt_create_reply;
+use crate::{read_sock_frame, write_sock_frame};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::File;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub const MOCK_MESSAGES: &[&str] = &[
+    "sockclnt_create_455fb9c4",
+    "sockclnt_create_reply_35166268",
+    "sockclnt_delete_8ac76db6",
+    "sockclnt_delete_reply_8f38b1ee",
+    "memclnt_keepalive_51077d14",
+    "memclnt_keepalive_reply_e8d4e804",
+    "control_ping_51077d14",
+    "control_ping_reply_f6b0b8ca",
+    "cli_inband_f8377302",
+    "cli_inband_reply_05879051",
+    "show_version_51077d14",
+    "show_version_reply_c919bde1",
+    "sw_interface_dump_aa610c27",
+    "sw_interface_details_17b69fa2",
+    "sw_interface_add_del_address_5803d5c4",
+    "sw_interface_add_del_address_reply_e8d4e804",
+    "want_interface_events_476f5a08",
+    "want_interface_events_reply_e8d4e804",
+    "sw_interface_event_f709f78d",
+    "sock_init_shm_51646d92",
+    "sock_init_shm_reply_e8d4e804",
+    "memclnt_create_9c5e1c2f",
+    "memclnt_create_reply_42ec4560",
+    "memclnt_delete_7e1c04e3",
+    "memclnt_delete_reply_3d3b6312",
+];
+
+/* sockclnt_create and its reply are at fixed ids, the rest follow */
+pub const MOCK_FIRST_MSG_ID: u16 = 15;
+
+static SOCKET_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A unique socket path in the temp dir, for the mock or for whatever sits in front of it
+pub fn temp_socket_path(what: &str) -> String {
+    let mut path = std::env::temp_dir();
+    path.push(format!(
+        "vpp-api-{}-{}-{}.sock",
+        what,
+        std::process::id(),
+        SOCKET_COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+    path.to_str().unwrap().to_string()
+}
+
+fn mock_msg_name(id: u16) -> Option<&'static str> {
+    let idx = id.checked_sub(MOCK_FIRST_MSG_ID)? as usize;
+    MOCK_MESSAGES.get(idx).copied()
+}
+
+fn mock_msg_id(name_prefix: &str) -> Option<u16> {
+    MOCK_MESSAGES
+        .iter()
+        .position(|n| n.rsplit_once('_').map(|(prefix, _)| prefix) == Some(name_prefix))
+        .map(|i| i as u16 + MOCK_FIRST_MSG_ID)
+}
+
+pub struct MockVpp {
+    path: String,
+    received: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockVpp {
+    pub fn start() -> Self {
+        let path = temp_socket_path("mock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let received = Arc::new(Mutex::new(vec![]));
+        let received_clone = received.clone();
+        std::thread::spawn(move || {
+            for (i, stream) in listener.incoming().enumerate() {
+                let stream = match stream {
+                    Ok(s) => s,
+                    Err(_) => break,
+                };
+                let client_index = 0x80000001u32 + i as u32;
+                let received = received_clone.clone();
+                std::thread::spawn(move || mock_serve_client(stream, client_index, received));
+            }
+        });
+        MockVpp { path, received }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Names (without CRC) of all the messages that reached the mock so far
+    pub fn received(&self) -> Vec<String> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockVpp {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn be_u32(data: &[u8], ofs: usize) -> u32 {
+    u32::from_be_bytes(data[ofs..ofs + 4].try_into().unwrap())
+}
+
+/// The sockclnt_create_reply with the mock message table
+pub fn mock_sockclnt_create_reply(context: u32, client_index: u32) -> Vec<u8> {
+    let mut reply = vec![];
+    reply.extend_from_slice(&(MOCK_FIRST_MSG_ID + 1).to_be_bytes());
+    reply.extend_from_slice(&0u32.to_be_bytes());
+    reply.extend_from_slice(&context.to_be_bytes());
+    reply.extend_from_slice(&0i32.to_be_bytes());
+    reply.extend_from_slice(&client_index.to_be_bytes());
+    reply.extend_from_slice(&(MOCK_MESSAGES.len() as u16).to_be_bytes());
+    for (i, n) in MOCK_MESSAGES.iter().enumerate() {
+        let mut entry_name = [0u8; 64];
+        entry_name[..n.len()].copy_from_slice(n.as_bytes());
+        reply.extend_from_slice(&(i as u16 + MOCK_FIRST_MSG_ID).to_be_bytes());
+        reply.extend_from_slice(&entry_name);
+    }
+    reply
+}
+
+/// The sw_interface_details as VPP would send it, with everything but the index, flags and name zeroed
+pub fn mock_sw_interface_details(
+    context: u32,
+    sw_if_index: u32,
+    flags: u32,
+    name: &str,
+) -> Vec<u8> {
+    let mut details = vec![];
+    details.extend_from_slice(&mock_msg_id("sw_interface_details").unwrap().to_be_bytes());
+    details.extend_from_slice(&context.to_be_bytes());
+    details.extend_from_slice(&sw_if_index.to_be_bytes());
+    details.extend_from_slice(&[0u8; 10]);
+    details.extend_from_slice(&flags.to_be_bytes());
+    details.extend_from_slice(&[0u8; 79]);
+    let mut if_name = [0u8; 64];
+    if_name[..name.len()].copy_from_slice(name.as_bytes());
+    details.extend_from_slice(&if_name);
+    details.extend_from_slice(&[0u8; 128]);
+    details
+}
+
+/// The replies which are the same over the socket and the shared memory
+fn mock_simple_reply(name: &str, msg: &[u8], client_index: u32) -> Vec<u8> {
+    let mut reply: Vec<u8> = vec![];
+    match name {
+        "control_ping" => {
+            /* _vl_msg_id, client_index, context */
+            reply.extend_from_slice(&mock_msg_id("control_ping_reply").unwrap().to_be_bytes());
+            reply.extend_from_slice(&msg[6..10]);
+            reply.extend_from_slice(&0i32.to_be_bytes());
+            reply.extend_from_slice(&client_index.to_be_bytes());
+            reply.extend_from_slice(&std::process::id().to_be_bytes());
+        }
+        "cli_inband" => {
+            /* _vl_msg_id, client_index, context, cmd length, cmd */
+            let cmd_len = be_u32(msg, 10) as usize;
+            let cmd = String::from_utf8_lossy(&msg[14..14 + cmd_len]);
+            let out = if cmd == "show version" {
+                "vpp v0.0-mock built by nobody\n".to_string()
+            } else {
+                format!("mock: {}\n", cmd)
+            };
+            reply.extend_from_slice(&mock_msg_id("cli_inband_reply").unwrap().to_be_bytes());
+            reply.extend_from_slice(&msg[6..10]);
+            reply.extend_from_slice(&0i32.to_be_bytes());
+            reply.extend_from_slice(&(out.len() as u32).to_be_bytes());
+            reply.extend_from_slice(out.as_bytes());
+        }
+        n => {
+            /* other dumps get no details, everything else gets a bare reply */
+            if let Some(reply_id) = mock_msg_id(&format!("{}_reply", n)) {
+                reply.extend_from_slice(&reply_id.to_be_bytes());
+                reply.extend_from_slice(&msg[6..10]);
+                reply.extend_from_slice(&0i32.to_be_bytes());
+            }
+        }
+    }
+    reply
+}
+
+fn mock_serve_client(stream: UnixStream, client_index: u32, received: Arc<Mutex<Vec<String>>>) {
+    let shm_stop = Arc::new(AtomicBool::new(false));
+    mock_serve_socket(stream, client_index, received, shm_stop.clone());
+    shm_stop.store(true, Ordering::SeqCst);
+}
+
+fn mock_serve_socket(
+    mut stream: UnixStream,
+    client_index: u32,
+    received: Arc<Mutex<Vec<String>>>,
+    shm_stop: Arc<AtomicBool>,
+) {
+    let mut local0_flags = 0u32;
+    /* the show_version waiting for the memclnt_keepalive_reply */
+    let mut show_version: Option<Vec<u8>> = None;
+    while let Ok(msg) = read_sock_frame(&mut stream) {
+        if msg.len() < 2 {
+            break;
+        }
+        let id = u16::from_be_bytes([msg[0], msg[1]]);
+        let name = match mock_msg_name(id) {
+            Some(n) => n.rsplit_once('_').unwrap().0,
+            None => continue,
+        };
+        received.lock().unwrap().push(name.to_string());
+
+        let mut reply: Vec<u8> = vec![];
+        match name {
+            "sockclnt_create" => {
+                /* _vl_msg_id, context, name[64] */
+                reply = mock_sockclnt_create_reply(be_u32(&msg, 2), client_index);
+            }
+            "control_ping" | "cli_inband" => reply = mock_simple_reply(name, &msg, client_index),
+            "sock_init_shm" => {
+                /* _vl_msg_id, client_index, context, requested_size, nitems, configs */
+                let size = be_u32(&msg, 10) as usize;
+                let elems: Vec<_> = msg[15..]
+                    .chunks(8)
+                    .take(msg[14] as usize)
+                    .filter_map(|c| mock_shm_elem(u64::from_be_bytes(c.try_into().unwrap())))
+                    .collect();
+                let shm = MockShm::new(size, &elems);
+                reply.extend_from_slice(&mock_msg_id("sock_init_shm_reply").unwrap().to_be_bytes());
+                reply.extend_from_slice(&msg[6..10]);
+                reply.extend_from_slice(&0i32.to_be_bytes());
+                if write_sock_frame(&mut stream, &reply).is_err()
+                    || mock_send_fd(&stream, &shm.fd).is_err()
+                {
+                    break;
+                }
+                reply = vec![];
+                let received = received.clone();
+                let stop = shm_stop.clone();
+                std::thread::spawn(move || {
+                    mock_serve_shm(shm, client_index, received, stop);
+                });
+            }
+            "sw_interface_dump" => {
+                let context = be_u32(&msg, 6);
+                for (sw_if_index, flags, name) in
+                    [(0, local0_flags, "local0"), (1, 3, "host-vpp1out")].iter()
+                {
+                    let details = mock_sw_interface_details(context, *sw_if_index, *flags, name);
+                    if write_sock_frame(&mut stream, &details).is_err() {
+                        return;
+                    }
+                }
+            }
+            "want_interface_events" => {
+                reply.extend_from_slice(
+                    &mock_msg_id("want_interface_events_reply")
+                        .unwrap()
+                        .to_be_bytes(),
+                );
+                reply.extend_from_slice(&msg[6..10]);
+                reply.extend_from_slice(&0i32.to_be_bytes());
+                if write_sock_frame(&mut stream, &reply).is_err() {
+                    break;
+                }
+                /* _vl_msg_id, client_index, pid, sw_if_index, flags, deleted */
+                local0_flags = 3;
+                reply = vec![];
+                reply.extend_from_slice(&mock_msg_id("sw_interface_event").unwrap().to_be_bytes());
+                reply.extend_from_slice(&client_index.to_be_bytes());
+                reply.extend_from_slice(&msg[14..18]);
+                reply.extend_from_slice(&0u32.to_be_bytes());
+                reply.extend_from_slice(&local0_flags.to_be_bytes());
+                reply.push(0);
+            }
+            "sockclnt_delete" => {
+                reply.extend_from_slice(
+                    &mock_msg_id("sockclnt_delete_reply").unwrap().to_be_bytes(),
+                );
+                reply.extend_from_slice(&msg[6..10]);
+                reply.extend_from_slice(&0i32.to_be_bytes());
+                let _ = write_sock_frame(&mut stream, &reply);
+                break;
+            }
+            "show_version" => {
+                /* _vl_msg_id, client_index, context: a keepalive first */
+                reply.extend_from_slice(&mock_msg_id("memclnt_keepalive").unwrap().to_be_bytes());
+                reply.extend_from_slice(&client_index.to_be_bytes());
+                reply.extend_from_slice(&msg[6..10]);
+                show_version = Some(msg);
+            }
+            "memclnt_keepalive_reply" => {
+                if let Some(msg) = show_version.take() {
+                    reply = mock_simple_reply("show_version", &msg, client_index);
+                }
+            }
+            n => reply = mock_simple_reply(n, &msg, client_index),
+        }
+        if !reply.is_empty() && write_sock_frame(&mut stream, &reply).is_err() {
+            break;
+        }
+    }
+}
+
+/*
+ * The VPP side of the shared memory (see afunix::shm for the layout).
+ * The segment is mapped here at one address, while the client is told
+ * to map it at another, free one; the pointers inside the segment are
+ * all in the client's terms and get translated here.
+ */
+struct MockShm {
+    fd: File,
+    server: *mut u8,
+    client: u64,
+    size: usize,
+    vpp_queue: u64,
+    /* the VPP rings, smallest first */
+    vlib_rings: Vec<u64>,
+}
+
+unsafe impl Send for MockShm {}
+
+const MOCK_SHM_CLIENT_VA: u64 = 0x2000_0000_0000;
+
+fn mock_shm_elem(v: u64) -> Option<ShmElemConfig> {
+    let b = v.to_ne_bytes();
+    let typ = match b[0] {
+        0 => ShmElemType::VlibRing,
+        1 => ShmElemType::ClientRing,
+        2 => ShmElemType::Queue,
+        _ => return None,
+    };
+    Some(ShmElemConfig {
+        typ,
+        count: u16::from_ne_bytes([b[2], b[3]]),
+        size: u32::from_ne_bytes([b[4], b[5], b[6], b[7]]),
+    })
+}
+
+impl MockShm {
+    fn new(size: usize, elems: &[ShmElemConfig]) -> Self {
+        let name = std::ffi::CString::new("mock-vpp-shm").unwrap();
+        let fd = unsafe { libc::memfd_create(name.as_ptr(), 0) };
+        assert!(fd >= 0);
+        let fd = unsafe { File::from_raw_fd(fd) };
+        fd.set_len(size as u64).unwrap();
+        let map = |prot, flags, fd| unsafe {
+            let p = libc::mmap(std::ptr::null_mut(), size, prot, flags, fd, 0);
+            assert!(p != libc::MAP_FAILED);
+            p
+        };
+        let rw = libc::PROT_READ | libc::PROT_WRITE;
+        let server = map(rw, libc::MAP_SHARED, fd.as_raw_fd()) as *mut u8;
+        /*
+         * A free address range for the client, away from where the kernel
+         * puts the mappings by itself (e.g. the thread stacks in the meantime)
+         */
+        let client = (0..)
+            .map(|i: u64| (MOCK_SHM_CLIENT_VA + (i << 32)) as *mut libc::c_void)
+            .find(|va| unsafe {
+                let flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED_NOREPLACE;
+                let p = libc::mmap(*va, size, libc::PROT_NONE, flags, -1, 0);
+                p != libc::MAP_FAILED && libc::munmap(p, size) == 0 && p == *va
+            })
+            .unwrap();
+        let mut shm = MockShm {
+            fd,
+            server,
+            client: client as u64,
+            size,
+            vpp_queue: 0,
+            vlib_rings: vec![],
+        };
+
+        let page = page_size();
+        let mut next = page + 256;
+        let mut alloc = |len: usize| {
+            let addr = shm.client + next as u64;
+            next = (next + len + 63) & !63;
+            assert!(next <= size);
+            addr
+        };
+        let hdr = alloc(HDR_LEN);
+        let n_rings = |typ| elems.iter().filter(|e| e.typ == typ).count();
+        let vlib_rings = alloc(VEC_HEADER_LEN + n_rings(ShmElemType::VlibRing) * RING_ALLOC_LEN);
+        let client_rings =
+            alloc(VEC_HEADER_LEN + n_rings(ShmElemType::ClientRing) * RING_ALLOC_LEN);
+        let queues: Vec<_> = elems
+            .iter()
+            .map(|e| alloc(Q_DATA + e.count as usize * e.size as usize))
+            .collect();
+
+        shm.write_u64(page as u64 + REGION_USER_CTX as u64, hdr);
+        shm.write_u64(
+            hdr + HDR_VL_RINGS as u64,
+            vlib_rings + VEC_HEADER_LEN as u64,
+        );
+        shm.write_u64(
+            hdr + HDR_CLIENT_RINGS as u64,
+            client_rings + VEC_HEADER_LEN as u64,
+        );
+        for (e, q) in elems.iter().zip(queues) {
+            unsafe { Queue::init(shm.at(q), e.count as u32, e.size).unwrap() };
+            let vec = match e.typ {
+                ShmElemType::Queue => {
+                    shm.vpp_queue = q;
+                    shm.write_u64(hdr + HDR_VL_INPUT_QUEUE as u64, q);
+                    continue;
+                }
+                ShmElemType::VlibRing => {
+                    shm.vlib_rings.push(q);
+                    vlib_rings
+                }
+                ShmElemType::ClientRing => client_rings,
+            };
+            let n = unsafe { *(shm.at(vec) as *const u32) };
+            let ring = shm.at(vec + (VEC_HEADER_LEN + n as usize * RING_ALLOC_LEN) as u64);
+            unsafe {
+                *(ring.add(RING_RP) as *mut u64) = q;
+                *(ring.add(RING_SIZE) as *mut u16) = e.size as u16;
+                *(ring.add(RING_NITEMS) as *mut u16) = e.count;
+                *(shm.at(vec) as *mut u32) = n + 1;
+            }
+        }
+        let mut vlib_rings = std::mem::take(&mut shm.vlib_rings);
+        vlib_rings.sort_by_key(|q| shm.queue(*q).elsize());
+        shm.vlib_rings = vlib_rings;
+
+        shm.write_u64(SSVM_VA as u64, shm.client);
+        shm.write_u64(SSVM_SIZE as u64, size as u64);
+        unsafe { *(server.add(SSVM_READY) as *mut u32) = 1 };
+        shm
+    }
+
+    /// The server view of a client address
+    fn at(&self, addr: u64) -> *mut u8 {
+        assert!(addr >= self.client && addr < self.client + self.size as u64);
+        unsafe { self.server.add((addr - self.client) as usize) }
+    }
+
+    /* offsets below the first page are taken relative to the segment */
+    fn write_u64(&self, addr: u64, v: u64) {
+        let p = if addr < self.client {
+            unsafe { self.server.add(addr as usize) }
+        } else {
+            self.at(addr)
+        };
+        unsafe { *(p as *mut u64) = v };
+    }
+
+    fn queue(&self, addr: u64) -> Queue {
+        unsafe { Queue::from_raw(self.at(addr)) }
+    }
+
+    fn recv(&self) -> Option<Vec<u8>> {
+        let mut elem = [0u8; 8];
+        let q = self.queue(self.vpp_queue);
+        if !q.sub(&mut elem, Some(Duration::from_millis(20))).ok()? {
+            return None;
+        }
+        let addr = u64::from_ne_bytes(elem);
+        let left = self.size - (addr - self.client) as usize;
+        unsafe { take_msgbuf(self.at(addr), left) }.ok()
+    }
+
+    fn send(&self, client_queue: u64, msg: &[u8]) {
+        let data = self
+            .vlib_rings
+            .iter()
+            .find_map(|q| unsafe { put_msgbuf(&self.queue(*q), msg) })
+            .unwrap();
+        let addr = self.client + (data as usize - self.server as usize) as u64;
+        let q = self.queue(client_queue);
+        q.add(&addr.to_ne_bytes(), Some(Duration::from_secs(1)))
+            .unwrap();
+    }
+}
+
+impl Drop for MockShm {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.server as *mut libc::c_void, self.size) };
+    }
+}
+
+fn mock_send_fd(stream: &UnixStream, file: &File) -> std::io::Result<()> {
+    let mut data = *b"fdmsg";
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+    let fd_len = std::mem::size_of::<libc::c_int>() as u32;
+    let mut cmsg_buf = vec![0u8; unsafe { libc::CMSG_SPACE(fd_len) } as usize];
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = cmsg_buf.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = cmsg_buf.len() as _;
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(fd_len) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut libc::c_int, file.as_raw_fd());
+        if libc::sendmsg(stream.as_raw_fd(), &msg, 0) < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/* the shared memory registration gets its own client index */
+fn mock_shm_client_index(client_index: u32) -> u32 {
+    client_index + 0x1000
+}
+
+fn mock_serve_shm(
+    shm: MockShm,
+    client_index: u32,
+    received: Arc<Mutex<Vec<String>>>,
+    stop: Arc<AtomicBool>,
+) {
+    let shm_index = mock_shm_client_index(client_index);
+    let handle = MOCK_SHM_CLIENT_VA + shm_index as u64;
+    let mut client_queue = None;
+    let mut show_version: Option<Vec<u8>> = None;
+    while !stop.load(Ordering::SeqCst) {
+        let msg = match shm.recv() {
+            Some(msg) if msg.len() >= 2 => msg,
+            _ => continue,
+        };
+        let name = match mock_msg_name(u16::from_be_bytes([msg[0], msg[1]])) {
+            Some(n) => n.rsplit_once('_').unwrap().0,
+            None => continue,
+        };
+        received.lock().unwrap().push(name.to_string());
+        let reply = if name == "memclnt_create" {
+            /* _vl_msg_id, context, ctx_quota, input_queue, name, api_versions */
+            client_queue = Some(u64::from_ne_bytes(msg[10..18].try_into().unwrap()));
+            let mut reply = vec![];
+            reply.extend_from_slice(&mock_msg_id("memclnt_create_reply").unwrap().to_be_bytes());
+            reply.extend_from_slice(&msg[2..6]);
+            reply.extend_from_slice(&0i32.to_be_bytes());
+            /* VPP puts the handle and the index in as they are */
+            reply.extend_from_slice(&handle.to_ne_bytes());
+            reply.extend_from_slice(&shm_index.to_ne_bytes());
+            reply.extend_from_slice(&0u64.to_be_bytes());
+            reply
+        } else if name == "memclnt_delete" {
+            /* _vl_msg_id, index, handle, do_cleanup */
+            if msg[2..6] != shm_index.to_ne_bytes() || msg[6..14] != handle.to_ne_bytes() {
+                received
+                    .lock()
+                    .unwrap()
+                    .push("bad_memclnt_delete".to_string());
+            }
+            let mut reply = vec![];
+            reply.extend_from_slice(&mock_msg_id("memclnt_delete_reply").unwrap().to_be_bytes());
+            reply.extend_from_slice(&0i32.to_be_bytes());
+            reply.extend_from_slice(&msg[6..14]);
+            reply
+        } else if name == "memclnt_keepalive_reply" {
+            /* _vl_msg_id, context, retval */
+            match show_version.take() {
+                Some(msg) => mock_simple_reply("show_version", &msg, shm_index),
+                None => vec![],
+            }
+        } else if name == "show_version" {
+            /* _vl_msg_id, client_index, context: a keepalive first */
+            let mut reply = vec![];
+            reply.extend_from_slice(&mock_msg_id("memclnt_keepalive").unwrap().to_be_bytes());
+            reply.extend_from_slice(&shm_index.to_ne_bytes());
+            reply.extend_from_slice(&msg[6..10]);
+            show_version = Some(msg);
+            reply
+        } else {
+            /* _vl_msg_id, client_index, ... */
+            if msg[2..6] != shm_index.to_ne_bytes() {
+                received
+                    .lock()
+                    .unwrap()
+                    .push("bad_client_index".to_string());
+            }
+            mock_simple_reply(name, &msg, shm_index)
+        };
+        if let (Some(q), false) = (client_queue, reply.is_empty()) {
+            shm.send(q, &reply);
+        }
+    }
+}
+
+/// Connect to a mock (or something in front of it) and do the sockclnt_create exchange
+pub fn mock_client_connect(path: &str) -> (UnixStream, HashMap<String, u16>) {
+    let mut stream = UnixStream::connect(path).unwrap();
+    let mut msg = vec![];
+    msg.extend_from_slice(&MOCK_FIRST_MSG_ID.to_be_bytes());
+    msg.extend_from_slice(&124u32.to_be_bytes());
+    msg.extend_from_slice(&[0u8; 64]);
+    write_sock_frame(&mut stream, &msg).unwrap();
+    let reply = read_sock_frame(&mut stream).unwrap();
+    let (_hdr, table) = parse_sockclnt_create_reply(&reply).unwrap();
+    (stream, table)
+}
+
+/*
+ * A stand-in for cli.sock: the telnet negotiation, a banner with some
+ * ANSI sequences, a "vpp# " prompt, echo of the input, and a paged
+ * output for "show lines".
+ */
+pub struct MockCli {
+    path: String,
+    negotiation: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl MockCli {
+    pub fn start() -> Self {
+        let path = temp_socket_path("mock-cli");
+        let listener = UnixListener::bind(&path).unwrap();
+        let negotiation = Arc::new(Mutex::new(vec![]));
+        let negotiation_clone = negotiation.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(s) => s,
+                    Err(_) => break,
+                };
+                let negotiation = negotiation_clone.clone();
+                std::thread::spawn(move || mock_serve_cli(stream, negotiation));
+            }
+        });
+        MockCli { path, negotiation }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The IAC sequences received from the clients so far
+    pub fn negotiation(&self) -> Vec<Vec<u8>> {
+        self.negotiation.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockCli {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn mock_cli_read_byte(stream: &mut UnixStream) -> Option<u8> {
+    use std::io::Read;
+    let mut b = [0u8; 1];
+    match stream.read(&mut b) {
+        Ok(1) => Some(b[0]),
+        _ => None,
+    }
+}
+
+/// The next text byte from the client, recording and answering the IAC sequences on the way
+fn mock_cli_next_char(stream: &mut UnixStream, negotiation: &Mutex<Vec<Vec<u8>>>) -> Option<u8> {
+    use crate::clisock::telnet::{IAC, OPT_TTYPE, SB, SE, WILL};
+    use std::io::Write;
+    loop {
+        let b = mock_cli_read_byte(stream)?;
+        if b != IAC {
+            return Some(b);
+        }
+        let mut seq = vec![IAC, mock_cli_read_byte(stream)?];
+        if seq[1] == SB {
+            while !seq.ends_with(&[IAC, SE]) {
+                seq.push(mock_cli_read_byte(stream)?);
+            }
+        } else {
+            seq.push(mock_cli_read_byte(stream)?);
+        }
+        if seq == [IAC, WILL, OPT_TTYPE] {
+            /* IAC SB TTYPE SEND IAC SE */
+            stream.write_all(&[IAC, SB, OPT_TTYPE, 1, IAC, SE]).ok()?;
+        }
+        negotiation.lock().unwrap().push(seq);
+    }
+}
+
+fn mock_serve_cli(mut stream: UnixStream, negotiation: Arc<Mutex<Vec<Vec<u8>>>>) {
+    use crate::clisock::telnet::{DO, IAC, OPT_ECHO, OPT_NAWS, OPT_SGA, OPT_TTYPE, WILL};
+    use std::io::Write;
+    let prompt = "vpp# ";
+    let mut hello = vec![
+        IAC, WILL, OPT_ECHO, IAC, WILL, OPT_SGA, IAC, DO, OPT_TTYPE, IAC, DO, OPT_NAWS,
+    ];
+    hello.extend_from_slice(b"\x1b[0m\x1b[1m    _______    _        _   _____  ___ \x1b[0m\r\n");
+    hello.extend_from_slice(b"     mock VPP\r\n\r\n");
+    hello.extend_from_slice(prompt.as_bytes());
+    if stream.write_all(&hello).is_err() {
+        return;
+    }
+
+    let mut line = vec![];
+    while let Some(b) = mock_cli_next_char(&mut stream, &negotiation) {
+        if b == b'\r' {
+            continue;
+        }
+        if b != b'\n' {
+            line.push(b);
+            if stream.write_all(&[b]).is_err() {
+                return;
+            }
+            continue;
+        }
+        let cmd = String::from_utf8_lossy(&line).trim().to_string();
+        line.clear();
+        let mut out = "\r\n".to_string();
+        match &cmd[..] {
+            "" => {}
+            "quit" => return,
+            "show version" => out.push_str("vpp v0.0-mock built by nobody\r\n"),
+            "show lines" => {
+                out.push_str("line 1\r\nline 2\r\nline 3\r\n-- more -- (1-3/5)");
+                if stream.write_all(out.as_bytes()).is_err() {
+                    return;
+                }
+                while mock_cli_next_char(&mut stream, &negotiation) != Some(b' ') {}
+                out = "\r                  \rline 4\r\nline 5\r\n".to_string();
+            }
+            c => out.push_str(&format!("unknown input `{}'\r\n", c)),
+        }
+        out.push_str(prompt);
+        if stream.write_all(out.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
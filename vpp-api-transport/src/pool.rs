@@ -0,0 +1,324 @@
+/*
+ * A pool of AF_UNIX connections to the same api.sock, so that
+ * the request handlers can run in parallel without sharing a socket.
+ *
+ * Each connection has its own client name ("<prefix>-<n>") and so its
+ * own client index. A checked-out connection goes back to the pool
+ * when the guard is dropped, unless it has been marked as broken;
+ * the idle connections are checked with a control_ping on checkout,
+ * and the broken ones are replaced by new ones.
+ */
+use crate::afunix;
+use crate::error::{Error, Result};
+use crate::VppApiTransport;
+use log::{debug, warn};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    pub sock_path: String,
+    /// The connections are named "<name_prefix>-<n>"
+    pub name_prefix: String,
+    pub max_size: usize,
+    /// How long get() waits for a connection if all of them are in use
+    pub checkout_timeout: Duration,
+    /// Check the idle connections with a control_ping before handing them out
+    pub health_check: bool,
+    /// How long to wait for the control_ping_reply
+    pub health_check_timeout: Duration,
+}
+
+impl PoolConfig {
+    pub fn new(sock_path: &str, name_prefix: &str, max_size: usize) -> Self {
+        PoolConfig {
+            sock_path: sock_path.to_owned(),
+            name_prefix: name_prefix.to_owned(),
+            max_size,
+            checkout_timeout: Duration::from_secs(5),
+            health_check: true,
+            health_check_timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Default)]
+struct PoolState {
+    idle: Vec<afunix::Transport>,
+    /* idle + checked out + being connected */
+    total: usize,
+    next_id: usize,
+}
+
+struct PoolInner {
+    config: PoolConfig,
+    state: Mutex<PoolState>,
+    returned: Condvar,
+}
+
+impl PoolInner {
+    fn put_back(&self, t: afunix::Transport) {
+        self.state.lock().unwrap().idle.push(t);
+        self.returned.notify_one();
+    }
+
+    fn release_slot(&self) {
+        self.state.lock().unwrap().total -= 1;
+        self.returned.notify_one();
+    }
+}
+
+#[derive(Clone)]
+pub struct Pool {
+    inner: Arc<PoolInner>,
+}
+
+/// The answer to a control_ping within the timeout
+pub fn health_check(t: &mut afunix::Transport, timeout: Duration) -> Result<()> {
+    t.set_read_timeout(Some(timeout))?;
+    let context = t.control_ping()?;
+    let res = t.skip_to_control_ping_reply(context);
+    t.set_read_timeout(None)?;
+    res
+}
+
+impl Pool {
+    pub fn new(config: PoolConfig) -> Self {
+        Pool {
+            inner: Arc::new(PoolInner {
+                config,
+                state: Mutex::new(PoolState::default()),
+                returned: Condvar::new(),
+            }),
+        }
+    }
+
+    pub fn config(&self) -> &PoolConfig {
+        &self.inner.config
+    }
+
+    /// The number of the idle connections and of all of them
+    pub fn status(&self) -> (usize, usize) {
+        let state = self.inner.state.lock().unwrap();
+        (state.idle.len(), state.total)
+    }
+
+    fn connect(&self, id: usize) -> Result<afunix::Transport> {
+        let config = &self.inner.config;
+        let mut t = afunix::Transport::new(&config.sock_path);
+        let name = format!("{}-{}", &config.name_prefix, id);
+        t.connect(&name, None, 256)?;
+        debug!(
+            "Pool connection {} has client index {}",
+            &name,
+            t.get_client_index()
+        );
+        Ok(t)
+    }
+
+    /// Check out a connection: an idle one which passes the health check, or a new one
+    pub fn get(&self) -> Result<PooledTransport> {
+        let config = &self.inner.config;
+        let deadline = Instant::now() + config.checkout_timeout;
+        let mut state = self.inner.state.lock().unwrap();
+        loop {
+            if let Some(mut t) = state.idle.pop() {
+                drop(state);
+                if config.health_check {
+                    if let Err(e) = health_check(&mut t, config.health_check_timeout) {
+                        warn!("Replacing a broken pool connection: {:?}", e);
+                        drop(t);
+                        self.inner.release_slot();
+                        state = self.inner.state.lock().unwrap();
+                        continue;
+                    }
+                }
+                return Ok(self.guard(t));
+            }
+            if state.total < config.max_size {
+                state.total += 1;
+                state.next_id += 1;
+                let id = state.next_id;
+                drop(state);
+                return match self.connect(id) {
+                    Ok(t) => Ok(self.guard(t)),
+                    Err(e) => {
+                        self.inner.release_slot();
+                        Err(e)
+                    }
+                };
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::Error(format!(
+                    "no connection available in the pool of {} in {:?}",
+                    config.max_size, config.checkout_timeout
+                )));
+            }
+            state = self
+                .inner
+                .returned
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    /// Health-check all the idle connections and drop the broken ones, returns how many were dropped
+    pub fn check_idle(&self) -> usize {
+        let idle = std::mem::take(&mut self.inner.state.lock().unwrap().idle);
+        let mut dropped = 0;
+        for mut t in idle {
+            match health_check(&mut t, self.inner.config.health_check_timeout) {
+                Ok(()) => self.inner.put_back(t),
+                Err(e) => {
+                    warn!("Dropping a broken pool connection: {:?}", e);
+                    dropped += 1;
+                    drop(t);
+                    self.inner.release_slot();
+                }
+            }
+        }
+        dropped
+    }
+
+    fn guard(&self, t: afunix::Transport) -> PooledTransport {
+        PooledTransport {
+            transport: Some(t),
+            pool: self.inner.clone(),
+            broken: false,
+        }
+    }
+}
+
+/// A checked-out connection, which goes back to the pool when dropped
+pub struct PooledTransport {
+    transport: Option<afunix::Transport>,
+    pool: Arc<PoolInner>,
+    broken: bool,
+}
+
+impl PooledTransport {
+    /// Do not put this connection back, e.g. after an IO error or with a reply left unread
+    pub fn mark_broken(&mut self) {
+        self.broken = true;
+    }
+}
+
+impl Deref for PooledTransport {
+    type Target = afunix::Transport;
+    fn deref(&self) -> &afunix::Transport {
+        self.transport.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledTransport {
+    fn deref_mut(&mut self) -> &mut afunix::Transport {
+        self.transport.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledTransport {
+    fn drop(&mut self) {
+        let t = self.transport.take().unwrap();
+        if self.broken || !t.is_connected() {
+            drop(t);
+            self.pool.release_slot();
+        } else {
+            self.pool.put_back(t);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockVpp;
+    use crate::protocol::control_ping_msg;
+    use std::io::Write;
+
+    fn mock_pool(mock: &MockVpp, max_size: usize) -> Pool {
+        let mut config = PoolConfig::new(mock.path(), "pool-test", max_size);
+        config.checkout_timeout = Duration::from_millis(200);
+        Pool::new(config)
+    }
+
+    fn count(mock: &MockVpp, name: &str) -> usize {
+        mock.received().iter().filter(|n| *n == name).count()
+    }
+
+    #[test]
+    fn test_pool_checkout_and_return() {
+        let mock = MockVpp::start();
+        let pool = mock_pool(&mock, 2);
+        let t1 = pool.get().unwrap();
+        let t2 = pool.get().unwrap();
+        assert_ne!(t1.get_client_index(), t2.get_client_index());
+        assert_eq!(pool.status(), (0, 2));
+        assert!(pool.get().is_err());
+
+        let index1 = t1.get_client_index();
+        drop(t1);
+        assert_eq!(pool.status(), (1, 2));
+        let mut t3 = pool.get().unwrap();
+        assert_eq!(t3.get_client_index(), index1);
+        assert!(t3
+            .run_cli_inband("show version")
+            .unwrap()
+            .starts_with("vpp "));
+        assert_eq!(count(&mock, "sockclnt_create"), 2);
+        assert_eq!(count(&mock, "control_ping"), 1);
+    }
+
+    #[test]
+    fn test_pool_parallel() {
+        let mock = MockVpp::start();
+        let pool = mock_pool(&mock, 3);
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let pool = pool.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        let mut t = pool.get().unwrap();
+                        let out = t.run_cli_inband("show version").unwrap();
+                        assert!(out.starts_with("vpp "));
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        let (idle, total) = pool.status();
+        assert_eq!(idle, total);
+        assert!(total <= 3);
+        assert_eq!(count(&mock, "sockclnt_create"), total);
+    }
+
+    #[test]
+    fn test_pool_replaces_broken() {
+        let mock = MockVpp::start();
+        let pool = mock_pool(&mock, 1);
+
+        let mut t = pool.get().unwrap();
+        t.mark_broken();
+        drop(t);
+        assert_eq!(pool.status(), (0, 0));
+
+        /* VPP drops the client after the sockclnt_delete, the health check notices it */
+        let mut t = pool.get().unwrap();
+        let delete_id = t.get_msg_index("sockclnt_delete_8ac76db6").unwrap();
+        let msg = control_ping_msg(delete_id, t.get_client_index(), 1);
+        t.write_all(&msg).unwrap();
+        drop(t);
+        assert_eq!(pool.status(), (1, 1));
+        let mut t = pool.get().unwrap();
+        assert!(t.run_cli_inband("show version").is_ok());
+        assert_eq!(count(&mock, "sockclnt_create"), 3);
+        drop(t);
+
+        assert_eq!(pool.check_idle(), 0);
+        assert_eq!(pool.status(), (1, 1));
+    }
+}
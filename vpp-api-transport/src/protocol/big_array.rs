@@ -65,5 +65,5 @@ macro_rules! big_array {
 }
 
 big_array! {
-    64,
+    64, 256,
 }
@@ -0,0 +1,889 @@
+/*
+ * The sans-IO core of the VPP API socket protocol.
+ *
+ * Nothing in here touches a socket: the bytes read from the wire are
+ * fed into a Protocol, and what comes out are the decoded messages,
+ * the handshake state, the completion of the outstanding requests and
+ * the keepalive replies. Whatever has to be sent is collected in the
+ * outgoing buffer, and it is up to the caller to get it onto the wire,
+ * blocking or not.
+ *
+ * The message layouts this relies on:
+ *   request:  _vl_msg_id: u16, client_index: u32, context: u32, ...
+ *   reply:    _vl_msg_id: u16, context: u32, ...
+ */
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryInto;
+use std::io::{Read, Write};
+use vpp_api_encoding::wire;
+
+pub(crate) mod big_array;
+use big_array::BigArray;
+
+pub const SOCK_MSG_HEADER_LEN: usize = 16;
+
+/* the longest message taken from the wire, a longer one is an error */
+pub const MAX_MSG_LEN: usize = 64 << 20;
+
+/* sockclnt_create and its reply are sent before the message table is known */
+pub const SOCKCLNT_CREATE_ID: u16 = 15;
+
+pub const CONTROL_PING: &str = "control_ping_51077d14";
+pub const CONTROL_PING_REPLY: &str = "control_ping_reply_f6b0b8ca";
+pub const CLI_INBAND: &str = "cli_inband_f8377302";
+pub const CLI_INBAND_REPLY: &str = "cli_inband_reply_05879051";
+pub const SOCKCLNT_DELETE: &str = "sockclnt_delete_8ac76db6";
+pub const SOCKCLNT_DELETE_REPLY: &str = "sockclnt_delete_reply_8f38b1ee";
+pub const MEMCLNT_KEEPALIVE: &str = "memclnt_keepalive_51077d14";
+pub const MEMCLNT_KEEPALIVE_REPLY: &str = "memclnt_keepalive_reply_e8d4e804";
+
+#[derive(Serialize, Deserialize, Debug)]
+struct SockMsgHeader {
+    _q: u64,
+    msglen: u32,
+    gc_mark: u32,
+}
+
+/// Prepend the SockMsgHeader to the message
+pub fn encode_frame(msg: &[u8]) -> Vec<u8> {
+    let hdr = SockMsgHeader {
+        _q: 0,
+        msglen: msg.len() as u32,
+        gc_mark: 0,
+    };
+    let mut frame = wire::to_bytes(&hdr).unwrap();
+    frame.extend_from_slice(msg);
+    frame
+}
+
+/// Decode the SockMsgHeader at the start of the buffer and return the message length
+pub fn decode_frame_header(buf: &[u8]) -> Result<usize> {
+    if buf.len() < SOCK_MSG_HEADER_LEN {
+        return Err(Error::InvalidHeader);
+    }
+    let hdr: SockMsgHeader = wire::from_bytes(&buf[0..SOCK_MSG_HEADER_LEN])?;
+    hdr.msglen.try_into().map_err(|e| {
+        Error::Error(format!(
+            "msg length {} couldn't be converted to usize: {}",
+            hdr.msglen, e
+        ))
+    })
+}
+
+/// Split a message into its id and the rest
+pub fn split_msg_id(msg: &[u8]) -> Result<(u16, &[u8])> {
+    if msg.len() < 3 {
+        return Err(format!("short read message len: {}  {:x?}", msg.len(), msg).into());
+    }
+    Ok((u16::from_be_bytes([msg[0], msg[1]]), &msg[2..]))
+}
+
+/// Reassembles the SockMsgHeader-framed messages from the bytes as they arrive
+#[derive(Debug)]
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+    max_len: usize,
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        FrameDecoder::with_max_len(MAX_MSG_LEN)
+    }
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// A decoder which fails on the messages longer than max_len, for the peers which are not trusted
+    pub fn with_max_len(max_len: usize) -> Self {
+        FrameDecoder {
+            buf: vec![],
+            max_len,
+        }
+    }
+
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /* the header is complete */
+    fn frame_len(&self) -> Result<usize> {
+        let msglen = decode_frame_header(&self.buf)?;
+        if msglen > self.max_len {
+            return Err(Error::Error(format!(
+                "msg length {} is over {}",
+                msglen, self.max_len
+            )));
+        }
+        Ok(SOCK_MSG_HEADER_LEN + msglen)
+    }
+
+    /// How many more bytes are needed to complete the current header or message
+    pub fn wanted(&self) -> Result<usize> {
+        if self.buf.len() < SOCK_MSG_HEADER_LEN {
+            return Ok(SOCK_MSG_HEADER_LEN - self.buf.len());
+        }
+        /* an oversized header stays in the buffer, and keeps failing */
+        Ok(self.frame_len()?.saturating_sub(self.buf.len()))
+    }
+
+    /// The next complete message, without the header, if there is one
+    pub fn next_frame(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.buf.len() < SOCK_MSG_HEADER_LEN {
+            return Ok(None);
+        }
+        let frame_len = self.frame_len()?;
+        if self.buf.len() < frame_len {
+            return Ok(None);
+        }
+        let msg = self.buf[SOCK_MSG_HEADER_LEN..frame_len].to_vec();
+        self.buf.drain(0..frame_len);
+        Ok(Some(msg))
+    }
+
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+}
+
+type ArrayOf64U8 = [u8; 64];
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MsgSockClntCreate {
+    _vl_msg_id: u16,
+    context: u32,
+    #[serde(with = "BigArray")]
+    name: ArrayOf64U8,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MsgSockClntCreateReplyHdr {
+    _vl_msg_id: u16,
+    client_index: u32,
+    context: u32,
+    response: i32,
+    pub(crate) index: u32,
+    pub(crate) count: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MsgSockClntCreateReplyEntry {
+    index: u16,
+    #[serde(with = "BigArray")]
+    name: ArrayOf64U8,
+}
+
+/// Encode the sockclnt_create message, which is sent before the message table is known
+pub fn sockclnt_create_msg(name: &str) -> Vec<u8> {
+    /* FIXME: this is ugly and odd, there's gotta be a better way... */
+    let mut name1 = name.to_string();
+    let mut name_a: [u8; 64] = [0; 64];
+    while name1.len() < name_a.len() {
+        name1.push('\0');
+    }
+    name_a.copy_from_slice(name1.as_bytes());
+
+    let sockclnt_create = MsgSockClntCreate {
+        _vl_msg_id: SOCKCLNT_CREATE_ID,
+        context: 124,
+        name: name_a,
+    };
+
+    wire::to_bytes(&sockclnt_create).unwrap()
+}
+
+/// Decode the sockclnt_create_reply: the header and the message name to id table
+pub fn parse_sockclnt_create_reply(
+    buf: &[u8],
+) -> Result<(MsgSockClntCreateReplyHdr, HashMap<String, u16>)> {
+    const HDR_SIZE: usize = 20;
+    const ENTRY_SIZE: usize = 66; /* MsgSockClntCreateReplyEntry */
+
+    if buf.len() < HDR_SIZE {
+        return Err(Error::InvalidMessage);
+    }
+    let hdr: MsgSockClntCreateReplyHdr = wire::from_bytes(&buf[0..HDR_SIZE])?;
+    let mut message_name_to_id = HashMap::new();
+    let mut i = 0;
+    while i < hdr.count as usize {
+        let ofs1 = HDR_SIZE + i * ENTRY_SIZE;
+        let ofs2 = ofs1 + ENTRY_SIZE;
+        if buf.len() < ofs2 {
+            return Err(Error::InvalidMessage);
+        }
+
+        let msg: MsgSockClntCreateReplyEntry = wire::from_bytes(&buf[ofs1..ofs2])?;
+        let msg_name_trailing_zero = String::from_utf8_lossy(&msg.name);
+        let msg_name = msg_name_trailing_zero.trim_end_matches("\u{0}");
+        message_name_to_id.insert(msg_name.into(), msg.index);
+        i += 1;
+    }
+    Ok((hdr, message_name_to_id))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RawControlPing {
+    _vl_msg_id: u16,
+    client_index: u32,
+    context: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RawKeepaliveReply {
+    _vl_msg_id: u16,
+    context: u32,
+    retval: i32,
+}
+
+/// Encode the memclnt_keepalive_reply to a memclnt_keepalive with this context
+pub fn keepalive_reply_msg(msg_id: u16, context: u32) -> Vec<u8> {
+    let msg = RawKeepaliveReply {
+        _vl_msg_id: msg_id,
+        context,
+        retval: 0,
+    };
+    wire::to_bytes(&msg).unwrap()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RawSockClntDelete {
+    _vl_msg_id: u16,
+    client_index: u32,
+    context: u32,
+    index: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RawCliInband<'a> {
+    _vl_msg_id: u16,
+    client_index: u32,
+    context: u32,
+    cmd: &'a str,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RawCliInbandReply<'a> {
+    context: u32,
+    retval: i32,
+    reply: &'a [u8],
+}
+
+/// Encode a control_ping, or any other request which has nothing past the context
+pub fn control_ping_msg(msg_id: u16, client_index: u32, context: u32) -> Vec<u8> {
+    let msg = RawControlPing {
+        _vl_msg_id: msg_id,
+        client_index,
+        context,
+    };
+    wire::to_bytes(&msg).unwrap()
+}
+
+/// Encode a sockclnt_delete of our own registration
+pub fn sockclnt_delete_msg(msg_id: u16, client_index: u32, context: u32) -> Vec<u8> {
+    let msg = RawSockClntDelete {
+        _vl_msg_id: msg_id,
+        client_index,
+        context,
+        index: client_index,
+    };
+    wire::to_bytes(&msg).unwrap()
+}
+
+pub fn cli_inband_msg(msg_id: u16, client_index: u32, context: u32, cmd: &str) -> Vec<u8> {
+    let msg = RawCliInband {
+        _vl_msg_id: msg_id,
+        client_index,
+        context,
+        cmd,
+    };
+    wire::to_bytes(&msg).unwrap()
+}
+
+/// Decode the cli_inband_reply (without the message id) into the CLI output
+pub fn parse_cli_inband_reply(data: &[u8]) -> Result<String> {
+    let r: RawCliInbandReply = wire::from_bytes(data)?;
+    Ok(String::from_utf8_lossy(r.reply).to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Idle,
+    Handshaking,
+    Connected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestKind {
+    /// The first message with the same context completes the request
+    Single,
+    /// Everything with the same context up to the control_ping_reply
+    Dump,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The sockclnt_create_reply has arrived, the message table is known
+    Connected { client_index: u32 },
+    /// The reply to a Single request, which is not pending anymore
+    Reply {
+        context: u32,
+        msg_id: u16,
+        data: Vec<u8>,
+    },
+    /// One of the messages in response to a Dump request
+    Details {
+        context: u32,
+        msg_id: u16,
+        data: Vec<u8>,
+    },
+    /// The control_ping_reply which completes a Dump request
+    DumpDone { context: u32 },
+    /// VPP has checked if we are alive, the reply is in the outgoing buffer
+    Keepalive,
+    /// Anything that is not for a pending request
+    Message { msg_id: u16, data: Vec<u8> },
+}
+
+/// The protocol state of one API socket connection, as seen by the client
+#[derive(Debug)]
+pub struct Protocol {
+    state: State,
+    decoder: FrameDecoder,
+    outgoing: Vec<u8>,
+    events: VecDeque<Event>,
+    message_name_to_id: HashMap<String, u16>,
+    message_max_index: u16,
+    client_index: u32,
+    next_context: u32,
+    pending: HashMap<u32, RequestKind>,
+    /* watching a connection made by someone else, see passive() */
+    passive: bool,
+}
+
+impl Default for Protocol {
+    fn default() -> Self {
+        Protocol {
+            state: State::Idle,
+            decoder: FrameDecoder::new(),
+            outgoing: vec![],
+            events: VecDeque::new(),
+            message_name_to_id: HashMap::new(),
+            message_max_index: 0,
+            client_index: 0,
+            next_context: 1,
+            pending: HashMap::new(),
+            passive: false,
+        }
+    }
+}
+
+impl Protocol {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /*
+     * A Protocol for the connection of another client, e.g. in the proxy:
+     * it waits for the sockclnt_create_reply without sending anything,
+     * leaves the keepalives to the client and passes every message on as
+     * Message, the sockclnt_create_reply included.
+     */
+    pub fn passive() -> Self {
+        Protocol {
+            state: State::Handshaking,
+            passive: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn client_index(&self) -> u32 {
+        self.client_index
+    }
+
+    pub fn message_max_index(&self) -> u16 {
+        self.message_max_index
+    }
+
+    pub fn message_table(&self) -> &HashMap<String, u16> {
+        &self.message_name_to_id
+    }
+
+    pub fn get_msg_index(&self, name: &str) -> Option<u16> {
+        self.message_name_to_id.get(name).copied()
+    }
+
+    pub fn next_context(&mut self) -> u32 {
+        let context = self.next_context;
+        self.next_context = self.next_context.wrapping_add(1).max(1);
+        context
+    }
+
+    /// Queue the sockclnt_create with this client name
+    pub fn start_handshake(&mut self, name: &str) -> Result<()> {
+        if self.state != State::Idle {
+            return Err(format!("handshake in state {:?}", self.state).into());
+        }
+        self.outgoing
+            .extend_from_slice(&encode_frame(&sockclnt_create_msg(name)));
+        self.state = State::Handshaking;
+        Ok(())
+    }
+
+    /// Queue a message as-is, the caller keeps track of what comes back
+    pub fn send_msg(&mut self, msg: &[u8]) {
+        self.outgoing.extend_from_slice(&encode_frame(msg));
+    }
+
+    /// Expect the response to a request already sent with this context
+    pub fn add_pending(&mut self, context: u32, kind: RequestKind) {
+        self.pending.insert(context, kind);
+    }
+
+    /// Queue the request with the header filled in, followed by a control_ping for a Dump
+    pub fn send_request(&mut self, msg_id: u16, body: &[u8], kind: RequestKind) -> Result<u32> {
+        if self.state != State::Connected {
+            return Err(format!("sending a request in state {:?}", self.state).into());
+        }
+        let context = self.next_context();
+        let mut msg = control_ping_msg(msg_id, self.client_index, context);
+        msg.extend_from_slice(body);
+        self.send_msg(&msg);
+        if kind == RequestKind::Dump {
+            let ping_id = self
+                .get_msg_index(CONTROL_PING)
+                .ok_or_else(|| Error::Error(format!("{} is not in the table", CONTROL_PING)))?;
+            self.send_msg(&control_ping_msg(ping_id, self.client_index, context));
+        }
+        self.add_pending(context, kind);
+        Ok(context)
+    }
+
+    pub fn is_pending(&self, context: u32) -> bool {
+        self.pending.contains_key(&context)
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// How many bytes to read to complete the next message, for the callers which must not overread
+    pub fn wanted(&self) -> Result<usize> {
+        self.decoder.wanted()
+    }
+
+    /// Feed the bytes received from the socket and process all the complete messages
+    pub fn receive(&mut self, data: &[u8]) -> Result<()> {
+        self.decoder.feed(data);
+        while let Some(msg) = self.decoder.next_frame()? {
+            self.handle_msg(&msg)?;
+        }
+        Ok(())
+    }
+
+    pub fn poll_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    /// The next message with its id, for the callers which match the replies themselves
+    pub fn next_msg(&mut self) -> Option<Vec<u8>> {
+        while let Some(event) = self.poll_event() {
+            match event {
+                Event::Reply { msg_id, data, .. }
+                | Event::Details { msg_id, data, .. }
+                | Event::Message { msg_id, data } => {
+                    let mut msg = msg_id.to_be_bytes().to_vec();
+                    msg.extend_from_slice(&data);
+                    return Some(msg);
+                }
+                Event::Connected { .. } | Event::DumpDone { .. } | Event::Keepalive => {}
+            }
+        }
+        None
+    }
+
+    pub fn wants_write(&self) -> bool {
+        !self.outgoing.is_empty()
+    }
+
+    /// The bytes to be written to the socket, the buffer is empty afterwards
+    pub fn take_outgoing(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    fn handle_msg(&mut self, msg: &[u8]) -> Result<()> {
+        match self.state {
+            State::Idle => Err(format!("message before the handshake: {:x?}", msg).into()),
+            State::Handshaking => {
+                let (hdr, message_name_to_id) = parse_sockclnt_create_reply(msg)?;
+                self.client_index = hdr.index;
+                self.message_max_index = hdr.count;
+                self.message_name_to_id = message_name_to_id;
+                self.state = State::Connected;
+                self.events.push_back(Event::Connected {
+                    client_index: self.client_index,
+                });
+                if self.passive {
+                    let (msg_id, data) = split_msg_id(msg)?;
+                    self.events.push_back(Event::Message {
+                        msg_id,
+                        data: data.to_vec(),
+                    });
+                }
+                Ok(())
+            }
+            State::Connected => {
+                let (msg_id, data) = split_msg_id(msg)?;
+                if !self.passive && Some(msg_id) == self.get_msg_index(MEMCLNT_KEEPALIVE) {
+                    self.answer_keepalive(data);
+                    return Ok(());
+                }
+                let data = data.to_vec();
+                let context = match data.get(0..4) {
+                    Some(c) => u32::from_be_bytes(c.try_into().unwrap()),
+                    None => {
+                        self.events.push_back(Event::Message { msg_id, data });
+                        return Ok(());
+                    }
+                };
+                let event = match self.pending.get(&context) {
+                    Some(RequestKind::Single) => {
+                        self.pending.remove(&context);
+                        Event::Reply {
+                            context,
+                            msg_id,
+                            data,
+                        }
+                    }
+                    Some(RequestKind::Dump)
+                        if Some(msg_id) == self.get_msg_index(CONTROL_PING_REPLY) =>
+                    {
+                        self.pending.remove(&context);
+                        Event::DumpDone { context }
+                    }
+                    Some(RequestKind::Dump) => Event::Details {
+                        context,
+                        msg_id,
+                        data,
+                    },
+                    None => Event::Message { msg_id, data },
+                };
+                self.events.push_back(event);
+                Ok(())
+            }
+        }
+    }
+
+    fn answer_keepalive(&mut self, data: &[u8]) {
+        /* memclnt_keepalive: client_index, context */
+        let context = match data.get(4..8) {
+            Some(c) => u32::from_be_bytes(c.try_into().unwrap()),
+            None => 0,
+        };
+        if let Some(reply_id) = self.get_msg_index(MEMCLNT_KEEPALIVE_REPLY) {
+            self.send_msg(&keepalive_reply_msg(reply_id, context));
+        }
+        self.events.push_back(Event::Keepalive);
+    }
+}
+
+/// Drive the handshake over a blocking stream, without reading past the sockclnt_create_reply
+pub fn blocking_handshake<S: Read + Write>(sock: &mut S, name: &str) -> Result<Protocol> {
+    let mut proto = Protocol::new();
+    proto.start_handshake(name)?;
+    sock.write_all(&proto.take_outgoing())?;
+    sock.flush()?;
+    loop {
+        let mut buf = vec![0u8; proto.wanted()?];
+        sock.read_exact(&mut buf)?;
+        proto.receive(&buf)?;
+        if let Some(Event::Connected { .. }) = proto.poll_event() {
+            return Ok(proto);
+        }
+    }
+}
+
+/*
+ * Read from a blocking stream until the next message for the caller,
+ * without reading past it. The keepalives are answered on the way.
+ */
+pub fn blocking_next_msg<S: Read + Write + ?Sized>(
+    proto: &mut Protocol,
+    sock: &mut S,
+) -> Result<Vec<u8>> {
+    loop {
+        if proto.wants_write() {
+            sock.write_all(&proto.take_outgoing())?;
+            sock.flush()?;
+        }
+        if let Some(msg) = proto.next_msg() {
+            return Ok(msg);
+        }
+        let mut buf = vec![0u8; proto.wanted()?];
+        let n = sock.read(&mut buf)?;
+        if n == 0 {
+            return Err(Error::ServerClosed);
+        }
+        proto.receive(&buf[..n])?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{mock_sockclnt_create_reply, MOCK_FIRST_MSG_ID, MOCK_MESSAGES};
+
+    fn mock_id(name: &str) -> u16 {
+        MOCK_MESSAGES.iter().position(|n| *n == name).unwrap() as u16 + MOCK_FIRST_MSG_ID
+    }
+
+    fn connected() -> Protocol {
+        let mut p = Protocol::new();
+        p.start_handshake("test").unwrap();
+        let _ = p.take_outgoing();
+        p.receive(&encode_frame(&mock_sockclnt_create_reply(124, 0x80000001)))
+            .unwrap();
+        assert_eq!(
+            p.poll_event(),
+            Some(Event::Connected {
+                client_index: 0x80000001
+            })
+        );
+        p
+    }
+
+    fn reply(msg_id: u16, context: u32, rest: &[u8]) -> Vec<u8> {
+        let mut msg = vec![];
+        msg.extend_from_slice(&msg_id.to_be_bytes());
+        msg.extend_from_slice(&context.to_be_bytes());
+        msg.extend_from_slice(rest);
+        encode_frame(&msg)
+    }
+
+    #[test]
+    fn test_frame_decoder_partial() {
+        let mut frames = encode_frame(b"hello");
+        frames.extend_from_slice(&encode_frame(b""));
+        frames.extend_from_slice(&encode_frame(b"world!"));
+
+        let mut d = FrameDecoder::new();
+        assert_eq!(d.wanted().unwrap(), SOCK_MSG_HEADER_LEN);
+        let mut out = vec![];
+        for b in frames.iter() {
+            d.feed(&[*b]);
+            while let Some(f) = d.next_frame().unwrap() {
+                out.push(f);
+            }
+        }
+        assert_eq!(out, vec![b"hello".to_vec(), vec![], b"world!".to_vec()]);
+        assert_eq!(d.buffered(), 0);
+
+        d.feed(&encode_frame(b"abc")[0..SOCK_MSG_HEADER_LEN + 1]);
+        assert_eq!(d.wanted().unwrap(), 2);
+        assert_eq!(d.next_frame().unwrap(), None);
+    }
+
+    #[test]
+    fn test_frame_decoder_max_len() {
+        let mut d = FrameDecoder::with_max_len(4);
+        d.feed(&encode_frame(b"abcd"));
+        assert_eq!(d.next_frame().unwrap(), Some(b"abcd".to_vec()));
+        d.feed(&encode_frame(b"abcde")[0..SOCK_MSG_HEADER_LEN]);
+        assert!(d.wanted().is_err());
+        assert!(d.next_frame().is_err());
+        assert!(d.wanted().is_err());
+    }
+
+    #[test]
+    fn test_handshake() {
+        let mut p = Protocol::new();
+        assert!(p.send_request(1, &[], RequestKind::Single).is_err());
+        p.start_handshake("test").unwrap();
+        assert_eq!(p.state(), State::Handshaking);
+        let out = p.take_outgoing();
+        assert_eq!(decode_frame_header(&out).unwrap(), 70);
+        assert_eq!(split_msg_id(&out[16..]).unwrap().0, SOCKCLNT_CREATE_ID);
+        assert!(!p.wants_write());
+
+        let reply = encode_frame(&mock_sockclnt_create_reply(124, 0x80000002));
+        let (first, rest) = reply.split_at(100);
+        p.receive(first).unwrap();
+        assert_eq!(p.poll_event(), None);
+        p.receive(rest).unwrap();
+        assert_eq!(
+            p.poll_event(),
+            Some(Event::Connected {
+                client_index: 0x80000002
+            })
+        );
+        assert_eq!(p.state(), State::Connected);
+        assert_eq!(
+            p.get_msg_index(CONTROL_PING),
+            Some(mock_id("control_ping_51077d14"))
+        );
+        assert_eq!(p.message_max_index() as usize, MOCK_MESSAGES.len());
+    }
+
+    #[test]
+    fn test_pending_requests() {
+        let mut p = connected();
+        let show_version = mock_id("show_version_51077d14");
+        let c1 = p
+            .send_request(show_version, &[], RequestKind::Single)
+            .unwrap();
+        let dump = mock_id("sw_interface_dump_aa610c27");
+        let c2 = p.send_request(dump, &[0; 8], RequestKind::Dump).unwrap();
+        assert_ne!(c1, c2);
+        assert_eq!(p.pending_count(), 2);
+
+        /* the dump and the control_ping following it */
+        let mut d = FrameDecoder::new();
+        d.feed(&p.take_outgoing());
+        let sent: Vec<Vec<u8>> = std::iter::from_fn(|| d.next_frame().unwrap()).collect();
+        assert_eq!(sent.len(), 3);
+        assert_eq!(&sent[2][0..2], &mock_id(CONTROL_PING).to_be_bytes());
+        assert_eq!(&sent[2][6..10], &c2.to_be_bytes());
+
+        let details = mock_id("sw_interface_details_17b69fa2");
+        let mut input = reply(details, c2, &[1, 2, 3]);
+        input.extend_from_slice(&reply(mock_id("show_version_reply_c919bde1"), c1, &[0; 4]));
+        input.extend_from_slice(&reply(details, c2, &[4, 5, 6]));
+        input.extend_from_slice(&reply(mock_id(CONTROL_PING_REPLY), c2, &[0; 12]));
+        input.extend_from_slice(&reply(details, 12345, &[]));
+        p.receive(&input).unwrap();
+
+        let mut c2_bytes = c2.to_be_bytes().to_vec();
+        c2_bytes.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(
+            p.poll_event(),
+            Some(Event::Details {
+                context: c2,
+                msg_id: details,
+                data: c2_bytes
+            })
+        );
+        assert!(matches!(p.poll_event(), Some(Event::Reply { context, .. }) if context == c1));
+        assert!(matches!(p.poll_event(), Some(Event::Details { .. })));
+        assert_eq!(p.poll_event(), Some(Event::DumpDone { context: c2 }));
+        assert!(matches!(p.poll_event(), Some(Event::Message { msg_id, .. }) if msg_id == details));
+        assert_eq!(p.poll_event(), None);
+        assert_eq!(p.pending_count(), 0);
+    }
+
+    #[test]
+    fn test_keepalive() {
+        let mut p = connected();
+        let mut msg = vec![];
+        msg.extend_from_slice(&mock_id(MEMCLNT_KEEPALIVE).to_be_bytes());
+        msg.extend_from_slice(&0x80000001u32.to_be_bytes());
+        msg.extend_from_slice(&99u32.to_be_bytes());
+        p.receive(&encode_frame(&msg)).unwrap();
+        assert_eq!(p.poll_event(), Some(Event::Keepalive));
+
+        let out = p.take_outgoing();
+        let mut expected = vec![];
+        expected.extend_from_slice(&mock_id(MEMCLNT_KEEPALIVE_REPLY).to_be_bytes());
+        expected.extend_from_slice(&99u32.to_be_bytes());
+        expected.extend_from_slice(&0i32.to_be_bytes());
+        assert_eq!(out, encode_frame(&expected));
+    }
+
+    #[test]
+    fn test_passive() {
+        let mut p = Protocol::passive();
+        assert!(!p.wants_write());
+        let create_reply = mock_sockclnt_create_reply(124, 0x80000003);
+        p.receive(&encode_frame(&create_reply)).unwrap();
+        assert_eq!(
+            p.poll_event(),
+            Some(Event::Connected {
+                client_index: 0x80000003
+            })
+        );
+        assert_eq!(p.next_msg(), Some(create_reply));
+
+        /* the keepalive is for the client to answer */
+        let mut keepalive = vec![];
+        keepalive.extend_from_slice(&mock_id(MEMCLNT_KEEPALIVE).to_be_bytes());
+        keepalive.extend_from_slice(&0x80000003u32.to_be_bytes());
+        keepalive.extend_from_slice(&99u32.to_be_bytes());
+        p.receive(&encode_frame(&keepalive)).unwrap();
+        assert_eq!(p.next_msg(), Some(keepalive));
+        assert!(!p.wants_write());
+    }
+
+    #[test]
+    fn test_blocking_next_msg_answers_keepalive() {
+        let (mut client, mut server) = std::os::unix::net::UnixStream::pair().unwrap();
+        let mut p = connected();
+
+        let mut keepalive = vec![];
+        keepalive.extend_from_slice(&mock_id(MEMCLNT_KEEPALIVE).to_be_bytes());
+        keepalive.extend_from_slice(&0x80000001u32.to_be_bytes());
+        keepalive.extend_from_slice(&98u32.to_be_bytes());
+        server.write_all(&encode_frame(&keepalive)).unwrap();
+        let ping_reply = reply(mock_id(CONTROL_PING_REPLY), 7, &[0; 12]);
+        server.write_all(&ping_reply).unwrap();
+
+        let msg = blocking_next_msg(&mut p, &mut client).unwrap();
+        assert_eq!(encode_frame(&msg), ping_reply);
+
+        let mut d = FrameDecoder::new();
+        let mut buf = vec![0u8; d.wanted().unwrap()];
+        server.read_exact(&mut buf).unwrap();
+        d.feed(&buf);
+        let mut buf = vec![0u8; d.wanted().unwrap()];
+        server.read_exact(&mut buf).unwrap();
+        d.feed(&buf);
+        assert_eq!(
+            d.next_frame().unwrap(),
+            Some(keepalive_reply_msg(mock_id(MEMCLNT_KEEPALIVE_REPLY), 98))
+        );
+
+        drop(server);
+        match blocking_next_msg(&mut p, &mut client) {
+            Err(Error::ServerClosed) => {}
+            other => panic!("expected ServerClosed, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_blocking_next_msg_oversized() {
+        let (mut client, mut server) = std::os::unix::net::UnixStream::pair().unwrap();
+        let mut p = connected();
+
+        let mut hdr = encode_frame(b"");
+        hdr[8..12].copy_from_slice(&(MAX_MSG_LEN as u32 + 1).to_be_bytes());
+        server.write_all(&hdr).unwrap();
+        drop(server);
+
+        /* the second call must not read nothing and take it for a close */
+        for _ in 0..2 {
+            match blocking_next_msg(&mut p, &mut client) {
+                Err(Error::Error(e)) => assert!(e.contains("is over"), "{}", e),
+                other => panic!("expected the framing error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_cli_inband_roundtrip() {
+        let msg = cli_inband_msg(1, 2, 3, "show version");
+        assert_eq!(msg.len(), 14 + "show version".len());
+        assert_eq!(&msg[10..14], &12u32.to_be_bytes());
+
+        let mut reply = vec![];
+        reply.extend_from_slice(&3u32.to_be_bytes());
+        reply.extend_from_slice(&0i32.to_be_bytes());
+        reply.extend_from_slice(&4u32.to_be_bytes());
+        reply.extend_from_slice(b"vpp ");
+        assert_eq!(parse_cli_inband_reply(&reply).unwrap(), "vpp ");
+    }
+}
@@ -0,0 +1,190 @@
+/*
+ * The sizes of the fixed part of the messages, from the .api.json files
+ * VPP installs (usually in /usr/share/vpp/api). The proxy zero-fills
+ * the synthetic replies up to them, so that the fields after the retval
+ * are there for the client to decode.
+ *
+ * A variable length string counts as its u32 length, and a variable
+ * length array as nothing, its count being a field of its own.
+ */
+use crate::error::Result;
+use log::debug;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+enum Def {
+    Struct(Vec<Value>),
+    Union(Vec<Value>),
+    Enum(String),
+    Alias(Value),
+}
+
+/// The fixed size of each message in the .api.json files under dir, by "<name>_<crc>"
+pub fn load_message_sizes(dir: &str) -> Result<HashMap<String, usize>> {
+    let mut files = vec![];
+    api_json_files(Path::new(dir), &mut files)?;
+
+    /* the types of one file are used by the others, collect them all first */
+    let mut defs = HashMap::new();
+    let mut messages = vec![];
+    for path in files {
+        let data = std::fs::read_to_string(&path)?;
+        let json: Value =
+            serde_json::from_str(&data).map_err(|e| format!("{}: {}", path.display(), e))?;
+        for (name, fields) in section(&json, "types").filter_map(split_def) {
+            defs.insert(name, Def::Struct(fields));
+        }
+        for (name, fields) in section(&json, "unions").filter_map(split_def) {
+            defs.insert(name, Def::Union(fields));
+        }
+        for e in section(&json, "enums").chain(section(&json, "enumflags")) {
+            let enumtype = options(e)
+                .and_then(|o| o.get("enumtype"))
+                .and_then(Value::as_str)
+                .unwrap_or("u32");
+            if let Some((name, _)) = split_def(e) {
+                defs.insert(name, Def::Enum(enumtype.to_string()));
+            }
+        }
+        if let Some(aliases) = json.get("aliases").and_then(Value::as_object) {
+            for (name, alias) in aliases {
+                defs.insert(name.clone(), Def::Alias(alias.clone()));
+            }
+        }
+        messages.extend(section(&json, "messages").cloned());
+    }
+
+    let mut sizes = HashMap::new();
+    for m in messages.iter() {
+        let crc = options(m)
+            .and_then(|o| o.get("crc"))
+            .and_then(Value::as_str);
+        let (name, fields) = match split_def(m) {
+            Some(def) => def,
+            None => continue,
+        };
+        match (crc, fields_size(&defs, &fields, 0)) {
+            (Some(crc), Some(size)) => {
+                sizes.insert(format!("{}_{}", name, crc.trim_start_matches("0x")), size);
+            }
+            _ => debug!("no size for the message {}", name),
+        }
+    }
+    Ok(sizes)
+}
+
+fn api_json_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            api_json_files(&path, out)?;
+        } else if path.to_string_lossy().ends_with(".api.json") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn section<'a>(json: &'a Value, key: &str) -> impl Iterator<Item = &'a Value> {
+    json.get(key)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+/* ["name", field..., {options}] */
+fn options(def: &Value) -> Option<&Value> {
+    def.as_array()?.last().filter(|o| o.is_object())
+}
+
+fn split_def(def: &Value) -> Option<(String, Vec<Value>)> {
+    let items = def.as_array()?;
+    let name = items.first()?.as_str()?.to_string();
+    let fields = items[1..]
+        .iter()
+        .filter(|f| f.is_array())
+        .cloned()
+        .collect();
+    Some((name, fields))
+}
+
+fn fields_size(defs: &HashMap<String, Def>, fields: &[Value], depth: usize) -> Option<usize> {
+    fields.iter().map(|f| field_size(defs, f, depth)).sum()
+}
+
+/* [type, name], [type, name, length] or [type, name, 0, lengthfrom], maybe with {options} */
+fn field_size(defs: &HashMap<String, Def>, field: &Value, depth: usize) -> Option<usize> {
+    let items = field.as_array()?;
+    let typ = items.first()?.as_str()?;
+    type_size(defs, typ, items.get(2).and_then(Value::as_u64), depth)
+}
+
+fn type_size(
+    defs: &HashMap<String, Def>,
+    typ: &str,
+    length: Option<u64>,
+    depth: usize,
+) -> Option<usize> {
+    /* only a broken file would nest the types this deep */
+    if depth > 32 {
+        return None;
+    }
+    let one = match typ {
+        "string" => {
+            return match length {
+                Some(n) if n > 0 => Some(n as usize),
+                _ => Some(4),
+            }
+        }
+        "u8" | "i8" | "bool" => 1,
+        "u16" | "i16" => 2,
+        "u32" | "i32" | "f32" => 4,
+        "u64" | "i64" | "f64" => 8,
+        _ => {
+            let name = typ
+                .strip_prefix("vl_api_")
+                .and_then(|t| t.strip_suffix("_t"))
+                .unwrap_or(typ);
+            match defs.get(name)? {
+                Def::Struct(fields) => fields_size(defs, fields, depth + 1)?,
+                Def::Union(fields) => fields
+                    .iter()
+                    .map(|f| field_size(defs, f, depth + 1))
+                    .collect::<Option<Vec<_>>>()?
+                    .into_iter()
+                    .max()
+                    .unwrap_or(0),
+                Def::Enum(enumtype) => type_size(defs, enumtype, None, depth + 1)?,
+                Def::Alias(alias) => type_size(
+                    defs,
+                    alias.get("type")?.as_str()?,
+                    alias.get("length").and_then(Value::as_u64),
+                    depth + 1,
+                )?,
+            }
+        }
+    };
+    Some(one * length.unwrap_or(1) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_message_sizes() {
+        let dir = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../vpp-api-gen/testdata/vpp/api"
+        );
+        let sizes = load_message_sizes(dir).unwrap();
+        assert_eq!(sizes["show_version_reply_c919bde1"], 10 + 3 * 32 + 256);
+        assert_eq!(sizes["ip_route_add_del_reply_1992deab"], 14);
+        /* the route: table_id, stats_index, the prefix (af, the union, len), n_paths */
+        assert_eq!(
+            sizes["ip_route_add_del_c1ff832d"],
+            12 + 4 + 4 + 1 + 16 + 1 + 1
+        );
+    }
+}
@@ -0,0 +1,561 @@
+/*
+ * A policy-enforcing proxy in front of the VPP API socket.
+ *
+ * Each client connection gets its own upstream connection to VPP.
+ * The clients are identified by their SO_PEERCRED uid/gid, and
+ * every request is checked against the allow-list of the matching
+ * policy. The allowed requests are forwarded as-is, the denied ones
+ * get a synthetic "<name>_reply" with the request context and the
+ * configured retval, zero-filled to the size of the reply if the
+ * .api.json files are configured. Denied requests which have no such reply
+ * (the dumps) are not forwarded, and the control_ping the client
+ * sends after the dump gets the synthetic control_ping_reply
+ * instead. A sockclnt_delete is always for the client's own
+ * registration, whatever index it has.
+ */
+use crate::error::Result;
+use crate::protocol::{blocking_next_msg, Protocol, State, SOCKCLNT_CREATE_ID};
+use crate::{read_sock_frame_max, write_sock_frame};
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{Arc, Mutex};
+
+mod api_json;
+mod policy;
+pub use api_json::load_message_sizes;
+pub use policy::{pattern_matches, strip_crc, ClientPolicy, ProxyConfig};
+
+/* the longest request taken from a client, the connection is closed on a longer one */
+pub const PROXY_MAX_REQUEST_LEN: usize = 4 << 20;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PeerCred {
+    pub pid: i32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+pub fn get_peer_cred(sock: &UnixStream) -> std::io::Result<PeerCred> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            sock.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(PeerCred {
+        pid: cred.pid,
+        uid: cred.uid,
+        gid: cred.gid,
+    })
+}
+
+pub struct Proxy {
+    config: Arc<ProxyConfig>,
+    msg_sizes: Arc<HashMap<String, usize>>,
+}
+
+impl Proxy {
+    pub fn new(config: ProxyConfig) -> Self {
+        let msg_sizes = match &config.api_json_dir {
+            Some(dir) => load_message_sizes(dir).unwrap_or_else(|e| {
+                warn!(
+                    "proxy: can not load the message sizes from {}: {:?}",
+                    dir, e
+                );
+                HashMap::new()
+            }),
+            None => HashMap::new(),
+        };
+        Proxy {
+            config: Arc::new(config),
+            msg_sizes: Arc::new(msg_sizes),
+        }
+    }
+
+    /// Listen on the given path, replacing a stale socket file if any, and serve forever
+    pub fn run(&self, listen_path: &str) -> Result<()> {
+        let _ = std::fs::remove_file(listen_path);
+        let listener = UnixListener::bind(listen_path)?;
+        self.serve(listener)
+    }
+
+    pub fn serve(&self, listener: UnixListener) -> Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let config = self.config.clone();
+            let msg_sizes = self.msg_sizes.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = handle_client(config, msg_sizes, stream) {
+                    warn!("proxy client error: {:?}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+/* The message table as seen by one client */
+#[derive(Debug, Default)]
+struct MessageTable {
+    id_to_name: HashMap<u16, String>,
+    name_to_id: HashMap<String, u16>,
+    /* the registration of the client with VPP, from the sockclnt_create_reply */
+    client_index: Option<u32>,
+    /* from the .api.json files, by name with the CRC */
+    msg_sizes: Arc<HashMap<String, usize>>,
+}
+
+impl MessageTable {
+    fn reply_for(&self, request_name: &str) -> Option<(&str, u16)> {
+        let reply_name = format!("{}_reply", strip_crc(request_name));
+        self.name_to_id
+            .iter()
+            .find(|(name, _)| strip_crc(name) == reply_name)
+            .map(|(name, id)| (name.as_str(), *id))
+    }
+
+    fn reply_id_for(&self, request_name: &str) -> Option<u16> {
+        self.reply_for(request_name).map(|(_, id)| id)
+    }
+
+    /* _vl_msg_id, context, retval, and zeroes for the rest of the reply */
+    fn denied_reply(&self, request_name: &str, context: &[u8], retval: i32) -> Option<Vec<u8>> {
+        let (reply_name, reply_id) = self.reply_for(request_name)?;
+        let mut reply = vec![];
+        reply.extend_from_slice(&reply_id.to_be_bytes());
+        reply.extend_from_slice(context);
+        reply.extend_from_slice(&retval.to_be_bytes());
+        if let Some(size) = self.msg_sizes.get(reply_name) {
+            reply.resize(reply.len().max(*size), 0);
+        }
+        Some(reply)
+    }
+}
+
+fn handle_client(
+    config: Arc<ProxyConfig>,
+    msg_sizes: Arc<HashMap<String, usize>>,
+    client: UnixStream,
+) -> Result<()> {
+    let cred = get_peer_cred(&client)?;
+    let policy = match config.policy_for(cred.uid, cred.gid) {
+        Some(p) => p,
+        None => {
+            warn!("proxy: no policy for {:?}, disconnecting", &cred);
+            return Ok(());
+        }
+    };
+    info!("proxy: new client {:?} with policy {:?}", &cred, &policy);
+
+    let upstream = UnixStream::connect(&config.upstream_path)?;
+    let table = Arc::new(Mutex::new(MessageTable {
+        msg_sizes,
+        ..Default::default()
+    }));
+    let client_tx = Arc::new(Mutex::new(client.try_clone()?));
+
+    let relay = {
+        let mut upstream_rx = upstream.try_clone()?;
+        let table = table.clone();
+        let client_tx = client_tx.clone();
+        std::thread::spawn(move || relay_upstream(&mut upstream_rx, &table, &client_tx))
+    };
+
+    let res = filter_client(
+        &config,
+        &policy,
+        client,
+        upstream.try_clone()?,
+        &table,
+        &client_tx,
+    );
+    let _ = upstream.shutdown(std::net::Shutdown::Both);
+    let _ = relay.join();
+    res
+}
+
+fn relay_upstream(
+    upstream_rx: &mut UnixStream,
+    table: &Mutex<MessageTable>,
+    client_tx: &Mutex<UnixStream>,
+) {
+    /* the keepalives are for the client to answer */
+    let mut proto = Protocol::passive();
+    loop {
+        let msg = match blocking_next_msg(&mut proto, upstream_rx) {
+            Ok(msg) => msg,
+            Err(e) => {
+                debug!("proxy: upstream: {:?}", e);
+                break;
+            }
+        };
+        let mut t = table.lock().unwrap();
+        if t.client_index.is_none() && proto.state() == State::Connected {
+            /* the very first thing from VPP is the sockclnt_create_reply */
+            t.name_to_id = proto.message_table().clone();
+            t.id_to_name = t.name_to_id.iter().map(|(n, i)| (*i, n.clone())).collect();
+            t.client_index = Some(proto.client_index());
+        }
+        drop(t);
+        if write_sock_frame(&mut *client_tx.lock().unwrap(), &msg).is_err() {
+            break;
+        }
+    }
+    let _ = client_tx.lock().unwrap().shutdown(std::net::Shutdown::Both);
+}
+
+fn filter_client(
+    config: &ProxyConfig,
+    policy: &ClientPolicy,
+    mut client_rx: UnixStream,
+    mut upstream_tx: UnixStream,
+    table: &Mutex<MessageTable>,
+    client_tx: &Mutex<UnixStream>,
+) -> Result<()> {
+    /* a denied dump, its control_ping gets the synthetic reply */
+    let mut denied_dump = false;
+    loop {
+        let mut msg = match read_sock_frame_max(&mut client_rx, PROXY_MAX_REQUEST_LEN) {
+            Ok(m) => m,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        if msg.len() < 2 {
+            return Err(format!("proxy: short message from client: {:x?}", &msg).into());
+        }
+        let msg_id = u16::from_be_bytes([msg[0], msg[1]]);
+        if msg_id == SOCKCLNT_CREATE_ID {
+            write_sock_frame(&mut upstream_tx, &msg)?;
+            continue;
+        }
+
+        let t = table.lock().unwrap();
+        let name = t.id_to_name.get(&msg_id).cloned().unwrap_or_default();
+        /* _vl_msg_id, client_index, context */
+        if msg.len() < 10 {
+            warn!("proxy: dropping short message id {} ({})", msg_id, &name);
+            continue;
+        }
+        match strip_crc(&name) {
+            "sockclnt_delete" => {
+                /* _vl_msg_id, client_index, context, index */
+                match (t.client_index, msg.get_mut(10..14)) {
+                    (Some(own), Some(index)) => index.copy_from_slice(&own.to_be_bytes()),
+                    _ => {
+                        warn!("proxy: dropping sockclnt_delete {:x?}", &msg);
+                        continue;
+                    }
+                }
+            }
+            "control_ping" if denied_dump => {
+                denied_dump = false;
+                let reply_id = match t.reply_id_for(&name) {
+                    Some(id) => id,
+                    None => continue,
+                };
+                debug!("proxy: ending the denied dump");
+                /* _vl_msg_id, context, retval, client_index, vpe_pid */
+                let mut reply = vec![];
+                reply.extend_from_slice(&reply_id.to_be_bytes());
+                reply.extend_from_slice(&msg[6..10]);
+                reply.extend_from_slice(&config.denied_retval.to_be_bytes());
+                reply.extend_from_slice(&msg[2..6]);
+                reply.extend_from_slice(&0u32.to_be_bytes());
+                drop(t);
+                write_sock_frame(&mut *client_tx.lock().unwrap(), &reply)?;
+                continue;
+            }
+            _ => {}
+        }
+        if policy.allows(&name) {
+            drop(t);
+            write_sock_frame(&mut upstream_tx, &msg)?;
+            continue;
+        }
+
+        match t.denied_reply(&name, &msg[6..10], config.denied_retval) {
+            Some(reply) => {
+                debug!("proxy: denied {} (id {})", &name, msg_id);
+                drop(t);
+                write_sock_frame(&mut *client_tx.lock().unwrap(), &reply)?;
+            }
+            None => {
+                debug!("proxy: denied {} (id {}), a dump", &name, msg_id);
+                denied_dump = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{mock_client_connect, mock_sockclnt_create_reply, temp_socket_path, MockVpp};
+    use crate::protocol::big_array::BigArray;
+    use crate::read_sock_frame;
+    use serde::Deserialize;
+    use std::io::Write;
+    use vpp_api_encoding::wire;
+
+    #[test]
+    fn test_pattern_matches() {
+        assert!(pattern_matches("*_dump", "sw_interface_dump"));
+        assert!(pattern_matches("show_*", "show_version"));
+        assert!(pattern_matches("control_ping", "control_ping"));
+        assert!(pattern_matches("*", ""));
+        assert!(pattern_matches("*_*_dump", "ip_route_dump"));
+        assert!(!pattern_matches("*_dump", "sw_interface_dump_reply"));
+        assert!(!pattern_matches("control_ping", "control_ping_reply"));
+        assert!(!pattern_matches("show_*", "cli_show"));
+        assert_eq!(strip_crc("control_ping_51077d14"), "control_ping");
+        assert_eq!(strip_crc("control_ping"), "control_ping");
+    }
+
+    #[test]
+    fn test_proxy_allow_and_deny() {
+        let mock = MockVpp::start();
+        let config = ProxyConfig {
+            upstream_path: mock.path().to_string(),
+            clients: vec![ClientPolicy {
+                uid: Some(unsafe { libc::getuid() }),
+                gid: None,
+                allow: vec!["*_dump".into(), "show_*".into(), "control_ping".into()],
+            }],
+            default_allow: None,
+            denied_retval: -123,
+            api_json_dir: None,
+        };
+        let listen_path = temp_socket_path("proxy");
+        let listener = UnixListener::bind(&listen_path).unwrap();
+        std::thread::spawn(move || Proxy::new(config).serve(listener));
+
+        let (mut s, table) = mock_client_connect(&listen_path);
+        let ping_id = table["control_ping_51077d14"];
+        let ping_reply_id = table["control_ping_reply_f6b0b8ca"];
+        let add_del_id = table["sw_interface_add_del_address_5803d5c4"];
+        let add_del_reply_id = table["sw_interface_add_del_address_reply_e8d4e804"];
+
+        let mut ping = vec![];
+        ping.extend_from_slice(&ping_id.to_be_bytes());
+        ping.extend_from_slice(&0u32.to_be_bytes());
+        ping.extend_from_slice(&77u32.to_be_bytes());
+        write_sock_frame(&mut s, &ping).unwrap();
+        let reply = read_sock_frame(&mut s).unwrap();
+        assert_eq!(&reply[0..2], &ping_reply_id.to_be_bytes());
+        assert_eq!(&reply[2..6], &77u32.to_be_bytes());
+        assert_eq!(&reply[6..10], &0i32.to_be_bytes());
+
+        let mut add_del = vec![];
+        add_del.extend_from_slice(&add_del_id.to_be_bytes());
+        add_del.extend_from_slice(&0u32.to_be_bytes());
+        add_del.extend_from_slice(&78u32.to_be_bytes());
+        add_del.extend_from_slice(&[0u8; 26]);
+        write_sock_frame(&mut s, &add_del).unwrap();
+        let reply = read_sock_frame(&mut s).unwrap();
+        assert_eq!(&reply[0..2], &add_del_reply_id.to_be_bytes());
+        assert_eq!(&reply[2..6], &78u32.to_be_bytes());
+        assert_eq!(&reply[6..10], &(-123i32).to_be_bytes());
+
+        assert!(mock.received().contains(&"control_ping".to_string()));
+        assert!(!mock
+            .received()
+            .contains(&"sw_interface_add_del_address".to_string()));
+        let _ = std::fs::remove_file(&listen_path);
+    }
+
+    #[test]
+    fn test_proxy_denied_reply_size() {
+        #[derive(Deserialize)]
+        struct ShowVersionReply {
+            _vl_msg_id: u16,
+            context: u32,
+            retval: i32,
+            program: [u8; 32],
+            version: [u8; 32],
+            build_date: [u8; 32],
+            #[serde(with = "BigArray")]
+            build_directory: [u8; 256],
+        }
+
+        let mock = MockVpp::start();
+        let config = ProxyConfig {
+            upstream_path: mock.path().to_string(),
+            clients: vec![ClientPolicy {
+                uid: Some(unsafe { libc::getuid() }),
+                gid: None,
+                allow: vec![],
+            }],
+            default_allow: None,
+            denied_retval: -123,
+            api_json_dir: Some(
+                concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/../vpp-api-gen/testdata/vpp/api"
+                )
+                .into(),
+            ),
+        };
+        let listen_path = temp_socket_path("proxy");
+        let listener = UnixListener::bind(&listen_path).unwrap();
+        std::thread::spawn(move || Proxy::new(config).serve(listener));
+
+        let (mut s, table) = mock_client_connect(&listen_path);
+        let mut show_version = vec![];
+        show_version.extend_from_slice(&table["show_version_51077d14"].to_be_bytes());
+        show_version.extend_from_slice(&0u32.to_be_bytes());
+        show_version.extend_from_slice(&82u32.to_be_bytes());
+        write_sock_frame(&mut s, &show_version).unwrap();
+
+        let reply: ShowVersionReply = wire::from_bytes(&read_sock_frame(&mut s).unwrap()).unwrap();
+        assert_eq!(reply._vl_msg_id, table["show_version_reply_c919bde1"]);
+        assert_eq!(reply.context, 82);
+        assert_eq!(reply.retval, -123);
+        assert_eq!(reply.program, [0; 32]);
+        assert_eq!(reply.version, [0; 32]);
+        assert_eq!(reply.build_date, [0; 32]);
+        assert_eq!(reply.build_directory, [0; 256]);
+        assert!(!mock.received().contains(&"show_version".to_string()));
+        let _ = std::fs::remove_file(&listen_path);
+    }
+
+    fn start_proxy(upstream_path: &str, allow: &[&str]) -> String {
+        let config = ProxyConfig {
+            upstream_path: upstream_path.to_string(),
+            clients: vec![ClientPolicy {
+                uid: Some(unsafe { libc::getuid() }),
+                gid: None,
+                allow: allow.iter().map(|a| a.to_string()).collect(),
+            }],
+            default_allow: None,
+            denied_retval: -123,
+            api_json_dir: None,
+        };
+        let listen_path = temp_socket_path("proxy");
+        let listener = UnixListener::bind(&listen_path).unwrap();
+        std::thread::spawn(move || Proxy::new(config).serve(listener));
+        listen_path
+    }
+
+    #[test]
+    fn test_proxy_denied_dump() {
+        let mock = MockVpp::start();
+        let listen_path = start_proxy(mock.path(), &["show_*"]);
+
+        let (mut s, table) = mock_client_connect(&listen_path);
+        let ping_id = table["control_ping_51077d14"];
+        let ping_reply_id = table["control_ping_reply_f6b0b8ca"];
+        let dump_id = table["sw_interface_dump_aa610c27"];
+
+        let mut dump = vec![];
+        dump.extend_from_slice(&dump_id.to_be_bytes());
+        dump.extend_from_slice(&5u32.to_be_bytes());
+        dump.extend_from_slice(&79u32.to_be_bytes());
+        dump.extend_from_slice(&[0u8; 9]);
+        write_sock_frame(&mut s, &dump).unwrap();
+        let mut ping = vec![];
+        ping.extend_from_slice(&ping_id.to_be_bytes());
+        ping.extend_from_slice(&5u32.to_be_bytes());
+        ping.extend_from_slice(&80u32.to_be_bytes());
+        write_sock_frame(&mut s, &ping).unwrap();
+
+        /* no details, only the end of the dump with the denied retval */
+        let reply = read_sock_frame(&mut s).unwrap();
+        assert_eq!(&reply[0..2], &ping_reply_id.to_be_bytes());
+        assert_eq!(&reply[2..6], &80u32.to_be_bytes());
+        assert_eq!(&reply[6..10], &(-123i32).to_be_bytes());
+        assert_eq!(&reply[10..14], &5u32.to_be_bytes());
+
+        /* the next ping is a ping again */
+        write_sock_frame(&mut s, &ping).unwrap();
+        let reply = read_sock_frame(&mut s).unwrap();
+        assert_eq!(&reply[0..2], &ping_reply_id.to_be_bytes());
+        assert_eq!(&reply[6..10], &0i32.to_be_bytes());
+
+        let received = mock.received();
+        assert!(!received.contains(&"sw_interface_dump".to_string()));
+        assert_eq!(received.iter().filter(|n| *n == "control_ping").count(), 1);
+        let _ = std::fs::remove_file(&listen_path);
+    }
+
+    #[test]
+    fn test_proxy_sockclnt_delete_own_index() {
+        /* an upstream which records the sockclnt_delete it gets */
+        let upstream_path = temp_socket_path("upstream");
+        let listener = UnixListener::bind(&upstream_path).unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            let create = read_sock_frame(&mut s).unwrap();
+            let context = u32::from_be_bytes([create[2], create[3], create[4], create[5]]);
+            write_sock_frame(&mut s, &mock_sockclnt_create_reply(context, 0x1234)).unwrap();
+            tx.send(read_sock_frame(&mut s).unwrap()).unwrap();
+        });
+        let listen_path = start_proxy(&upstream_path, &["*"]);
+
+        let (mut s, table) = mock_client_connect(&listen_path);
+        let mut delete = vec![];
+        delete.extend_from_slice(&table["sockclnt_delete_8ac76db6"].to_be_bytes());
+        delete.extend_from_slice(&0x1234u32.to_be_bytes());
+        delete.extend_from_slice(&81u32.to_be_bytes());
+        /* somebody else's registration */
+        delete.extend_from_slice(&0x4321u32.to_be_bytes());
+        write_sock_frame(&mut s, &delete).unwrap();
+
+        let forwarded = rx.recv().unwrap();
+        assert_eq!(&forwarded[0..10], &delete[0..10]);
+        assert_eq!(&forwarded[10..14], &0x1234u32.to_be_bytes());
+        let _ = std::fs::remove_file(&listen_path);
+        let _ = std::fs::remove_file(&upstream_path);
+    }
+
+    #[test]
+    fn test_proxy_frame_too_long() {
+        let mock = MockVpp::start();
+        let listen_path = start_proxy(mock.path(), &["*"]);
+
+        let (mut s, _table) = mock_client_connect(&listen_path);
+        /* _q, msglen, gc_mark_timestamp */
+        let mut header = vec![0u8; 16];
+        header[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        s.write_all(&header).unwrap();
+        assert!(read_sock_frame(&mut s).is_err());
+        let _ = std::fs::remove_file(&listen_path);
+    }
+
+    #[test]
+    fn test_proxy_no_policy_disconnects() {
+        let mock = MockVpp::start();
+        let config = ProxyConfig {
+            upstream_path: mock.path().to_string(),
+            clients: vec![ClientPolicy {
+                uid: Some(unsafe { libc::getuid() }.wrapping_add(1)),
+                gid: None,
+                allow: vec!["*".into()],
+            }],
+            default_allow: None,
+            denied_retval: -1,
+            api_json_dir: None,
+        };
+        let listen_path = temp_socket_path("proxy");
+        let listener = UnixListener::bind(&listen_path).unwrap();
+        std::thread::spawn(move || Proxy::new(config).serve(listener));
+
+        let mut s = UnixStream::connect(&listen_path).unwrap();
+        assert!(read_sock_frame(&mut s).is_err());
+        let _ = std::fs::remove_file(&listen_path);
+    }
+}
@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+
+/*
+ * Messages that keep the connection itself alive and are always
+ * let through, regardless of the client policy. The sockclnt_delete
+ * is for the client's own registration only, and the control_ping
+ * ends a denied dump (see the proxy).
+ */
+const HOUSEKEEPING_MESSAGES: &[&str] = &[
+    "sockclnt_create",
+    "sockclnt_delete",
+    "memclnt_keepalive_reply",
+    "control_ping",
+];
+
+/// An allow-list for clients matching the given uid and/or gid
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClientPolicy {
+    /// Match the peer uid, any uid if not set
+    #[serde(default)]
+    pub uid: Option<u32>,
+    /// Match the peer gid, any gid if not set
+    #[serde(default)]
+    pub gid: Option<u32>,
+    /// Message name patterns, without the CRC, e.g. "*_dump", "show_*", "control_ping"
+    pub allow: Vec<String>,
+}
+
+impl ClientPolicy {
+    pub fn matches_peer(&self, uid: u32, gid: u32) -> bool {
+        self.uid.unwrap_or(uid) == uid && self.gid.unwrap_or(gid) == gid
+    }
+
+    /// Check a message name, with or without the "_<crc>" suffix, against the allow-list
+    pub fn allows(&self, name: &str) -> bool {
+        let name = strip_crc(name);
+        HOUSEKEEPING_MESSAGES.contains(&name) || self.allow.iter().any(|p| pattern_matches(p, name))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    /// The VPP API socket to forward the allowed requests to
+    pub upstream_path: String,
+    /// Per-client policies, the first one matching the peer credentials wins
+    #[serde(default)]
+    pub clients: Vec<ClientPolicy>,
+    /// The allow-list for clients not matching any policy, if none - they are disconnected
+    #[serde(default)]
+    pub default_allow: Option<Vec<String>>,
+    /// The retval to put into the synthetic replies to the denied requests
+    #[serde(default = "default_denied_retval")]
+    pub denied_retval: i32,
+    /// The VPP .api.json files (e.g. /usr/share/vpp/api), for the full size of the synthetic replies
+    #[serde(default)]
+    pub api_json_dir: Option<String>,
+}
+
+fn default_denied_retval() -> i32 {
+    -1 /* VNET_API_ERROR_UNSPECIFIED */
+}
+
+impl ProxyConfig {
+    pub fn policy_for(&self, uid: u32, gid: u32) -> Option<ClientPolicy> {
+        match self.clients.iter().find(|p| p.matches_peer(uid, gid)) {
+            Some(p) => Some(p.clone()),
+            None => self.default_allow.as_ref().map(|allow| ClientPolicy {
+                uid: Some(uid),
+                gid: Some(gid),
+                allow: allow.clone(),
+            }),
+        }
+    }
+}
+
+/// "control_ping_51077d14" => "control_ping"
+pub fn strip_crc(name: &str) -> &str {
+    match name.rsplit_once('_') {
+        Some((base, crc)) if crc.len() == 8 && crc.chars().all(|c| c.is_ascii_hexdigit()) => base,
+        _ => name,
+    }
+}
+
+/// Glob-style match, where '*' matches any (possibly empty) sequence of characters
+pub fn pattern_matches(pattern: &str, name: &str) -> bool {
+    let p = pattern.as_bytes();
+    let n = name.as_bytes();
+    let (mut pi, mut ni) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while ni < n.len() {
+        if pi < p.len() && p[pi] == b'*' {
+            backtrack = Some((pi, ni));
+            pi += 1;
+        } else if pi < p.len() && p[pi] == n[ni] {
+            pi += 1;
+            ni += 1;
+        } else if let Some((star_pi, star_ni)) = backtrack {
+            /* let the last star eat one more character */
+            pi = star_pi + 1;
+            ni = star_ni + 1;
+            backtrack = Some((star_pi, star_ni + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == b'*')
+}
@@ -0,0 +1,294 @@
+/*
+ * The transport to reach a VPP API socket on another host, via the
+ * relay (see relay.rs) which exposes the local api.sock over TCP,
+ * optionally wrapped into TLS.
+ *
+ * Once the relay has accepted the token, the bytes on the wire are
+ * exactly what goes over api.sock, so the message exchange is the same
+ * as in the afunix transport: SockMsgHeader framing and the
+ * sockclnt_create handshake.
+ */
+use crate::afunix::SOCKCLNT_DELETE_TIMEOUT;
+use crate::error::{Error, Result};
+use crate::protocol::{
+    blocking_handshake, blocking_next_msg, sockclnt_delete_msg, split_msg_id, Protocol,
+    SOCKCLNT_DELETE, SOCKCLNT_DELETE_REPLY,
+};
+use crate::{read_sock_frame, write_sock_frame, VppApiTransport};
+use log::debug;
+use std::convert::TryInto;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Instant;
+
+mod relay;
+#[cfg(feature = "tls")]
+pub mod tls;
+
+pub use relay::Relay;
+
+/*
+ * Relay handshake: the client sends the token (possibly empty)
+ * in a SockMsgHeader frame, the relay answers with an i32 status
+ * in the same framing, and closes the connection if it is not OK.
+ */
+const RELAY_HANDSHAKE_OK: i32 = 0;
+const RELAY_HANDSHAKE_DENIED: i32 = -1;
+const RELAY_MAX_TOKEN_LEN: usize = 4096;
+
+trait RemoteStream: Read + Write + Send {}
+impl<T: Read + Write + Send> RemoteStream for T {}
+
+pub struct Transport {
+    connected: bool,
+    addr: String,
+    token: Option<String>,
+    #[cfg(feature = "tls")]
+    tls: Option<tls::ClientTls>,
+    tcp: Option<TcpStream>,
+    sock: Option<Box<dyn RemoteStream>>,
+    proto: Protocol,
+}
+
+impl Transport {
+    /// The relay is at addr, e.g. "vpp-host:9999"
+    pub fn new(addr: &str) -> Self {
+        Transport {
+            connected: false,
+            addr: addr.to_owned(),
+            token: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+            tcp: None,
+            sock: None,
+            proto: Protocol::new(),
+        }
+    }
+
+    /// Present this shared token to the relay
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_owned());
+        self
+    }
+
+    /// Wrap the connection into TLS, verifying the relay as server_name against the CA
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, server_name: &str, ca_pem_path: &str) -> Result<Self> {
+        self.tls = Some(tls::ClientTls::new(server_name, ca_pem_path)?);
+        Ok(self)
+    }
+
+    fn open_stream(&self, tcp: &TcpStream) -> Result<Box<dyn RemoteStream>> {
+        #[cfg(feature = "tls")]
+        {
+            if let Some(tls) = &self.tls {
+                return Ok(Box::new(tls.wrap(tcp.try_clone()?)?));
+            }
+        }
+        Ok(Box::new(tcp.try_clone()?))
+    }
+
+    /* unregister from VPP through the relay, the same as the afunix transport */
+    fn sockclnt_delete(&mut self) -> Result<()> {
+        let delete_id = match self.get_msg_index(SOCKCLNT_DELETE) {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        let reply_id = self.get_msg_index(SOCKCLNT_DELETE_REPLY);
+        let context = self.get_next_context();
+        let msg = sockclnt_delete_msg(delete_id, self.proto.client_index(), context);
+        if let Some(tcp) = &self.tcp {
+            tcp.set_nonblocking(false)?;
+            tcp.set_read_timeout(Some(SOCKCLNT_DELETE_TIMEOUT))?;
+        }
+        let sock = self.sock.as_mut().ok_or(Error::ServerClosed)?;
+        write_sock_frame(sock, &msg)?;
+
+        let deadline = Instant::now() + SOCKCLNT_DELETE_TIMEOUT;
+        loop {
+            let data = match blocking_next_msg(&mut self.proto, sock) {
+                Ok(data) => data,
+                /* VPP may close the socket right away */
+                Err(Error::ServerClosed) => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            let (msg_id, _) = split_msg_id(&data)?;
+            if Some(msg_id) == reply_id {
+                return Ok(());
+            }
+            if Instant::now() > deadline {
+                return Err(Error::Error(
+                    "timeout waiting for sockclnt_delete_reply".into(),
+                ));
+            }
+        }
+    }
+}
+
+impl std::io::Read for Transport {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.sock.as_mut().unwrap().read(buf)
+    }
+}
+impl std::io::Write for Transport {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        write_sock_frame(self.sock.as_mut().unwrap(), buf)?;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.sock.as_mut().unwrap().flush()
+    }
+}
+
+impl VppApiTransport for Transport {
+    fn connect(&mut self, name: &str, _chroot_prefix: Option<&str>, _rx_qlen: i32) -> Result<()> {
+        let tcp = TcpStream::connect(&self.addr)?;
+        tcp.set_nodelay(true)?;
+        let mut sock = self.open_stream(&tcp)?;
+
+        let token = self.token.clone().unwrap_or_default();
+        write_sock_frame(&mut sock, token.as_bytes())?;
+        let status = read_sock_frame(&mut sock)?;
+        if status.len() != 4 {
+            return Err(format!("invalid relay handshake reply: {:x?}", &status).into());
+        }
+        let status = i32::from_be_bytes(status[0..4].try_into().unwrap());
+        if status != RELAY_HANDSHAKE_OK {
+            return Err(
+                format!("relay at {} refused the connection: {}", &self.addr, status).into(),
+            );
+        }
+
+        let proto = blocking_handshake(&mut sock, name)?;
+        self.tcp = Some(tcp);
+        self.sock = Some(sock);
+        self.connected = true;
+        self.proto = proto;
+        Ok(())
+    }
+    fn disconnect(&mut self) {
+        if self.connected {
+            if let Err(e) = self.sockclnt_delete() {
+                debug!("sockclnt_delete failed: {:?}", e);
+            }
+            if let Some(tcp) = &self.tcp {
+                let _ = tcp.shutdown(std::net::Shutdown::Both);
+            }
+            self.sock = None;
+            self.tcp = None;
+            self.proto = Protocol::new();
+            self.connected = false;
+        }
+    }
+    fn set_nonblocking(&mut self, nonblocking: bool) -> Result<()> {
+        if let Some(ref tcp) = self.tcp {
+            tcp.set_nonblocking(nonblocking)?;
+            Ok(())
+        } else {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                "trying to set unconnected socket non-blocking",
+            )
+            .into())
+        }
+    }
+
+    fn get_client_index(&self) -> u32 {
+        self.proto.client_index()
+    }
+    fn get_msg_index(&mut self, name: &str) -> Option<u16> {
+        self.proto.get_msg_index(name)
+    }
+    fn get_table_max_index(&mut self) -> u16 {
+        self.proto.message_max_index()
+    }
+    fn dump(&self) {
+        println!(
+            "Remote transport to {}: connected: {} client index: {} messages: {}",
+            &self.addr,
+            self.connected,
+            self.proto.client_index(),
+            self.proto.message_table().len()
+        );
+    }
+
+    fn read_one_msg_into(&mut self, data: &mut Vec<u8>) -> Result<()> {
+        let sock = self.sock.as_mut().ok_or(Error::ServerClosed)?;
+        *data = blocking_next_msg(&mut self.proto, sock)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockVpp, MOCK_MESSAGES};
+    use std::net::TcpListener;
+
+    fn start_relay(relay: Relay) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || relay.serve(listener));
+        addr
+    }
+
+    #[test]
+    fn test_remote_connect() {
+        let mock = MockVpp::start();
+        let addr = start_relay(Relay::new(mock.path()).with_token("sekrit"));
+
+        let mut t = Transport::new(&addr).with_token("sekrit");
+        t.connect("test", None, 32).unwrap();
+        assert!(t.get_msg_index("control_ping_51077d14").is_some());
+        assert_eq!(t.get_table_max_index(), MOCK_MESSAGES.len() as u16);
+        let context = t.control_ping().unwrap();
+        t.skip_to_control_ping_reply(context).unwrap();
+        let s = t.run_cli_inband("show version").unwrap();
+        assert!(s.starts_with("vpp "));
+        t.disconnect();
+        assert_eq!(mock.received().last().unwrap(), "sockclnt_delete");
+    }
+
+    #[test]
+    fn test_remote_bad_token() {
+        let mock = MockVpp::start();
+        let addr = start_relay(Relay::new(mock.path()).with_token("sekrit"));
+
+        let mut t = Transport::new(&addr).with_token("guess");
+        assert!(t.connect("test", None, 32).is_err());
+        let mut t = Transport::new(&addr);
+        assert!(t.connect("test", None, 32).is_err());
+        assert!(mock.received().is_empty());
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn test_remote_tls_connect() {
+        let mock = MockVpp::start();
+        let key = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let dir = std::env::temp_dir();
+        let cert_path = dir.join(format!("vpp-api-relay-{}.crt", std::process::id()));
+        let key_path = dir.join(format!("vpp-api-relay-{}.key", std::process::id()));
+        std::fs::write(&cert_path, key.cert.pem()).unwrap();
+        std::fs::write(&key_path, key.signing_key.serialize_pem()).unwrap();
+        let cert_path = cert_path.to_str().unwrap();
+        let key_path = key_path.to_str().unwrap();
+
+        let relay = Relay::new(mock.path())
+            .with_token("sekrit")
+            .with_tls(cert_path, key_path)
+            .unwrap();
+        let addr = start_relay(relay);
+
+        let mut t = Transport::new(&addr)
+            .with_token("sekrit")
+            .with_tls("localhost", cert_path)
+            .unwrap();
+        t.connect("test", None, 32).unwrap();
+        let s = t.run_cli_inband("show version").unwrap();
+        assert!(s.starts_with("vpp "));
+        t.disconnect();
+        let _ = std::fs::remove_file(cert_path);
+        let _ = std::fs::remove_file(key_path);
+    }
+}
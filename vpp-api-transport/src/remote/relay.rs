@@ -0,0 +1,129 @@
+use super::{RELAY_HANDSHAKE_DENIED, RELAY_HANDSHAKE_OK, RELAY_MAX_TOKEN_LEN};
+use crate::error::Result;
+use crate::protocol::{decode_frame_header, SOCK_MSG_HEADER_LEN};
+use crate::write_sock_frame;
+use log::{info, warn};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::net::UnixStream;
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+
+/// Exposes a local VPP API socket over TCP, optionally with TLS and a shared token
+pub struct Relay {
+    upstream_path: String,
+    token: Option<String>,
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<rustls::ServerConfig>>,
+}
+
+impl Relay {
+    pub fn new(upstream_path: &str) -> Self {
+        Relay {
+            upstream_path: upstream_path.to_owned(),
+            token: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Require the clients to present this token
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_owned());
+        self
+    }
+
+    /// Serve TLS with the certificate chain and the private key from the PEM files
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, cert_pem_path: &str, key_pem_path: &str) -> Result<Self> {
+        self.tls = Some(super::tls::server_config(cert_pem_path, key_pem_path)?);
+        Ok(self)
+    }
+
+    pub fn run(&self, listen_addr: &str) -> Result<()> {
+        let listener = TcpListener::bind(listen_addr)?;
+        self.serve(listener)
+    }
+
+    pub fn serve(&self, listener: TcpListener) -> Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let upstream_path = self.upstream_path.clone();
+            let token = self.token.clone();
+            #[cfg(feature = "tls")]
+            let tls = self.tls.clone();
+            std::thread::spawn(move || {
+                let peer = stream.peer_addr().ok();
+                #[cfg(feature = "tls")]
+                let res = match tls {
+                    Some(tls) => super::tls::relay_tls(tls, stream, token, &upstream_path),
+                    None => relay_plain(stream, token, &upstream_path),
+                };
+                #[cfg(not(feature = "tls"))]
+                let res = relay_plain(stream, token, &upstream_path);
+                if let Err(e) = res {
+                    warn!("relay: connection from {:?}: {:?}", peer, e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+fn token_matches(expected: &[u8], got: &[u8]) -> bool {
+    /* do not bail out on the first mismatch */
+    expected.len() == got.len()
+        && expected
+            .iter()
+            .zip(got.iter())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Check the client token and connect to the upstream API socket if it is fine
+pub(super) fn relay_handshake<S: Read + Write>(
+    client: &mut S,
+    token: Option<String>,
+    upstream_path: &str,
+) -> Result<UnixStream> {
+    let mut header_buf = [0u8; SOCK_MSG_HEADER_LEN];
+    client.read_exact(&mut header_buf)?;
+    let msglen = decode_frame_header(&header_buf)?;
+    if msglen > RELAY_MAX_TOKEN_LEN {
+        return Err(format!("relay: token length {} is too long", msglen).into());
+    }
+    let mut got = vec![0u8; msglen];
+    client.read_exact(&mut got)?;
+
+    let expected = token.unwrap_or_default();
+    if !token_matches(expected.as_bytes(), &got) {
+        write_sock_frame(client, &RELAY_HANDSHAKE_DENIED.to_be_bytes())?;
+        return Err("relay: wrong token".into());
+    }
+    let upstream = UnixStream::connect(upstream_path)?;
+    write_sock_frame(client, &RELAY_HANDSHAKE_OK.to_be_bytes())?;
+    Ok(upstream)
+}
+
+fn relay_plain(mut client: TcpStream, token: Option<String>, upstream_path: &str) -> Result<()> {
+    client.set_nodelay(true)?;
+    let upstream = relay_handshake(&mut client, token, upstream_path)?;
+    info!(
+        "relay: {:?} connected to {}",
+        client.peer_addr(),
+        upstream_path
+    );
+
+    let mut client_rx = client.try_clone()?;
+    let mut upstream_tx = upstream.try_clone()?;
+    let to_upstream = std::thread::spawn(move || {
+        let _ = std::io::copy(&mut client_rx, &mut upstream_tx);
+        let _ = upstream_tx.shutdown(std::net::Shutdown::Both);
+    });
+
+    let mut upstream_rx = upstream;
+    let _ = std::io::copy(&mut upstream_rx, &mut client);
+    let _ = client.shutdown(std::net::Shutdown::Both);
+    let _ = to_upstream.join();
+    Ok(())
+}
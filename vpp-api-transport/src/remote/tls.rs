@@ -0,0 +1,179 @@
+/*
+ * TLS for the remote transport and the relay, on top of rustls.
+ *
+ * The relay needs to shovel the data in both directions at once,
+ * which rustls::StreamOwned can not do as it is not splittable.
+ * So after the token handshake the relay takes the connection apart
+ * and drives it by hand: each direction has its own copy of the
+ * TcpStream, and the rustls state is shared behind a mutex, which is
+ * never held while blocking on a read.
+ */
+use super::relay::relay_handshake;
+use crate::error::{Error, Result};
+use log::info;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::{ClientConfig, ClientConnection, ServerConfig, ServerConnection, StreamOwned};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::net::TcpStream;
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex};
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let mut rd = BufReader::new(File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut rd).collect::<std::io::Result<Vec<_>>>()?;
+    if certs.is_empty() {
+        return Err(format!("no certificates found in {}", path).into());
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let mut rd = BufReader::new(File::open(path)?);
+    rustls_pemfile::private_key(&mut rd)?
+        .ok_or_else(|| Error::Error(format!("no private key found in {}", path)))
+}
+
+pub fn server_config(cert_pem_path: &str, key_pem_path: &str) -> Result<Arc<ServerConfig>> {
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(load_certs(cert_pem_path)?, load_key(key_pem_path)?)?;
+    Ok(Arc::new(config))
+}
+
+/// The client side TLS settings: what to verify the relay against
+pub struct ClientTls {
+    config: Arc<ClientConfig>,
+    server_name: ServerName<'static>,
+}
+
+impl ClientTls {
+    pub fn new(server_name: &str, ca_pem_path: &str) -> Result<Self> {
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in load_certs(ca_pem_path)? {
+            roots.add(cert)?;
+        }
+        let config = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let server_name = ServerName::try_from(server_name.to_owned())
+            .map_err(|e| Error::Error(format!("invalid server name {}: {}", server_name, e)))?;
+        Ok(ClientTls {
+            config: Arc::new(config),
+            server_name,
+        })
+    }
+
+    pub fn wrap(&self, tcp: TcpStream) -> Result<StreamOwned<ClientConnection, TcpStream>> {
+        let conn = ClientConnection::new(self.config.clone(), self.server_name.clone())?;
+        Ok(StreamOwned::new(conn, tcp))
+    }
+}
+
+/* Move the decrypted data out, returns true if the peer has closed the TLS session */
+fn drain_plaintext(conn: &mut ServerConnection, out: &mut Vec<u8>) -> std::io::Result<bool> {
+    let mut buf = [0u8; 16384];
+    loop {
+        match conn.reader().read(&mut buf) {
+            Ok(0) => return Ok(true),
+            Ok(n) => out.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(false),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn flush_tls(conn: &mut ServerConnection, tcp_tx: &mut TcpStream) -> std::io::Result<()> {
+    while conn.wants_write() {
+        conn.write_tls(tcp_tx)?;
+    }
+    Ok(())
+}
+
+fn tls_to_upstream(
+    conn: &Mutex<ServerConnection>,
+    tcp_rx: &mut TcpStream,
+    tcp_tx: &mut TcpStream,
+    upstream_tx: &mut UnixStream,
+) -> Result<()> {
+    /* the handshake might have already decrypted more than the token */
+    let mut plain = vec![];
+    let mut eof = drain_plaintext(&mut conn.lock().unwrap(), &mut plain)?;
+    let mut buf = vec![0u8; 65536];
+    loop {
+        upstream_tx.write_all(&plain)?;
+        if eof {
+            return Ok(());
+        }
+        let n = tcp_rx.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        plain.clear();
+        let mut c = conn.lock().unwrap();
+        let mut rd = &buf[..n];
+        while !rd.is_empty() {
+            c.read_tls(&mut rd)?;
+            c.process_new_packets()?;
+            eof = drain_plaintext(&mut c, &mut plain)? || eof;
+        }
+        flush_tls(&mut c, tcp_tx)?;
+    }
+}
+
+fn upstream_to_tls(
+    conn: &Mutex<ServerConnection>,
+    upstream_rx: &mut UnixStream,
+    tcp_tx: &mut TcpStream,
+) -> Result<()> {
+    let mut buf = vec![0u8; 65536];
+    loop {
+        let n = upstream_rx.read(&mut buf)?;
+        let mut c = conn.lock().unwrap();
+        if n == 0 {
+            c.send_close_notify();
+            flush_tls(&mut c, tcp_tx)?;
+            return Ok(());
+        }
+        c.writer().write_all(&buf[..n])?;
+        flush_tls(&mut c, tcp_tx)?;
+    }
+}
+
+pub(super) fn relay_tls(
+    config: Arc<ServerConfig>,
+    tcp: TcpStream,
+    token: Option<String>,
+    upstream_path: &str,
+) -> Result<()> {
+    tcp.set_nodelay(true)?;
+    let mut stream = StreamOwned::new(ServerConnection::new(config)?, tcp);
+    let upstream = relay_handshake(&mut stream, token, upstream_path)?;
+    let (conn, tcp) = stream.into_parts();
+    info!(
+        "relay: TLS {:?} connected to {}",
+        tcp.peer_addr(),
+        upstream_path
+    );
+    let conn = Arc::new(Mutex::new(conn));
+
+    let to_upstream = {
+        let conn = conn.clone();
+        let mut tcp_rx = tcp.try_clone()?;
+        let mut tcp_tx = tcp.try_clone()?;
+        let mut upstream_tx = upstream.try_clone()?;
+        std::thread::spawn(move || {
+            let res = tls_to_upstream(&conn, &mut tcp_rx, &mut tcp_tx, &mut upstream_tx);
+            let _ = upstream_tx.shutdown(std::net::Shutdown::Both);
+            res
+        })
+    };
+
+    let mut upstream_rx = upstream;
+    let mut tcp_tx = tcp.try_clone()?;
+    let res = upstream_to_tls(&conn, &mut upstream_rx, &mut tcp_tx);
+    let _ = tcp.shutdown(std::net::Shutdown::Both);
+    let _ = to_upstream.join();
+    res
+}
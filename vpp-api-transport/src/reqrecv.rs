@@ -8,8 +8,9 @@
 )]
 use super::error::Result;
 use crate::error::Error;
+use crate::instrument::{reply_retval, CallTracker};
+use crate::protocol::CONTROL_PING_REPLY;
 use crate::VppApiTransport;
-use bincode::Options;
 use log::{debug, error, trace};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::collections::HashMap;
@@ -17,12 +18,12 @@ use std::convert::TryInto;
 use std::io::{Read, Write};
 use std::ops::Add;
 use std::time::{Duration, SystemTime};
+use vpp_api_encoding::wire;
 use vpp_api_message::VppApiMessage;
 
-fn get_encoder() -> impl bincode::config::Options {
-    bincode::DefaultOptions::new()
-        .with_big_endian()
-        .with_fixint_encoding()
+/* a newer VPP may have added fields at the end of the reply */
+fn decode_reply<TR: DeserializeOwned>(data: &[u8]) -> Result<TR> {
+    Ok(wire::from_bytes_prefix::<TR>(data)?.0)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,10 +52,8 @@ pub fn send_recv_one<
     let reply_name = &TR::get_message_name_and_crc();
     let vl_msg_id = t.get_msg_index(name).unwrap();
     let reply_vl_msg_id = t.get_msg_index(reply_name).unwrap();
-    let enc = get_encoder();
-    let mut v = enc.serialize(&vl_msg_id)?;
-    let enc = get_encoder();
-    let msg = enc.serialize(&m)?;
+    let mut v = wire::to_bytes(&vl_msg_id)?;
+    let msg = wire::to_bytes(&m)?;
 
     trace!(
         "About to send msg: {} id: {} reply_id: {} msg:{:x?}",
@@ -64,6 +63,7 @@ pub fn send_recv_one<
         &msg,
     );
 
+    let mut call = CallTracker::start(name, &msg, t.metrics());
     v.extend_from_slice(&msg);
     match t.write(&v) {
         Ok(i) => {
@@ -84,9 +84,8 @@ pub fn send_recv_one<
             Ok((msg_id, data)) => {
                 trace!("msg: {} id: {} data: {:x?}", name, msg_id, &data);
                 if msg_id == reply_vl_msg_id {
-                    let res = get_encoder()
-                        .allow_trailing_bytes()
-                        .deserialize::<TR>(&data)?;
+                    let res = decode_reply::<TR>(&data)?;
+                    call.reply(reply_retval(reply_name, &data));
                     return Ok(res);
                 }
             }
@@ -111,19 +110,16 @@ pub fn send_recv_many<
     let control_ping_id_reply = t.get_msg_index("control_ping_reply_f6b0b8ca").unwrap();
     let vl_msg_id = t.get_msg_index(name).unwrap();
     let reply_vl_msg_id = t.get_msg_index(reply_name).unwrap();
-    let enc = get_encoder();
-    let mut v = enc.serialize(&vl_msg_id)?;
-    let enc = get_encoder();
-    let msg = enc.serialize(&m)?;
+    let mut v = wire::to_bytes(&vl_msg_id)?;
+    let msg = wire::to_bytes(&m)?;
     let control_ping = ControlPing {
         client_index: t.get_client_index(),
         context: 0,
     };
-    let enc = get_encoder();
-    let mut c = enc.serialize(&control_ping_id)?;
-    let enc = get_encoder();
-    let control_ping_message = enc.serialize(&control_ping)?;
+    let mut c = wire::to_bytes(&control_ping_id)?;
+    let control_ping_message = wire::to_bytes(&control_ping)?;
     c.extend_from_slice(&control_ping_message);
+    let mut call = CallTracker::start(name, &msg, t.metrics());
     v.extend_from_slice(&msg);
     let mut out: Vec<u8> = vec![];
     t.write(&v); // Dump message
@@ -146,14 +142,14 @@ pub fn send_recv_many<
                 trace!("data.len: {}", data.len());
                 if msg_id == control_ping_id_reply {
                     trace!("finished. returning {:?}", out);
+                    call.reply(reply_retval(CONTROL_PING_REPLY, &data));
                     return Ok(out);
                 }
                 if msg_id == reply_vl_msg_id {
                     trace!("Received the intended message; attempt to deserialize");
-                    let res = get_encoder()
-                        .allow_trailing_bytes()
-                        .deserialize::<TR>(&data)?;
+                    let res = decode_reply::<TR>(&data)?;
                     trace!("Next thing will be the reply");
+                    call.detail();
                     out.extend_from_slice(&[res]);
                 } else {
                     trace!("Checking the next message for the reply id");
@@ -175,10 +171,8 @@ pub fn send_recv_msg<'a, T: Serialize + Deserialize<'a>, TR: Serialize + Deseria
 ) -> TR {
     let vl_msg_id = t.get_msg_index(name).unwrap();
     let reply_vl_msg_id = t.get_msg_index(reply_name).unwrap();
-    let enc = get_encoder();
-    let mut v = enc.serialize(&vl_msg_id).unwrap();
-    let enc = get_encoder();
-    let msg = enc.serialize(&m).unwrap();
+    let mut v = wire::to_bytes(&vl_msg_id).unwrap();
+    let msg = wire::to_bytes(&m).unwrap();
 
     trace!(
         "About to send msg: {} id: {} reply_id: {} msg:{:x?}",
@@ -188,6 +182,7 @@ pub fn send_recv_msg<'a, T: Serialize + Deserialize<'a>, TR: Serialize + Deseria
         &msg,
     );
 
+    let mut call = CallTracker::start(name, &msg, t.metrics());
     v.extend_from_slice(&msg);
     match t.write(&v) {
         Ok(i) => {
@@ -208,10 +203,8 @@ pub fn send_recv_msg<'a, T: Serialize + Deserialize<'a>, TR: Serialize + Deseria
         if let Ok((msg_id, data)) = res {
             trace!("msg: {} id: {} data: {:x?}", name, msg_id, &data);
             if msg_id == reply_vl_msg_id {
-                let res = get_encoder()
-                    .allow_trailing_bytes()
-                    .deserialize::<TR>(&data)
-                    .unwrap();
+                let res = decode_reply::<TR>(&data).unwrap();
+                call.reply(reply_retval(reply_name, &data));
                 return res;
             }
         } else {
@@ -233,19 +226,16 @@ pub fn send_bulk_msg<
     let control_ping_id_reply = t.get_msg_index("control_ping_reply_f6b0b8ca").unwrap();
     let vl_msg_id = t.get_msg_index(name).unwrap();
     let reply_vl_msg_id = t.get_msg_index(reply_name).unwrap();
-    let enc = get_encoder();
-    let mut v = enc.serialize(&vl_msg_id).unwrap();
-    let enc = get_encoder();
-    let msg = enc.serialize(&m).unwrap(); /////
+    let mut v = wire::to_bytes(&vl_msg_id).unwrap();
+    let msg = wire::to_bytes(&m).unwrap(); /////
     let control_ping = ControlPing {
         client_index: t.get_client_index(),
         context: 0,
     };
-    let enc = get_encoder();
-    let mut c = enc.serialize(&control_ping_id).unwrap();
-    let enc = get_encoder();
-    let control_ping_message = enc.serialize(&control_ping).unwrap();
+    let mut c = wire::to_bytes(&control_ping_id).unwrap();
+    let control_ping_message = wire::to_bytes(&control_ping).unwrap();
     c.extend_from_slice(&control_ping_message);
+    let mut call = CallTracker::start(name, &msg, t.metrics());
     v.extend_from_slice(&msg);
     let mut out: Vec<u8> = vec![];
     t.write(&v); // Dump message
@@ -268,15 +258,14 @@ pub fn send_bulk_msg<
             trace!("data.len: {}", data.len());
             if msg_id == control_ping_id_reply {
                 trace!("finished. returning {:?}", out);
+                call.reply(reply_retval(CONTROL_PING_REPLY, &data));
                 return out;
             }
             if msg_id == reply_vl_msg_id {
                 trace!("Received the intended message; attempt to deserialize");
-                let res = get_encoder()
-                    .allow_trailing_bytes()
-                    .deserialize::<TR>(&data)
-                    .unwrap();
+                let res = decode_reply::<TR>(&data).unwrap();
                 trace!("Next thing will be the reply");
+                call.detail();
                 out.extend_from_slice(&[res]);
             } else {
                 trace!("Checking the next message for the reply id");
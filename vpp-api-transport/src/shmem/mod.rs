@@ -1,8 +1,6 @@
 mod shmem_bindgen;
 use crate::error::Result;
-use bincode;
-use bincode::Options;
-use serde::{Deserialize, Serialize};
+use crate::protocol::encode_frame;
 use shmem_bindgen::*;
 use std::ffi::CString;
 
@@ -27,36 +25,12 @@ lazy_static! {
     };
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct SockMsgHeader {
-    _q: u64,
-    msglen: u32,
-    gc_mark: u32,
-}
-
-fn get_encoder() -> impl bincode::config::Options {
-    bincode::DefaultOptions::new()
-        .with_big_endian()
-        .with_fixint_encoding()
-}
-
 #[no_mangle]
 pub unsafe extern "C" fn shmem_default_cb(raw_data: *const u8, len: i32) {
     let data_slice = std::slice::from_raw_parts(raw_data, len as usize);
     let mut gs = GLOBAL.lock().unwrap();
 
-    let hdr = SockMsgHeader {
-        _q: 0,
-        msglen: data_slice.len() as u32,
-        gc_mark: 0,
-    };
-    let hs = get_encoder().serialize(&hdr).unwrap();
-    for d in hs {
-        gs.receive_buffer.push_back(d);
-    }
-    for d in data_slice {
-        gs.receive_buffer.push_back(*d);
-    }
+    gs.receive_buffer.extend(encode_frame(data_slice));
 }
 
 #[no_mangle]